@@ -0,0 +1,252 @@
+//! Фильтрация в частотной области: двумерное БПФ (radix-2) плоскости яркости,
+//! идеальные и гауссовы фильтры нижних и верхних частот, вывод спектра.
+//! Изображение дополняется нулями до степеней двойки и после обратного
+//! преобразования обрезается до исходного размера.
+
+use image::{DynamicImage, GrayImage};
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+
+    fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+}
+
+/// БПФ на месте, длина - степень двойки; обратное преобразование
+/// нормируется на длину
+fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    if n <= 1 {
+        return;
+    }
+    // Двоично-инверсная перестановка
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * std::f64::consts::PI / len as f64;
+        let step = Complex { re: angle.cos(), im: angle.sin() };
+        for chunk in data.chunks_exact_mut(len) {
+            let (low, high) = chunk.split_at_mut(len / 2);
+            let mut w = Complex { re: 1.0, im: 0.0 };
+            for (a, b) in low.iter_mut().zip(high.iter_mut()) {
+                let t = w.mul(*b);
+                *b = Complex { re: a.re - t.re, im: a.im - t.im };
+                *a = Complex { re: a.re + t.re, im: a.im + t.im };
+                w = w.mul(step);
+            }
+        }
+        len *= 2;
+    }
+
+    if inverse {
+        for v in data.iter_mut() {
+            v.re /= n as f64;
+            v.im /= n as f64;
+        }
+    }
+}
+
+/// Двумерное БПФ буфера `width`×`height` (обе - степени двойки):
+/// сначала строки, затем столбцы
+fn fft_2d(data: &mut [Complex], width: usize, height: usize, inverse: bool) {
+    for row in data.chunks_exact_mut(width) {
+        fft(row, inverse);
+    }
+    let mut column = vec![Complex::default(); height];
+    for x in 0..width {
+        for (y, v) in column.iter_mut().enumerate() {
+            *v = data[y * width + x];
+        }
+        fft(&mut column, inverse);
+        for (y, v) in column.iter().enumerate() {
+            data[y * width + x] = *v;
+        }
+    }
+}
+
+/// Спектр яркости изображения, дополненного нулями до степеней двойки
+struct Spectrum {
+    width: usize,
+    height: usize,
+    data: Vec<Complex>,
+}
+
+impl Spectrum {
+    fn new(gray_image: &GrayImage) -> Self {
+        let width = (gray_image.width() as usize).next_power_of_two();
+        let height = (gray_image.height() as usize).next_power_of_two();
+        let mut data = vec![Complex::default(); width * height];
+        for (x, y, pixel) in gray_image.enumerate_pixels() {
+            data[y as usize * width + x as usize].re = pixel[0] as f64;
+        }
+        fft_2d(&mut data, width, height, false);
+        Self { width, height, data }
+    }
+
+    /// Расстояние отсчета (u, v) до нулевой частоты с учетом периодичности
+    fn distance(&self, u: usize, v: usize) -> f64 {
+        let fu = if u < self.width / 2 { u as f64 } else { u as f64 - self.width as f64 };
+        let fv = if v < self.height / 2 { v as f64 } else { v as f64 - self.height as f64 };
+        fu.hypot(fv)
+    }
+}
+
+/// Фильтр частотной области с круглой маской радиуса среза
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrequencyFilter {
+    IdealLow,
+    IdealHigh,
+    GaussianLow,
+    GaussianHigh,
+}
+
+impl FrequencyFilter {
+    pub const ALL: [FrequencyFilter; 4] = [
+        FrequencyFilter::IdealLow,
+        FrequencyFilter::IdealHigh,
+        FrequencyFilter::GaussianLow,
+        FrequencyFilter::GaussianHigh,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FrequencyFilter::IdealLow => "Идеальный ФНЧ",
+            FrequencyFilter::IdealHigh => "Идеальный ФВЧ",
+            FrequencyFilter::GaussianLow => "Гауссов ФНЧ",
+            FrequencyFilter::GaussianHigh => "Гауссов ФВЧ",
+        }
+    }
+
+    fn is_high_pass(self) -> bool {
+        matches!(self, FrequencyFilter::IdealHigh | FrequencyFilter::GaussianHigh)
+    }
+
+    /// Коэффициент передачи на расстоянии `d` от нулевой частоты
+    fn gain(self, d: f64, cutoff: f64) -> f64 {
+        let low = match self {
+            FrequencyFilter::IdealLow | FrequencyFilter::IdealHigh => {
+                if d <= cutoff { 1.0 } else { 0.0 }
+            }
+            FrequencyFilter::GaussianLow | FrequencyFilter::GaussianHigh => {
+                (-(d * d) / (2.0 * cutoff * cutoff)).exp()
+            }
+        };
+        if self.is_high_pass() { 1.0 - low } else { low }
+    }
+}
+
+/// Фильтрация яркости в частотной области. После ФНЧ значения обрезаются
+/// к 0..255; результат ФВЧ знакопеременный и растягивается на 0..255.
+pub fn apply_frequency_filter(image: &DynamicImage, filter: FrequencyFilter, cutoff: f32) -> DynamicImage {
+    let gray_image = image.to_luma8();
+    let (width, height) = gray_image.dimensions();
+    if width == 0 || height == 0 {
+        return DynamicImage::ImageLuma8(gray_image);
+    }
+    let mut spectrum = Spectrum::new(&gray_image);
+    let cutoff = cutoff.max(f32::EPSILON) as f64;
+    for v in 0..spectrum.height {
+        for u in 0..spectrum.width {
+            let gain = filter.gain(spectrum.distance(u, v), cutoff);
+            let value = &mut spectrum.data[v * spectrum.width + u];
+            value.re *= gain;
+            value.im *= gain;
+        }
+    }
+    fft_2d(&mut spectrum.data, spectrum.width, spectrum.height, true);
+
+    let values: Vec<f64> = (0..height as usize)
+        .flat_map(|y| (0..width as usize).map(move |x| (x, y)))
+        .map(|(x, y)| spectrum.data[y * spectrum.width + x].re)
+        .collect();
+    let (min, max) = values.iter().fold((f64::MAX, f64::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    let data = values
+        .iter()
+        .map(|&v| {
+            let v = if filter.is_high_pass() && max > min { (v - min) / (max - min) * 255.0 } else { v };
+            v.round().clamp(0.0, 255.0) as u8
+        })
+        .collect();
+    DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, data).unwrap())
+}
+
+/// Логарифм модуля спектра ln(1 + |F|), сдвинутый так, что нулевая частота
+/// в центре, и нормированный к 0..255. Размер - дополненный до степеней двойки.
+pub fn spectrum_image(image: &DynamicImage) -> DynamicImage {
+    let gray_image = image.to_luma8();
+    if gray_image.width() == 0 || gray_image.height() == 0 {
+        return DynamicImage::ImageLuma8(gray_image);
+    }
+    let spectrum = Spectrum::new(&gray_image);
+    let (width, height) = (spectrum.width, spectrum.height);
+    let magnitude: Vec<f64> = spectrum.data.iter().map(|c| c.norm().ln_1p()).collect();
+    let max = magnitude.iter().copied().fold(0.0, f64::max);
+    let scale = if max > 0.0 { 255.0 / max } else { 0.0 };
+
+    let shifted = GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        let u = (x as usize + width / 2) % width;
+        let v = (y as usize + height / 2) % height;
+        image::Luma([(magnitude[v * width + u] * scale).round() as u8])
+    });
+    DynamicImage::ImageLuma8(shifted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Изображение 13×7 - размеры не степени двойки
+    fn sample() -> GrayImage {
+        GrayImage::from_fn(13, 7, |x, y| image::Luma([((x * 37 + y * 91) % 256) as u8]))
+    }
+
+    #[test]
+    fn round_trip_reconstructs_image() {
+        let gray_image = sample();
+        let mut spectrum = Spectrum::new(&gray_image);
+        assert_eq!((spectrum.width, spectrum.height), (16, 8));
+        fft_2d(&mut spectrum.data, spectrum.width, spectrum.height, true);
+        for (x, y, pixel) in gray_image.enumerate_pixels() {
+            let value = spectrum.data[y as usize * spectrum.width + x as usize].re;
+            assert!((value - pixel[0] as f64).abs() <= 1.0, "({x}, {y}): {value}");
+        }
+    }
+
+    #[test]
+    fn wide_low_pass_keeps_image() {
+        let gray_image = sample();
+        let image = DynamicImage::ImageLuma8(gray_image.clone());
+        let filtered = apply_frequency_filter(&image, FrequencyFilter::IdealLow, 1e6);
+        for (a, b) in filtered.to_luma8().iter().zip(gray_image.iter()) {
+            assert!(a.abs_diff(*b) <= 1, "{b} -> {a}");
+        }
+    }
+
+    #[test]
+    fn spectrum_of_flat_image_peaks_at_center() {
+        let flat = DynamicImage::ImageLuma8(GrayImage::from_pixel(8, 4, image::Luma([100])));
+        let spectrum = spectrum_image(&flat).to_luma8();
+        assert_eq!(spectrum[(4, 2)][0], 255);
+        assert_eq!(spectrum.iter().filter(|&&v| v > 0).count(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+//! Загрузка библиотеки ядер свертки из текстового файла.
+//!
+//! Каждое ядро начинается с заголовка `[Название]`, за ним необязательные
+//! строки `size W H`, `divisor D` и `offset O` и строки матрицы весов:
+//!
+//! ```text
+//! # Гауссово 3×3
+//! [Гаусс]
+//! divisor 16
+//! 1 2 1
+//! 2 4 2
+//! 1 2 1
+//! ```
+//!
+//! Без `divisor` делителем служит сумма весов (1, если она нулевая).
+
+use std::path::Path;
+
+use crate::processing::Kernel;
+
+pub fn load_kernels(path: &Path) -> Result<Vec<Kernel>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("не удалось прочитать файл: {e}"))?;
+    parse_kernels(&text)
+}
+
+/// Ядро в процессе чтения
+struct Draft {
+    name: String,
+    /// Строка заголовка - для сообщений об ошибках
+    line: usize,
+    size: Option<(usize, usize)>,
+    divisor: Option<f32>,
+    offset: f32,
+    rows: Vec<Vec<f32>>,
+}
+
+impl Draft {
+    fn finish(self) -> Result<Kernel, String> {
+        let error = |message: String| format!("ядро \"{}\" (строка {}): {message}", self.name, self.line);
+        let height = self.rows.len();
+        if height == 0 {
+            return Err(error("нет строк с весами".to_string()));
+        }
+        let width = self.rows[0].len();
+        if let Some(row) = self.rows.iter().position(|r| r.len() != width) {
+            let count = self.rows[row].len();
+            return Err(error(format!("строка {} матрицы содержит {count} значений вместо {width}", row + 1)));
+        }
+        if let Some((w, h)) = self.size
+            && (w, h) != (width, height)
+        {
+            return Err(error(format!("размер {w}×{h} не совпадает с матрицей {width}×{height}")));
+        }
+        if width.is_multiple_of(2) || height.is_multiple_of(2) {
+            return Err(error(format!("размеры ядра должны быть нечетными, получено {width}×{height}")));
+        }
+
+        let weights: Vec<f32> = self.rows.into_iter().flatten().collect();
+        let divisor = self.divisor.unwrap_or_else(|| {
+            let sum: f32 = weights.iter().sum();
+            if sum == 0.0 { 1.0 } else { sum }
+        });
+        if divisor == 0.0 {
+            return Err(error("делитель равен нулю".to_string()));
+        }
+        Ok(Kernel { name: self.name, width, height, weights, divisor, offset: self.offset })
+    }
+}
+
+pub fn parse_kernels(text: &str) -> Result<Vec<Kernel>, String> {
+    let mut kernels = Vec::new();
+    let mut current: Option<Draft> = None;
+
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("строка {}: {message}", line_number + 1);
+        // Конечное число: NaN и бесконечности отвергаются
+        let number = |token: &str| {
+            token
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| error(&format!("\"{token}\" - не число")))
+        };
+
+        if let Some(name) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            if let Some(draft) = current.take() {
+                kernels.push(draft.finish()?);
+            }
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(error("пустое название ядра"));
+            }
+            current = Some(Draft {
+                name: name.to_string(),
+                line: line_number + 1,
+                size: None,
+                divisor: None,
+                offset: 0.0,
+                rows: Vec::new(),
+            });
+            continue;
+        }
+
+        let draft = current.as_mut().ok_or_else(|| error("ожидался заголовок [Название]"))?;
+        let mut tokens = line.split_whitespace();
+        let first = tokens.next().unwrap_or_default();
+        let rest: Vec<&str> = tokens.collect();
+        match (first, rest.as_slice()) {
+            ("size", [w, h]) => {
+                let parse = |t: &str| t.parse::<usize>().ok().filter(|&n| n > 0);
+                let (Some(w), Some(h)) = (parse(w), parse(h)) else {
+                    return Err(error("некорректный размер"));
+                };
+                draft.size = Some((w, h));
+            }
+            ("divisor", [d]) => draft.divisor = Some(number(d)?),
+            ("offset", [o]) => draft.offset = number(o)?,
+            ("size" | "divisor" | "offset", _) => return Err(error(&format!("неверное число аргументов {first}"))),
+            _ => {
+                let row = line.split_whitespace().map(number).collect::<Result<Vec<_>, _>>()?;
+                draft.rows.push(row);
+            }
+        }
+    }
+
+    if let Some(draft) = current {
+        kernels.push(draft.finish()?);
+    }
+    if kernels.is_empty() {
+        return Err("в файле нет ядер".to_string());
+    }
+    Ok(kernels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_kernels_with_defaults() {
+        let text = "# библиотека\n[Гаусс]\ndivisor 16\n1 2 1\n2 4 2\n1 2 1\n\n[Рельеф]\nsize 3 1\noffset 128\n-1 0 1\n";
+        let kernels = parse_kernels(text).unwrap();
+        assert_eq!(kernels.len(), 2);
+        assert_eq!(kernels[0].name, "Гаусс");
+        assert_eq!((kernels[0].width, kernels[0].height, kernels[0].divisor), (3, 3, 16.0));
+        // Сумма весов нулевая - делитель 1
+        assert_eq!(
+            kernels[1],
+            Kernel {
+                name: "Рельеф".to_string(),
+                width: 3,
+                height: 1,
+                weights: vec![-1.0, 0.0, 1.0],
+                divisor: 1.0,
+                offset: 128.0
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_kernels() {
+        let cases = [
+            ("[A]\n1 2 1\n2 4\n1 2 1\n", "строка 2 матрицы содержит 2 значений вместо 3"),
+            ("[A]\n1 1\n1 1\n", "должны быть нечетными"),
+            ("[A]\ndivisor 0\n1 2 1\n", "делитель равен нулю"),
+            ("[A]\n1 NaN 1\n", "\"NaN\" - не число"),
+            ("[A]\n1 inf 1\n", "\"inf\" - не число"),
+            ("[A]\nsize 5 5\n1 2 1\n", "не совпадает с матрицей"),
+            ("[A]\n", "нет строк с весами"),
+            ("1 2 1\n", "ожидался заголовок"),
+            ("[ ]\n1\n", "пустое название"),
+            ("# пусто\n", "в файле нет ядер"),
+        ];
+        for (text, message) in cases {
+            let error = parse_kernels(text).unwrap_err();
+            assert!(error.contains(message), "{text:?}: {error}");
+        }
+    }
+}
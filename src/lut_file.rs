@@ -0,0 +1,154 @@
+//! Загрузка одномерных LUT из файлов: CSV из 256 значений или формат .cube (1D)
+
+use std::path::Path;
+
+use crate::processing::Lut;
+
+/// Читает LUT, формат выбирается по расширению файла
+pub fn load_lut(path: &Path) -> Result<Lut, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("не удалось прочитать файл: {e}"))?;
+    let is_cube = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cube"));
+    if is_cube { parse_cube_lut(&text) } else { parse_csv_lut(&text) }
+}
+
+/// Ровно 256 целых значений 0..=255, разделенных запятыми, точками
+/// с запятой или пробельными символами
+pub fn parse_csv_lut(text: &str) -> Result<Lut, String> {
+    let mut values = Vec::with_capacity(256);
+    for (line_number, line) in text.lines().enumerate() {
+        for token in line.split([',', ';', ' ', '\t']).filter(|t| !t.is_empty()) {
+            let value: u16 = token
+                .parse()
+                .map_err(|_| format!("строка {}: \"{token}\" - не целое число", line_number + 1))?;
+            if value > 255 {
+                return Err(format!("строка {}: значение {value} вне диапазона 0..=255", line_number + 1));
+            }
+            values.push(value as u8);
+        }
+    }
+    values
+        .try_into()
+        .map_err(|values: Vec<u8>| format!("ожидалось 256 значений, найдено {}", values.len()))
+}
+
+/// Одномерный .cube: заголовок LUT_1D_SIZE, необязательные DOMAIN_MIN/MAX и
+/// строки "r g b". Кривые каналов должны совпадать; таблица любого размера
+/// пересчитывается в 256 значений линейной интерполяцией.
+pub fn parse_cube_lut(text: &str) -> Result<Lut, String> {
+    let mut size = None;
+    let mut domain = (0.0f32, 1.0f32);
+    let mut entries: Vec<f32> = Vec::new();
+
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("строка {}: {message}", line_number + 1);
+        let mut tokens = line.split_whitespace();
+        let first = tokens.next().unwrap_or_default();
+
+        match first {
+            "TITLE" => {}
+            "LUT_3D_SIZE" => return Err(error("трехмерные LUT не поддерживаются")),
+            "LUT_1D_SIZE" => {
+                let value: usize = tokens
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .filter(|&n| n >= 2)
+                    .ok_or_else(|| error("некорректный LUT_1D_SIZE"))?;
+                size = Some(value);
+            }
+            "DOMAIN_MIN" | "DOMAIN_MAX" | "LUT_1D_INPUT_RANGE" => {
+                let numbers: Vec<f32> = tokens
+                    .map(|t| t.parse().map_err(|_| error("ожидалось число")))
+                    .collect::<Result<_, _>>()?;
+                let (low, high) = match (first, numbers.as_slice()) {
+                    ("LUT_1D_INPUT_RANGE", [low, high]) => (*low, *high),
+                    ("DOMAIN_MIN", [v, ..]) => (*v, domain.1),
+                    ("DOMAIN_MAX", [v, ..]) => (domain.0, *v),
+                    _ => return Err(error("некорректная область определения")),
+                };
+                domain = (low, high);
+            }
+            _ => {
+                let numbers: Vec<f32> = line
+                    .split_whitespace()
+                    .map(|t| t.parse().map_err(|_| error(&format!("\"{t}\" - не число"))))
+                    .collect::<Result<_, _>>()?;
+                let [r, g, b] = numbers[..] else {
+                    return Err(error("ожидалось три значения"));
+                };
+                if r != g || g != b {
+                    return Err(error("разные кривые для каналов не поддерживаются"));
+                }
+                entries.push(r);
+            }
+        }
+    }
+
+    let size = size.ok_or("нет заголовка LUT_1D_SIZE")?;
+    if entries.len() != size {
+        return Err(format!("ожидалось {size} строк данных, найдено {}", entries.len()));
+    }
+    if domain.1 <= domain.0 {
+        return Err("некорректная область определения".to_string());
+    }
+    if let Some(v) = entries.iter().find(|v| !(domain.0..=domain.1).contains(*v)) {
+        return Err(format!("значение {v} вне области определения"));
+    }
+
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        let position = i as f32 / 255.0 * (size - 1) as f32;
+        let k = (position as usize).min(size - 2);
+        let t = position - k as f32;
+        let value = entries[k] * (1.0 - t) + entries[k + 1] * t;
+        let normalized = (value - domain.0) / (domain.1 - domain.0);
+        *entry = (normalized * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    Ok(lut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Lut {
+        std::array::from_fn(|i| i as u8)
+    }
+
+    #[test]
+    fn csv_lut_accepts_any_separators() {
+        let text: String = (0..256).map(|i| format!("{i}{}", [",", ";", " ", "\n"][i % 4])).collect();
+        assert_eq!(parse_csv_lut(&text), Ok(identity()));
+    }
+
+    #[test]
+    fn csv_lut_reports_malformed_input() {
+        let short: String = (0..255).map(|i| format!("{i}\n")).collect();
+        assert_eq!(parse_csv_lut(&short), Err("ожидалось 256 значений, найдено 255".to_string()));
+        assert!(parse_csv_lut("1,2,300").unwrap_err().contains("вне диапазона"));
+        assert!(parse_csv_lut("1\nабв").unwrap_err().starts_with("строка 2"));
+    }
+
+    #[test]
+    fn cube_lut_is_resampled_to_256_entries() {
+        let text = "TITLE \"inverse\"\n# comment\nLUT_1D_SIZE 2\n1.0 1.0 1.0\n0.0 0.0 0.0\n";
+        let inverted: Lut = std::array::from_fn(|i| 255 - i as u8);
+        assert_eq!(parse_cube_lut(text), Ok(inverted));
+        let ranged = "LUT_1D_SIZE 3\nLUT_1D_INPUT_RANGE 0 2\n0 0 0\n1 1 1\n2 2 2\n";
+        assert_eq!(parse_cube_lut(ranged), Ok(identity()));
+    }
+
+    #[test]
+    fn cube_lut_reports_malformed_input() {
+        assert!(parse_cube_lut("LUT_3D_SIZE 17\n").unwrap_err().contains("трехмерные"));
+        assert_eq!(parse_cube_lut("0 0 0\n1 1 1\n"), Err("нет заголовка LUT_1D_SIZE".to_string()));
+        assert!(parse_cube_lut("LUT_1D_SIZE 2\n0 0 0\n").unwrap_err().contains("найдено 1"));
+        assert!(parse_cube_lut("LUT_1D_SIZE 2\n0 0.5 0\n1 1 1\n").unwrap_err().contains("разные кривые"));
+        assert!(parse_cube_lut("LUT_1D_SIZE 2\n0 0 0\n1.5 1.5 1.5\n").unwrap_err().contains("вне области"));
+    }
+}
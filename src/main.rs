@@ -1,170 +1,133 @@
+mod bitmap_font;
+mod fft;
+mod kernel_file;
+mod lut_file;
+mod operation;
+mod processing;
+
 use std::ops::Deref;
 use eframe::egui;
-use image::{DynamicImage, GenericImageView};
+use image::{DynamicImage, GenericImageView, GrayImage};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-fn rgb_to_hsv(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
-    let r_ = r as f32 / 255.0;
-    let g_ = g as f32 / 255.0;
-    let b_ = b as f32 / 255.0;
-
-    let c_max = r_.max(g_).max(b_);
-    let c_min = r_.min(g_).min(b_);
-    let delta = c_max - c_min;
-    
-    let hue = if delta == 0.0 {
-        0.0
-    } else if c_max == r_ {
-        60.0 * (((g_ - b_) / delta) % 6.0)
-    } else if c_max == g_ {
-        60.0 * (((b_ - r_) / delta) + 2.0)
-    } else { // c_max == b_
-        60.0 * (((r_ - g_) / delta) + 4.0)
-    };
-    let h = if hue < 0.0 { hue + 360.0 } else { hue };
-    
-    let s = if c_max == 0.0 { 0.0 } else { delta / c_max };
-    
-    let v = c_max;
-
-    (h, s, v)
-}
-
-fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
-    let c = v * s;
-    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
-    let m = v - c;
-
-    let (r_, g_, b_) = if (0.0..60.0).contains(&h) {
-        (c, x, 0.0)
-    } else if (60.0..120.0).contains(&h) {
-        (x, c, 0.0)
-    } else if (120.0..180.0).contains(&h) {
-        (0.0, c, x)
-    } else if (180.0..240.0).contains(&h) {
-        (0.0, x, c)
-    } else if (240.0..300.0).contains(&h) {
-        (x, 0.0, c)
-    } else { // 300.0..360.0
-        (c, 0.0, x)
-    };
-
-    let r = ((r_ + m) * 255.0) as u8;
-    let g = ((g_ + m) * 255.0) as u8;
-    let b = ((b_ + m) * 255.0) as u8;
+use operation::{OpOutput, Operation, Source};
+use processing::{apply_mask, apply_perspective, compute_quality_metrics, compute_stats, dominant_colors, ImageStats};
 
-    (r, g, b)
-}
-
-fn apply_linear_contrast(image: &DynamicImage) -> DynamicImage {
-    let mut img = image.to_rgb8();
-    
-    let mut min_v: f32 = 1.0;
-    let mut max_v: f32 = 0.0;
-
-    for pixel in img.pixels() {
-        let (_, _, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
-        min_v = min_v.min(v);
-        max_v = max_v.max(v);
-    }
-    
-    for pixel in img.pixels_mut() {
-        let (h, s, mut v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
-        
-        if max_v > min_v {
-            v = (v - min_v) / (max_v - min_v);
-        }
-
-        let (r, g, b) = hsv_to_rgb(h, s, v);
-        pixel[0] = r;
-        pixel[1] = g;
-        pixel[2] = b;
+/// Возвращает статистику изображения, пересчитывая ее только при смене изображения
+fn cached_stats<'a>(cache: &'a mut Option<(Arc<DynamicImage>, ImageStats)>, image: &Arc<DynamicImage>) -> &'a ImageStats {
+    let is_fresh = matches!(cache, Some((cached, _)) if Arc::ptr_eq(cached, image));
+    if !is_fresh {
+        *cache = Some((image.clone(), compute_stats(image)));
     }
-
-    DynamicImage::ImageRgb8(img)
+    &cache.as_ref().expect("кэш только что заполнен").1
 }
 
-fn apply_manual_threshold(image: &DynamicImage, threshold: u8) -> DynamicImage {
-    let mut gray_image = image.to_luma8();
-    
-    for pixel in gray_image.pixels_mut() {
-        if pixel[0] > threshold {
-            pixel[0] = 255; // Белый
-        } else {
-            pixel[0] = 0;   // Черный
+fn stats_grid(ui: &mut egui::Ui, id: &str, stats: &ImageStats) {
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for header in ["Канал", "Среднее", "СКО", "Мин", "Макс", "Медиана", "Энтропия"] {
+            ui.strong(header);
         }
-    }
+        ui.end_row();
+        for (name, s) in stats.channels() {
+            ui.label(name);
+            ui.label(format!("{:.2}", s.mean));
+            ui.label(format!("{:.2}", s.std_dev));
+            ui.label(s.min.to_string());
+            ui.label(s.max.to_string());
+            ui.label(s.median.to_string());
+            ui.label(format!("{:.3}", s.entropy));
+            ui.end_row();
+        }
+    });
+}
 
-    DynamicImage::ImageLuma8(gray_image)
+/// Запись журнала обработки: операция, ее параметры и время применения
+struct LogEntry {
+    /// Секунды от начала эпохи UNIX
+    timestamp: u64,
+    name: String,
+    params: Vec<(&'static str, String)>,
 }
 
-fn apply_otsu_threshold(image: &DynamicImage) -> DynamicImage {
-    let gray_image = image.to_luma8();
-    let pixels = gray_image.as_raw();
-    
-    let mut histogram = [0u64; 256];
-    for &p in pixels {
-        histogram[p as usize] += 1;
+impl LogEntry {
+    fn new(name: &str, params: Vec<(&'static str, String)>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self { timestamp, name: name.to_string(), params }
     }
 
-    let total_pixels = pixels.len() as u64;
-    if total_pixels == 0 {
-        return image.clone();
+    /// Время в формате ЧЧ:ММ:СС (UTC)
+    fn time_of_day(&self) -> String {
+        let secs = self.timestamp % 86_400;
+        format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
     }
-    
-    let mut sum = 0.0;
-    for (i, &h) in histogram.iter().enumerate() {
-        sum += (i as f64) * (h as f64);
-    }
-
-    let mut sum_b = 0.0;
-    let mut w_b = 0.0;
-    let mut w_f;
-
-    let mut max_variance = 0.0;
-    let mut optimal_threshold = 0;
-
-    for t in 0..256 {
-        w_b += histogram[t] as f64;
-        if w_b == 0.0 { continue; }
-
-        w_f = (total_pixels as f64) - w_b;
-        if w_f == 0.0 { break; }
 
-        sum_b += (t as f64) * (histogram[t] as f64);
-
-        let mean_b = sum_b / w_b;
-        let mean_f = (sum - sum_b) / w_f;
-        
-        let variance = w_b * w_f * (mean_b - mean_f).powi(2);
-        
-        if variance > max_variance {
-            max_variance = variance;
-            optimal_threshold = t as u8;
+    fn describe(&self) -> String {
+        if self.params.is_empty() {
+            return self.name.clone();
         }
+        let params: Vec<String> = self.params.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        format!("{}: {}", self.name, params.join(", "))
     }
-
-    apply_manual_threshold(image, optimal_threshold)
 }
 
-fn apply_inversion(image: &DynamicImage) -> DynamicImage {
-    let mut img = image.to_rgb8();
-    for pixel in img.pixels_mut() {
-        pixel[0] = 255 - pixel[0]; // R
-        pixel[1] = 255 - pixel[1]; // G
-        pixel[2] = 255 - pixel[2]; // B
+fn log_as_text(source: Option<&Path>, dimensions: (u32, u32), log: &[LogEntry]) -> String {
+    let source = source.map(|p| p.display().to_string()).unwrap_or_else(|| "-".to_string());
+    let mut text = format!("Источник: {source}\nРазмер: {}x{}\nОперации:\n", dimensions.0, dimensions.1);
+    for (i, entry) in log.iter().enumerate() {
+        text += &format!("{}. [{} UTC] {}\n", i + 1, entry.time_of_day(), entry.describe());
     }
-    DynamicImage::ImageRgb8(img)
+    text
 }
 
-fn apply_brightness(image: &DynamicImage, value: i16) -> DynamicImage {
-    let mut img = image.to_rgb8();
-    for pixel in img.pixels_mut() {
-        for i in 0..3 {
-            pixel[i] = ((pixel[i] as i16 + value).clamp(0, 255)) as u8;
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
         }
     }
-    DynamicImage::ImageRgb8(img)
+    out
+}
+
+fn log_as_json(source: Option<&Path>, dimensions: (u32, u32), log: &[LogEntry]) -> String {
+    let source = match source {
+        Some(p) => format!("\"{}\"", json_escape(&p.display().to_string())),
+        None => "null".to_string(),
+    };
+    let operations: Vec<String> = log
+        .iter()
+        .map(|entry| {
+            let params: Vec<String> = entry
+                .params
+                .iter()
+                .map(|(k, v)| format!("\"{}\": \"{}\"", json_escape(k), json_escape(v)))
+                .collect();
+            format!(
+                "    {{\"timestamp\": {}, \"name\": \"{}\", \"params\": {{{}}}}}",
+                entry.timestamp,
+                json_escape(&entry.name),
+                params.join(", ")
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"source\": {},\n  \"width\": {},\n  \"height\": {},\n  \"operations\": [\n{}\n  ]\n}}\n",
+        source,
+        dimensions.0,
+        dimensions.1,
+        operations.join(",\n")
+    )
 }
 
 struct ImageApp {
@@ -172,62 +135,504 @@ struct ImageApp {
     processed_image: Option<Arc<DynamicImage>>,
     original_texture: Option<egui::TextureHandle>,
     processed_texture: Option<egui::TextureHandle>,
-    manual_threshold_value: u8,
-    manual_brightness_value: i16,
+    /// Масштаб и сдвиг просмотра оригинала и результата; при связанных
+    /// видах обе панели выводятся по виду оригинала
+    original_view: ViewState,
+    processed_view: ViewState,
+    link_views: bool,
+    /// Растягивать маленькие изображения по области просмотра больше 100%
+    stretch_small: bool,
+    /// Маска применения операций (0 - оставить исходное, 255 - результат)
+    mask: Option<Arc<GrayImage>>,
+    use_mask: bool,
+    /// Применять операции к текущему результату, а не к оригиналу
+    chain: bool,
+    /// Предпросмотр при изменении параметров: результат еще не применен
+    live_preview: bool,
+    preview: Option<Arc<DynamicImage>>,
+    preview_texture: Option<egui::TextureHandle>,
+    /// Операция с измененными параметрами, ждущая пересчета
+    pending_preview: Option<Operation>,
+    /// Время последнего пересчета и его длительность, с
+    preview_time: f64,
+    preview_cost: f64,
+    /// История результатов для отмены и повтора; первый шаг - исходное изображение
+    history: Vec<HistoryStep>,
+    history_position: usize,
+    /// Сколько шагов назад можно отменить
+    history_depth: usize,
+    /// Второе изображение для смешивания и его миниатюра
+    second_image: Option<Arc<DynamicImage>>,
+    second_texture: Option<egui::TextureHandle>,
+    /// Операции панели алгоритмов вместе с текущими значениями параметров
+    operations: Vec<Operation>,
+    /// Последний отчет каждой операции (пороги, число компонент), по индексу в `operations`
+    reports: Vec<Option<String>>,
+    /// Палитра последнего квантования, пока результат не заменен
+    palette: Option<Vec<([u8; 3], f32)>>,
+    /// Число доминирующих цветов для анализа "Палитра"
+    palette_size: usize,
+    /// Ошибка последней операции
+    error: Option<String>,
+    /// Метрики качества результата, посчитанные по кнопке
+    metrics: Option<String>,
+    /// Режим выделения: перетаскивание по оригиналу задает область обрезки
+    select_mode: bool,
+    /// Выделенная область в координатах пикселей оригинала
+    selection: Option<egui::Rect>,
+    /// Точка начала перетаскивания в координатах пикселей оригинала
+    selection_anchor: Option<egui::Pos2>,
+    /// Режим перспективы: на оригинале перетаскиваются четыре угла
+    perspective_mode: bool,
+    /// Углы четырехугольника в координатах пикселей оригинала: левый верхний,
+    /// правый верхний, правый нижний, левый нижний
+    perspective_quad: Option<[egui::Pos2; 4]>,
+    /// Перетаскиваемый угол
+    perspective_handle: Option<usize>,
+    show_histogram: bool,
+    show_rgb_histogram: bool,
+    /// Отмечать ли на гистограммах ручной порог - после бинаризации по порогу
+    threshold_marker: bool,
+    original_stats: Option<(Arc<DynamicImage>, ImageStats)>,
+    processed_stats: Option<(Arc<DynamicImage>, ImageStats)>,
+    source_path: Option<PathBuf>,
+    processing_log: Vec<LogEntry>,
+    save_log: bool,
+    log_as_json: bool,
+    texture_smoothing: TextureSmoothing,
 }
 
 impl Default for ImageApp {
     fn default() -> Self {
+        let operations = Operation::defaults();
         Self {
             original_image: None,
             processed_image: None,
             original_texture: None,
             processed_texture: None,
-            manual_threshold_value: 128,
-            manual_brightness_value: 0,
+            original_view: ViewState::default(),
+            processed_view: ViewState::default(),
+            link_views: true,
+            stretch_small: false,
+            mask: None,
+            use_mask: false,
+            chain: true,
+            live_preview: true,
+            preview: None,
+            preview_texture: None,
+            pending_preview: None,
+            preview_time: 0.0,
+            preview_cost: 0.0,
+            history: Vec::new(),
+            history_position: 0,
+            history_depth: 30,
+            second_image: None,
+            second_texture: None,
+            reports: vec![None; operations.len()],
+            operations,
+            palette: None,
+            palette_size: 5,
+            error: None,
+            metrics: None,
+            select_mode: false,
+            selection: None,
+            selection_anchor: None,
+            perspective_mode: false,
+            perspective_quad: None,
+            perspective_handle: None,
+            show_histogram: false,
+            show_rgb_histogram: false,
+            threshold_marker: false,
+            original_stats: None,
+            processed_stats: None,
+            source_path: None,
+            processing_log: Vec::new(),
+            save_log: false,
+            log_as_json: false,
+            texture_smoothing: TextureSmoothing::Auto,
         }
     }
 }
 
+impl ImageApp {
+    /// Перетаскивание по оригиналу в режиме выделения: прямоугольник
+    /// от точки нажатия до указателя, зажатый в границах изображения;
+    /// правый щелчок снимает выделение. `image_rect` - место всего изображения на экране
+    fn track_selection(&mut self, response: &egui::Response, image_rect: egui::Rect, dimensions: (u32, u32)) {
+        if response.secondary_clicked() {
+            self.selection = None;
+            self.selection_anchor = None;
+            return;
+        }
+        let to_image = |pos| screen_to_image(image_rect, pos, dimensions);
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            self.selection_anchor = response.interact_pointer_pos().map(to_image);
+        }
+        if response.dragged_by(egui::PointerButton::Primary)
+            && let (Some(anchor), Some(pos)) = (self.selection_anchor, response.interact_pointer_pos())
+        {
+            self.selection = Some(egui::Rect::from_two_pos(anchor, to_image(pos)));
+        }
+        if response.drag_stopped() {
+            self.selection_anchor = None;
+        }
+    }
+
+    /// Перетаскивание углов перспективы; при первом включении углы
+    /// ставятся с отступом 10% от краев
+    fn track_perspective(&mut self, response: &egui::Response, image_rect: egui::Rect, dimensions: (u32, u32)) {
+        let (width, height) = (dimensions.0 as f32, dimensions.1 as f32);
+        let quad = self.perspective_quad.get_or_insert_with(|| {
+            let (dx, dy) = (width * 0.1, height * 0.1);
+            [
+                egui::pos2(dx, dy),
+                egui::pos2(width - dx, dy),
+                egui::pos2(width - dx, height - dy),
+                egui::pos2(dx, height - dy),
+            ]
+        });
+        if response.drag_started_by(egui::PointerButton::Primary)
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            // Захватывается ближайший к указателю угол в пределах маркера
+            self.perspective_handle = (0..4)
+                .map(|i| (i, image_to_screen(image_rect, quad[i], dimensions).distance(pos)))
+                .filter(|&(_, distance)| distance <= HANDLE_RADIUS * 2.0)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(i, _)| i);
+        }
+        if response.dragged_by(egui::PointerButton::Primary)
+            && let (Some(handle), Some(pos)) = (self.perspective_handle, response.interact_pointer_pos())
+        {
+            quad[handle] = screen_to_image(image_rect, pos, dimensions);
+        }
+        if response.drag_stopped() {
+            self.perspective_handle = None;
+        }
+    }
+
+    /// Выпрямляет выделенный углами четырехугольник оригинала в прямоугольник
+    fn rectify_perspective(&mut self) {
+        let (Some(original), Some(quad)) = (self.original_image.clone(), self.perspective_quad) else {
+            return;
+        };
+        match apply_perspective(&original, quad.map(|corner| (corner.x, corner.y))) {
+            Ok(result) => {
+                self.palette = None;
+                self.error = None;
+                let corners: Vec<String> = quad.iter().map(|c| format!("({:.0}, {:.0})", c.x, c.y)).collect();
+                self.set_processed(result, "Перспектива", vec![("углы", corners.join(", "))]);
+            }
+            Err(message) => self.error = Some(format!("Перспектива: {message}")),
+        }
+    }
+
+    /// Обрезает оригинал по выделению; результат становится обработанным изображением
+    fn crop_to_selection(&mut self) {
+        let Some(original) = self.original_image.clone() else {
+            return;
+        };
+        let Some(selection) = self.selection else {
+            self.error = Some("Обрезка: область не выделена".to_string());
+            return;
+        };
+        let (x, y, width, height) = selection_pixels(selection);
+        if width == 0 || height == 0 {
+            self.error = Some("Обрезка: выделена пустая область".to_string());
+            return;
+        }
+        self.palette = None;
+        self.error = None;
+        let params = vec![("область", format!("{width}×{height} от ({x}, {y})"))];
+        self.set_processed(original.crop_imm(x, y, width, height), "Обрезка", params);
+    }
+
+    /// Сохраняет результат операции и записывает ее в журнал
+    fn set_processed(&mut self, result: DynamicImage, name: &str, params: Vec<(&'static str, String)>) {
+        let result = Arc::new(result);
+        self.clear_preview();
+        self.processed_image = Some(result.clone());
+        self.processed_texture = None;
+        self.metrics = None;
+        self.processing_log.push(LogEntry::new(name, params));
+
+        // Новая операция посреди истории отбрасывает отмененные шаги
+        self.history.truncate(self.history_position + 1);
+        self.history.push(HistoryStep { image: result, description: name.to_string() });
+        let excess = self.history.len().saturating_sub(self.history_depth + 1);
+        self.history.drain(..excess);
+        self.history_position = self.history.len() - 1;
+    }
+
+    /// История начинается заново с исходного изображения
+    fn reset_history(&mut self) {
+        self.history.clear();
+        self.history_position = 0;
+        if let Some(original) = &self.original_image {
+            self.history.push(HistoryStep { image: original.clone(), description: "Исходное".to_string() });
+        }
+    }
+
+    /// Переход по истории на `step` шагов (отмена - назад, повтор - вперед)
+    fn step_history(&mut self, step: isize) {
+        let Some(position) = self.history_position.checked_add_signed(step).filter(|&p| p < self.history.len()) else {
+            return;
+        };
+        self.history_position = position;
+        self.clear_preview();
+        self.processed_image = Some(self.history[position].image.clone());
+        self.processed_texture = None;
+        self.palette = None;
+        self.metrics = None;
+        let name = if step < 0 { "Отмена" } else { "Повтор" };
+        let description = self.history[position].description.clone();
+        self.processing_log.push(LogEntry::new(name, vec![("шаг", description)]));
+    }
+
+    /// Единственный путь применения операций: выполнение, обновление
+    /// результата, журнал и сообщение об ошибке
+    fn run(&mut self, index: usize, operation: Operation) {
+        self.clear_preview();
+        let Some((source, original)) = self.inputs(&operation) else {
+            return;
+        };
+
+        match operation.execute(&source, &original).and_then(|output| self.masked(output, &source)) {
+            Ok(output) => {
+                let mut params = operation.params();
+                if self.use_mask && self.mask.is_some() {
+                    params.push(("маска", "применена".to_string()));
+                }
+                if let Some(report) = &output.report {
+                    params.push(("результат", report.clone()));
+                }
+                if let Some(threshold) = output.threshold {
+                    self.operations.iter_mut().for_each(|op| op.seed_threshold(threshold));
+                }
+                if output.threshold.is_some() || matches!(operation, Operation::ManualThreshold { .. }) {
+                    self.threshold_marker = true;
+                }
+                self.reports[index] = output.report;
+                self.palette = output.palette;
+                self.error = None;
+                self.set_processed(output.image, operation.label(), params);
+            }
+            Err(message) => self.error = Some(format!("{}: {message}", operation.label())),
+        }
+    }
+
+    /// Входные изображения операции: обрабатываемое и оригинал
+    fn inputs(&self, operation: &Operation) -> Option<(Arc<DynamicImage>, Arc<DynamicImage>)> {
+        // В режиме цепочки любая операция берет текущий результат
+        let source = match (operation.source(), self.chain) {
+            (Source::Processed, _) | (_, true) => self.processed_image.clone().or_else(|| self.original_image.clone()),
+            (Source::Original, false) => self.original_image.clone(),
+        };
+        Some((source?, self.original_image.clone()?))
+    }
+
+    /// Пересчитывает предпросмотр измененной операции. Пока кнопка мыши
+    /// зажата (перетаскивание слайдера), пересчет не чаще 10 раз в секунду,
+    /// а для медленных операций - не чаще, чем раз в два времени расчета;
+    /// после отпускания - сразу. В журнал и историю предпросмотр не попадает
+    fn update_preview(&mut self, ctx: &egui::Context) {
+        let Some(operation) = &self.pending_preview else {
+            return;
+        };
+        let (dragging, now) = ctx.input(|i| (i.pointer.any_down(), i.time));
+        if dragging && now - self.preview_time < (2.0 * self.preview_cost).max(0.1) {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        }
+        let Some((source, original)) = self.inputs(operation) else {
+            return;
+        };
+        let started = Instant::now();
+        match operation.execute(&source, &original).and_then(|output| self.masked(output, &source)) {
+            Ok(output) => {
+                self.preview = Some(Arc::new(output.image));
+                self.preview_texture = None;
+                self.error = None;
+            }
+            Err(message) => self.error = Some(format!("{}: {message}", operation.label())),
+        }
+        self.preview_cost = started.elapsed().as_secs_f64();
+        self.preview_time = now;
+        self.pending_preview = None;
+    }
+
+    fn clear_preview(&mut self) {
+        self.preview = None;
+        self.preview_texture = None;
+        self.pending_preview = None;
+    }
+
+    /// При включенной маске смешивает результат с исходным изображением операции
+    fn masked(&self, mut output: OpOutput, source: &DynamicImage) -> Result<OpOutput, String> {
+        if let (true, Some(mask)) = (self.use_mask, &self.mask) {
+            output.image = apply_mask(&output.image, source, mask)
+                .ok_or("маска неприменима: операция меняет размер изображения")?;
+        }
+        Ok(output)
+    }
+
+    /// Записывает журнал рядом с сохраненным изображением
+    fn write_log(&self, image_path: &Path) -> std::io::Result<()> {
+        let dimensions = self.original_image.as_ref().map(|img| img.dimensions()).unwrap_or((0, 0));
+        let source = self.source_path.as_deref();
+        let (extension, content) = if self.log_as_json {
+            ("json", log_as_json(source, dimensions, &self.processing_log))
+        } else {
+            ("txt", log_as_text(source, dimensions, &self.processing_log))
+        };
+        std::fs::write(image_path.with_extension(extension), content)
+    }
+}
+
+/// Шаг истории обработки: результат и название операции, которая его дала
+struct HistoryStep {
+    image: Arc<DynamicImage>,
+    description: String,
+}
+
 /// Реализация основного цикла приложения
 impl eframe::App for ImageApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::SidePanel::right("processing_log").default_width(240.0).show(ctx, |ui| {
+            ui.heading("Журнал");
+            egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+                if self.processing_log.is_empty() {
+                    ui.label("(операций пока нет)");
+                }
+                for (i, entry) in self.processing_log.iter().enumerate() {
+                    ui.label(format!("{}. [{}] {}", i + 1, entry.time_of_day(), entry.describe()));
+                }
+            });
+        });
+
+        let undo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+        let redo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y);
+        if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+            self.step_history(-1);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&redo)) {
+            self.step_history(1);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
-                if ui.button("Загрузить изображение").clicked() {
-                    if let Some(path) = rfd::FileDialog::new().pick_file() {
-                        if let Ok(img) = image::open(path) {
-                            let image_arc = Arc::new(img);
-                            self.original_image = Some(image_arc.clone());
-                            self.processed_image = Some(image_arc.clone()); // Сразу копируем для сброса
-                            self.original_texture = None; // Сбрасываем текстуры, чтобы они пересоздались
-                            self.processed_texture = None;
+                if ui.button("Загрузить изображение").clicked()
+                    && let Some(path) = rfd::FileDialog::new().pick_file()
+                    && let Ok(img) = image::open(&path)
+                {
+                    let image_arc = Arc::new(img);
+                    self.source_path = Some(path);
+                    self.processing_log.clear();
+                    self.original_image = Some(image_arc.clone());
+                    self.processed_image = Some(image_arc.clone()); // Сразу копируем для сброса
+                    self.original_texture = None; // Сбрасываем текстуры, чтобы они пересоздались
+                    self.processed_texture = None;
+                    self.original_view = ViewState::default();
+                    self.processed_view = ViewState::default();
+                    self.reports.iter_mut().for_each(|report| *report = None);
+                    self.palette = None;
+                    self.metrics = None;
+                    self.threshold_marker = false;
+                    self.selection = None;
+                    self.perspective_quad = None;
+                    self.error = None;
+                    self.reset_history();
+                    self.clear_preview();
+                }
+
+                if ui.button("Загрузить изображение B").clicked()
+                    && let Some(path) = rfd::FileDialog::new().pick_file()
+                {
+                    match image::open(&path) {
+                        Ok(img) => {
+                            self.second_image = Some(Arc::new(img));
+                            self.second_texture = None;
+                            self.error = None;
                         }
+                        Err(err) => self.error = Some(format!("Изображение B: {err}")),
                     }
                 }
+                if let Some(second) = &self.second_image {
+                    let texture = self
+                        .second_texture
+                        .get_or_insert_with(|| image_to_texture(second, "second", ctx, self.texture_smoothing));
+                    ui.add(egui::Image::new(texture.deref()).max_size(egui::vec2(48.0, 32.0)))
+                        .on_hover_text(format!("B: {}×{}", second.width(), second.height()));
+                }
 
                 let has_image = self.processed_image.is_some();
 
                 ui.add_enabled_ui(has_image, |ui| {
-                    if ui.button("Сохранить результат").clicked() {
-                        if let Some(image) = &self.processed_image {
-                            if let Some(path) = rfd::FileDialog::new().save_file() {
-                                // Добавляем расширение, если его нет
-                                let path = if path.extension().is_none() {
-                                    path.with_extension("png")
-                                } else {
-                                    path
-                                };
-                                let _ = image.save(path);
-                            }
+                    if ui.button("Сохранить результат").clicked()
+                        && let Some(image) = &self.processed_image
+                        && let Some(path) = rfd::FileDialog::new().save_file()
+                    {
+                        // Добавляем расширение, если его нет
+                        let path = if path.extension().is_none() {
+                            path.with_extension("png")
+                        } else {
+                            path
+                        };
+                        let _ = image.save(&path);
+                        if self.save_log {
+                            let _ = self.write_log(&path);
                         }
                     }
 
-                    if ui.button("Сбросить").clicked() {
-                        if let Some(original) = &self.original_image {
-                            self.processed_image = Some(original.clone());
-                            self.processed_texture = None; // Сброс для пересоздания
-                        }
+                    ui.checkbox(&mut self.save_log, "с журналом");
+                    ui.add_enabled(self.save_log, egui::Checkbox::new(&mut self.log_as_json, "JSON"));
+
+                    if ui.button("Сбросить").clicked()
+                        && let Some(original) = &self.original_image
+                    {
+                        self.processed_image = Some(original.clone());
+                        self.processed_texture = None; // Сброс для пересоздания
+                        self.palette = None;
+                        self.metrics = None;
+                        self.threshold_marker = false;
+                        self.processing_log.push(LogEntry::new("Сброс", Vec::new()));
+                        self.reset_history();
+                        self.clear_preview();
+                    }
+
+                    let can_undo = self.history_position > 0;
+                    let can_redo = self.history_position + 1 < self.history.len();
+                    if ui.add_enabled(can_undo, egui::Button::new("Отменить")).on_hover_text("Ctrl+Z").clicked() {
+                        self.step_history(-1);
+                    }
+                    if ui.add_enabled(can_redo, egui::Button::new("Повторить")).on_hover_text("Ctrl+Y").clicked() {
+                        self.step_history(1);
+                    }
+                    if let Some(step) = self.history.get(self.history_position) {
+                        let last = self.history.len() - 1;
+                        ui.label(format!("Шаг {}/{last}: {}", self.history_position, step.description));
+                    }
+                    ui.label("глубина");
+                    ui.add(egui::DragValue::new(&mut self.history_depth).range(1..=200));
+
+                    let smoothing = self.texture_smoothing;
+                    egui::ComboBox::from_label("сглаживание при масштабировании")
+                        .selected_text(self.texture_smoothing.name())
+                        .show_ui(ui, |ui| {
+                            for mode in TextureSmoothing::ALL {
+                                ui.selectable_value(&mut self.texture_smoothing, mode, mode.name());
+                            }
+                        });
+                    ui.checkbox(&mut self.link_views, "связать виды")
+                        .on_hover_text("Общие масштаб и сдвиг в обеих панелях при одинаковых размерах изображений");
+                    ui.checkbox(&mut self.stretch_small, "растянуть")
+                        .on_hover_text("Вписывать маленькие изображения в область просмотра с увеличением");
+                    if smoothing != self.texture_smoothing {
+                        // Текстуры загружаются заново с новым режимом фильтрации
+                        self.original_texture = None;
+                        self.processed_texture = None;
+                        self.preview_texture = None;
+                        self.second_texture = None;
                     }
                 });
             });
@@ -237,96 +642,550 @@ impl eframe::App for ImageApp {
             let main_rect = ui.available_rect_before_wrap();
             let image_width = main_rect.width() / 2.0 - ui.spacing().item_spacing.x;
 
+            ui.horizontal(|ui| {
+                if ui.button("Загрузить маску").clicked()
+                    && let Some(path) = rfd::FileDialog::new().pick_file()
+                {
+                    match image::open(&path) {
+                        Ok(img) => {
+                            self.mask = Some(Arc::new(img.to_luma8()));
+                            self.error = None;
+                        }
+                        Err(err) => self.error = Some(format!("Маска: {err}")),
+                    }
+                }
+                if ui
+                    .add_enabled(self.processed_image.is_some(), egui::Button::new("Использовать результат как маску"))
+                    .clicked()
+                    && let Some(processed) = &self.processed_image
+                {
+                    self.mask = Some(Arc::new(processed.to_luma8()));
+                }
+                ui.add_enabled(self.mask.is_some(), egui::Checkbox::new(&mut self.use_mask, "применять по маске"))
+                    .on_hover_text("Маска другого размера масштабируется методом ближайшего соседа");
+                if let Some(mask) = &self.mask {
+                    ui.label(format!("маска {}×{}", mask.width(), mask.height()));
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut self.select_mode, "Режим выделения").changed() && self.select_mode {
+                    self.perspective_mode = false;
+                }
+                ui.add_enabled_ui(self.original_image.is_some(), |ui| {
+                    if ui.button("Обрезать").clicked() {
+                        self.crop_to_selection();
+                    }
+                });
+                if let Some(selection) = self.selection {
+                    let (x, y, width, height) = selection_pixels(selection);
+                    ui.label(format!("{width}×{height} от ({x}, {y})"));
+                }
+                ui.separator();
+                if ui.checkbox(&mut self.perspective_mode, "Перспектива").changed() && self.perspective_mode {
+                    self.select_mode = false;
+                }
+                ui.add_enabled_ui(self.perspective_mode && self.original_image.is_some(), |ui| {
+                    if ui.button("Исправить перспективу").clicked() {
+                        self.rectify_perspective();
+                    }
+                });
+            });
+            if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.selection = None;
+                self.selection_anchor = None;
+            }
+
+            let view_size = egui::vec2(image_width, (main_rect.height() * 0.45).max(160.0));
+            // Пробел с перетаскиванием сдвигает вид и не трогает выделение
+            let space_held = ctx.input(|i| i.key_down(egui::Key::Space));
+            let mut wand_seed = None;
             ui.horizontal(|ui| {
                 ui.vertical(|ui| {
-                    ui.label("Оригинал");
+                    ui.label(image_title("Оригинал", self.original_image.as_deref()));
                     if let Some(original) = &self.original_image {
                         let texture = self.original_texture.get_or_insert_with(|| {
-                            image_to_texture(original, "original", ctx)
+                            image_to_texture(original, "original", ctx, self.texture_smoothing)
                         });
-                        ui.image(texture.deref());
+                        let view = &mut self.original_view;
+                        let (response, image_rect) = image_view(ui, texture, view, view_size, self.stretch_small);
+                        let dimensions = original.dimensions();
+                        if !space_held {
+                            if self.perspective_mode {
+                                self.track_perspective(&response, image_rect, dimensions);
+                            } else if self.select_mode {
+                                self.track_selection(&response, image_rect, dimensions);
+                            } else if response.clicked() {
+                                wand_seed = response
+                                    .interact_pointer_pos()
+                                    .and_then(|pos| pixel_at(image_rect, pos, dimensions));
+                            }
+                        }
+                        let painter = ui.painter_at(response.rect);
+                        if let Some(quad) = self.perspective_quad
+                            && self.perspective_mode
+                        {
+                            let color = egui::Color32::from_rgb(255, 170, 40);
+                            let corners = quad.map(|corner| image_to_screen(image_rect, corner, dimensions));
+                            painter.add(egui::Shape::closed_line(corners.to_vec(), egui::Stroke::new(1.5, color)));
+                            for corner in corners {
+                                painter.circle_stroke(corner, HANDLE_RADIUS, egui::Stroke::new(2.0, color));
+                            }
+                        } else if let Some(selection) = self.selection {
+                            let screen = image_rect_to_screen(image_rect, selection, dimensions);
+                            let color = egui::Color32::from_rgb(80, 140, 255);
+                            painter.rect_filled(screen, 0.0, color.gamma_multiply(0.15));
+                            painter.rect_stroke(screen, 0.0, egui::Stroke::new(1.5, color));
+                        }
                     } else {
                         ui.label("(изображение не загружено)");
                     }
                 });
 
                 ui.vertical(|ui| {
-                    ui.label("Результат");
-                    if let Some(processed) = &self.processed_image {
-                        let texture = self.processed_texture.get_or_insert_with(|| {
-                            image_to_texture(processed, "processed", ctx)
-                        });
-                        ui.image(texture.deref());
+                    if let Some(preview) = &self.preview {
+                        ui.label(image_title("Предпросмотр", Some(preview)));
+                    } else {
+                        ui.label(image_title("Результат", self.processed_image.as_deref()));
+                    }
+                    if let Some(processed) = self.preview.as_ref().or(self.processed_image.as_ref()) {
+                        let (cache, name) = if self.preview.is_some() {
+                            (&mut self.preview_texture, "preview")
+                        } else {
+                            (&mut self.processed_texture, "processed")
+                        };
+                        let texture =
+                            cache.get_or_insert_with(|| image_to_texture(processed, name, ctx, self.texture_smoothing));
+                        // Связанный вид возможен только при одинаковых размерах
+                        let linked = self.link_views
+                            && self.original_image.as_ref().is_some_and(|o| o.dimensions() == processed.dimensions());
+                        let view = if linked { &mut self.original_view } else { &mut self.processed_view };
+                        let before = *view;
+                        image_view(ui, texture, view, view_size, self.stretch_small);
+                        if linked && *view != before {
+                            // Оригинал уже выведен в этом кадре - перерисовываем с новым видом
+                            ctx.request_repaint();
+                        }
+                        if let Some(palette) = &self.palette {
+                            palette_swatches(ui, palette);
+                        }
                     } else {
                         ui.label("(изображение не загружено)");
                     }
                 });
             });
 
-            ui.separator();
+            if let Some(seed) = wand_seed {
+                let wand = self
+                    .operations
+                    .iter()
+                    .position(|op| matches!(op, Operation::FloodSelect { .. }));
+                if let Some(index) = wand
+                    && let Operation::FloodSelect { tolerance, connectivity, by_color, .. } = self.operations[index]
+                {
+                    let operation = Operation::FloodSelect { seed: Some(seed), tolerance, connectivity, by_color };
+                    self.run(index, operation);
+                }
+            }
 
-            // --- Панель с кнопками алгоритмов ---
-            ui.add_enabled_ui(self.original_image.is_some(), |ui| {
+            if let (Some(original), Some(processed)) = (&self.original_image, &self.processed_image) {
                 ui.horizontal(|ui| {
-                    if ui.button("Линейное контрастирование").clicked() {
-                        if let Some(original) = &self.original_image {
-                            let result = apply_linear_contrast(original);
-                            self.processed_image = Some(Arc::new(result));
-                            self.processed_texture = None;
-                        }
-                    }
-
-                    if ui.button("Порог (метод Оцу)").clicked() {
-                        if let Some(original) = &self.original_image {
-                            let result = apply_otsu_threshold(original);
-                            self.processed_image = Some(Arc::new(result));
-                            self.processed_texture = None;
+                    ui.checkbox(&mut self.show_histogram, "Гистограмма");
+                    ui.add_enabled(self.show_histogram, egui::Checkbox::new(&mut self.show_rgb_histogram, "R, G, B"));
+                });
+                if self.show_histogram {
+                    let marker = if self.threshold_marker {
+                        self.operations.iter().find_map(Operation::manual_threshold)
+                    } else {
+                        None
+                    };
+                    let rgb = self.show_rgb_histogram;
+                    ui.horizontal(|ui| {
+                        let panels = [
+                            (cached_stats(&mut self.original_stats, original), "original"),
+                            (cached_stats(&mut self.processed_stats, processed), "result"),
+                        ];
+                        for (stats, name) in panels {
+                            ui.vertical(|ui| {
+                                histogram_plot(ui, &stats.histograms, rgb, marker, image_width);
+                                if ui.button("Экспорт гистограммы...").clicked()
+                                    && let Some(path) = rfd::FileDialog::new()
+                                        .set_file_name(format!("histogram_{name}.csv"))
+                                        .add_filter("CSV", &["csv"])
+                                        .add_filter("PNG", &["png"])
+                                        .save_file()
+                                    && let Err(message) = export_histogram(&path, stats, rgb, marker)
+                                {
+                                    self.error = Some(message);
+                                }
+                            });
                         }
+                    });
+                }
+                ui.collapsing("Статистика", |ui| {
+                    let original_stats = cached_stats(&mut self.original_stats, original).clone();
+                    let processed_stats = cached_stats(&mut self.processed_stats, processed).clone();
+                    ui.horizontal(|ui| {
+                        ui.vertical(|ui| {
+                            ui.label("Оригинал");
+                            stats_grid(ui, "original_stats", &original_stats);
+                        });
+                        ui.vertical(|ui| {
+                            ui.label("Результат");
+                            stats_grid(ui, "processed_stats", &processed_stats);
+                        });
+                    });
+                    if ui.button("Копировать как текст").clicked() {
+                        let text = original_stats.to_table("Оригинал") + "\n" + &processed_stats.to_table("Результат");
+                        ui.ctx().output_mut(|o| o.copied_text = text);
                     }
                 });
-
                 ui.horizontal(|ui| {
-                    ui.add(egui::Slider::new(&mut self.manual_threshold_value, 0..=255).text("Ручной порог"));
-                    if ui.button("Применить").clicked() {
-                        if let Some(original) = &self.original_image {
-                            let result = apply_manual_threshold(original, self.manual_threshold_value);
-                            self.processed_image = Some(Arc::new(result));
-                            self.processed_texture = None;
-                        }
+                    ui.add(egui::Slider::new(&mut self.palette_size, 2..=16).text("цветов"));
+                    if ui.button("Палитра").clicked() {
+                        self.palette = Some(dominant_colors(processed, self.palette_size));
                     }
                 });
-
                 ui.horizontal(|ui| {
-                    if ui.button("Инверсия").clicked() {
-                        if let Some(original) = &self.original_image {
-                            let result = apply_inversion(original);
-                            self.processed_image = Some(Arc::new(result));
-                            self.processed_texture = None;
-                        }
+                    if ui.button("Метрики").clicked() {
+                        self.metrics = Some(format_metrics(original, processed));
                     }
-                    ui.add(egui::Slider::new(&mut self.manual_brightness_value, -255..=255).text("Ручной порог"));
-                    if ui.button("Яркость").clicked() {
-                        if let Some(original) = &self.original_image {
-                            let result = apply_brightness(original, self.manual_brightness_value);
-                            self.processed_image = Some(Arc::new(result));
-                            self.processed_texture = None;
-                        }
+                    if let Some(metrics) = &self.metrics {
+                        ui.label(metrics);
                     }
                 });
+            }
+
+            ui.separator();
+
+            // --- Панель с кнопками алгоритмов ---
+            if let Some(original) = &self.original_image {
+                let dimensions = original.dimensions();
+                self.operations.iter_mut().for_each(|op| op.set_source_size(dimensions));
+            }
+            let second = self.second_image.as_ref();
+            self.operations.iter_mut().for_each(|op| op.set_second_image(second));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.chain, egui::RichText::new("применять к результату").strong())
+                    .on_hover_text("Операции обрабатывают текущий результат; иначе - оригинал");
+                if ui.checkbox(&mut self.live_preview, "предпросмотр").changed() && !self.live_preview {
+                    self.clear_preview();
+                }
             });
+            let mut requested = None;
+            ui.add_enabled_ui(self.original_image.is_some(), |ui| {
+                let mut start = 0;
+                while start < self.operations.len() {
+                    // Строка - операция и следующие за ней встраиваемые операции
+                    let end = start + 1 + self.operations[start + 1..].iter().take_while(|op| op.inline()).count();
+                    ui.horizontal_wrapped(|ui| {
+                        for (offset, operation) in self.operations[start..end].iter_mut().enumerate() {
+                            let index = start + offset;
+                            let before = (self.live_preview && operation.previewable()).then(|| operation.clone());
+                            operation.params_ui(ui);
+                            if before.is_some_and(|before| before != *operation) {
+                                self.pending_preview = Some(operation.clone());
+                            }
+                            if operation.has_button() && ui.button(operation.label()).clicked() {
+                                requested = Some((index, operation.clone()));
+                            }
+                            if let Some(report) = &self.reports[index] {
+                                ui.label(report);
+                            }
+                        }
+                    });
+                    start = end;
+                }
+                if let Some(error) = &self.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
+            if let Some(element) = self.operations.iter().find_map(Operation::structuring_element) {
+                self.operations.iter_mut().for_each(|op| op.share_element(element));
+            }
+            if let Some(c) = self.operations.iter().find_map(Operation::log_coefficient) {
+                self.operations.iter_mut().for_each(|op| op.share_log_coefficient(c));
+            }
+            if let Some((index, operation)) = requested {
+                self.run(index, operation);
+            }
+            self.update_preview(ctx);
         });
     }
 }
 
+/// Сохраняет гистограмму: PNG для расширения .png, иначе CSV
+fn export_histogram(path: &Path, stats: &ImageStats, rgb: bool, marker: Option<u8>) -> Result<(), String> {
+    let path = if path.extension().is_none() {
+        path.with_extension("csv")
+    } else {
+        path.to_path_buf()
+    };
+    let is_png = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    let result = if is_png {
+        stats.render_histogram(rgb, marker).save(&path).map_err(|err| err.to_string())
+    } else {
+        std::fs::write(&path, stats.histogram_csv()).map_err(|err| err.to_string())
+    };
+    result.map_err(|err| format!("Не удалось сохранить гистограмму {}: {err}", path.display()))
+}
+
+/// Гистограмма яркости столбиками и, при `rgb`, полупрозрачные линии
+/// каналов R, G, B; `marker` - вертикальная отметка порога
+fn histogram_plot(ui: &mut egui::Ui, histograms: &[[u64; 256]; 4], rgb: bool, marker: Option<u8>, width: f32) {
+    const HEIGHT: f32 = 120.0;
+    let (response, painter) = ui.allocate_painter(egui::vec2(width, HEIGHT), egui::Sense::hover());
+    let rect = response.rect;
+    let [red, green, blue, luma] = histograms;
+    let shown: &[&[u64; 256]] = if rgb { &[luma, red, green, blue] } else { &[luma] };
+    let max = shown.iter().flat_map(|h| h.iter()).copied().max().unwrap_or(0).max(1) as f32;
+    let bin_width = rect.width() / 256.0;
+    let x_of = |bin: f32| rect.min.x + bin * bin_width;
+    let y_of = |count: u64| rect.max.y - count as f32 / max * HEIGHT;
+
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    let bar_color = ui.visuals().weak_text_color();
+    for (bin, &count) in luma.iter().enumerate() {
+        if count > 0 {
+            let top_left = egui::pos2(x_of(bin as f32), y_of(count));
+            let bar = egui::Rect::from_min_max(top_left, egui::pos2(x_of(bin as f32 + 1.0), rect.max.y));
+            painter.rect_filled(bar, 0.0, bar_color);
+        }
+    }
+    if rgb {
+        let colors = [(red, [255, 60, 60]), (green, [60, 200, 60]), (blue, [70, 110, 255])];
+        for (histogram, [r, g, b]) in colors {
+            let points = histogram
+                .iter()
+                .enumerate()
+                .map(|(bin, &count)| egui::pos2(x_of(bin as f32 + 0.5), y_of(count)))
+                .collect();
+            let color = egui::Color32::from_rgba_unmultiplied(r, g, b, 160);
+            painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+        }
+    }
+    if let Some(threshold) = marker {
+        let x = x_of(threshold as f32 + 0.5);
+        let stroke = egui::Stroke::new(1.5, egui::Color32::GOLD);
+        painter.line_segment([egui::pos2(x, rect.min.y), egui::pos2(x, rect.max.y)], stroke);
+    }
+    painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, ui.visuals().weak_text_color()));
+}
+
+/// Метрики качества результата относительно оригинала для вывода в интерфейсе
+fn format_metrics(original: &DynamicImage, processed: &DynamicImage) -> String {
+    match compute_quality_metrics(original, processed) {
+        Some(metrics) => {
+            let psnr = if metrics.psnr.is_infinite() { "∞".to_string() } else { format!("{:.1} dB", metrics.psnr) };
+            format!("MSE: {:.2}   PSNR: {psnr}   SSIM: {:.3}", metrics.mse, metrics.ssim)
+        }
+        None => "Метрики недоступны: размеры оригинала и результата различаются".to_string(),
+    }
+}
+
+/// Образцы цветов палитры с кодом и долей; щелчок копирует код цвета
+fn palette_swatches(ui: &mut egui::Ui, palette: &[([u8; 3], f32)]) {
+    // Подписи выводятся только для короткой палитры, иначе - во всплывающей подсказке
+    let labeled = palette.len() <= 16;
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing = egui::vec2(2.0, 2.0);
+        for &([r, g, b], share) in palette {
+            let hex = format!("#{r:02X}{g:02X}{b:02X}");
+            let caption = format!("{hex} {:.1}%", share * 100.0);
+            let (rect, response) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::click());
+            ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+            if labeled {
+                ui.label(&caption);
+                ui.add_space(6.0);
+            }
+            if response.on_hover_text(format!("{caption}\nЩелчок - скопировать код")).clicked() {
+                ui.ctx().output_mut(|o| o.copied_text = hex);
+            }
+        }
+    });
+}
+
+/// Радиус маркера угла перспективы на экране
+const HANDLE_RADIUS: f32 = 6.0;
+
+/// Заголовок панели изображения с его размерами
+fn image_title(title: &str, image: Option<&DynamicImage>) -> String {
+    match image {
+        Some(image) => format!("{title} ({}×{})", image.width(), image.height()),
+        None => title.to_string(),
+    }
+}
+
+/// Точка на экране в координатах пикселей изображения, показанного в `rect`,
+/// с зажатием в границы изображения
+fn screen_to_image(rect: egui::Rect, pos: egui::Pos2, dimensions: (u32, u32)) -> egui::Pos2 {
+    let (width, height) = (dimensions.0 as f32, dimensions.1 as f32);
+    egui::pos2(
+        ((pos.x - rect.min.x) / rect.width().max(1.0) * width).clamp(0.0, width),
+        ((pos.y - rect.min.y) / rect.height().max(1.0) * height).clamp(0.0, height),
+    )
+}
+
+/// Точка в пикселях изображения - в экранные координаты `rect`
+fn image_to_screen(rect: egui::Rect, point: egui::Pos2, dimensions: (u32, u32)) -> egui::Pos2 {
+    let scale = egui::vec2(rect.width() / dimensions.0 as f32, rect.height() / dimensions.1 as f32);
+    rect.min + egui::vec2(point.x * scale.x, point.y * scale.y)
+}
+
+/// Прямоугольник в пикселях изображения - в экранные координаты `rect`
+fn image_rect_to_screen(rect: egui::Rect, area: egui::Rect, dimensions: (u32, u32)) -> egui::Rect {
+    egui::Rect::from_min_max(image_to_screen(rect, area.min, dimensions), image_to_screen(rect, area.max, dimensions))
+}
+
+/// Выделение, округленное до целых пикселей: (x, y, ширина, высота)
+fn selection_pixels(selection: egui::Rect) -> (u32, u32, u32, u32) {
+    let (x0, y0) = (selection.min.x.round() as u32, selection.min.y.round() as u32);
+    let (x1, y1) = (selection.max.x.round() as u32, selection.max.y.round() as u32);
+    (x0, y0, x1 - x0, y1 - y0)
+}
+
+/// Переводит точку экрана в координаты пикселя изображения, показанного в `rect`
+fn pixel_at(rect: egui::Rect, pos: egui::Pos2, dimensions: (u32, u32)) -> Option<(u32, u32)> {
+    if !rect.contains(pos) || rect.width() <= 0.0 || rect.height() <= 0.0 {
+        return None;
+    }
+    let x = ((pos.x - rect.min.x) / rect.width() * dimensions.0 as f32) as u32;
+    let y = ((pos.y - rect.min.y) / rect.height() * dimensions.1 as f32) as u32;
+    (x < dimensions.0 && y < dimensions.1).then_some((x, y))
+}
+
+/// Наибольшее увеличение при просмотре
+const MAX_ZOOM: f32 = 32.0;
+
+/// Масштаб и сдвиг просмотра изображения
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+struct ViewState {
+    /// Экранных точек на пиксель изображения; None - вписать в область просмотра
+    zoom: Option<f32>,
+    /// Точка изображения (в пикселях) в центре области просмотра
+    center: Option<egui::Pos2>,
+    /// Размеры изображения, для которого задан вид: у нового вид сбрасывается
+    dimensions: (u32, u32),
+}
+
+/// Область просмотра `size` с масштабированием колесом мыши относительно
+/// указателя и сдвигом средней кнопкой или перетаскиванием с пробелом.
+/// Изначально изображение вписывается с сохранением пропорций, маленькое -
+/// не больше 100%, если не задано `stretch`. Меняется только размер вывода,
+/// текстура остается прежней. Возвращает ответ области и экранный
+/// прямоугольник всего изображения (он может выходить за область просмотра)
+fn image_view(
+    ui: &mut egui::Ui,
+    texture: &egui::TextureHandle,
+    state: &mut ViewState,
+    size: egui::Vec2,
+    stretch: bool,
+) -> (egui::Response, egui::Rect) {
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+    let image_size = texture.size_vec2();
+    let dimensions = (image_size.x as u32, image_size.y as u32);
+    if state.dimensions != dimensions {
+        *state = ViewState { dimensions, ..ViewState::default() };
+    }
+    let mut fit = (rect.width() / image_size.x).min(rect.height() / image_size.y);
+    if !stretch {
+        fit = fit.min(1.0);
+    }
+    let mut zoom = state.zoom.unwrap_or(fit).clamp(fit, MAX_ZOOM.max(fit));
+    let mut center = state.center.unwrap_or((image_size / 2.0).to_pos2());
+
+    if let Some(pointer) = response.hover_pos() {
+        let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+        if scroll != 0.0 {
+            // Точка изображения под указателем остается на месте
+            let anchor = center + (pointer - rect.center()) / zoom;
+            zoom = (zoom * (scroll / 200.0).exp()).clamp(fit, MAX_ZOOM.max(fit));
+            center = anchor - (pointer - rect.center()) / zoom;
+        }
+    }
+    let space_held = ui.input(|i| i.key_down(egui::Key::Space));
+    if response.dragged_by(egui::PointerButton::Middle) || (space_held && response.dragged()) {
+        center -= response.drag_delta() / zoom;
+    }
+    center = center.clamp(egui::Pos2::ZERO, image_size.to_pos2());
+    if response.double_clicked_by(egui::PointerButton::Middle) || zoom <= fit {
+        *state = ViewState { dimensions, ..ViewState::default() };
+        zoom = fit;
+        center = (image_size / 2.0).to_pos2();
+    } else {
+        state.zoom = Some(zoom);
+        state.center = Some(center);
+    }
+
+    let image_rect = egui::Rect::from_min_size(rect.center() - center.to_vec2() * zoom, image_size * zoom);
+    let visible = image_rect.intersect(rect);
+    if visible.is_positive() {
+        let uv = |p: egui::Pos2| ((p - image_rect.min) / image_rect.size()).to_pos2();
+        let uv_rect = egui::Rect::from_min_max(uv(visible.min), uv(visible.max));
+        ui.painter().image(texture.id(), visible, uv_rect, egui::Color32::WHITE);
+    }
+    ui.label(format!("масштаб {:.0}%", zoom * 100.0))
+        .on_hover_text("Колесо мыши - масштаб, средняя кнопка или пробел с перетаскиванием - сдвиг");
+    (response, image_rect)
+}
+
+/// Фильтрация текстур при масштабировании изображений на экране
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TextureSmoothing {
+    /// Без сглаживания для бинарных результатов, со сглаживанием для остальных
+    Auto,
+    Linear,
+    Nearest,
+}
+
+impl TextureSmoothing {
+    const ALL: [TextureSmoothing; 3] = [TextureSmoothing::Auto, TextureSmoothing::Linear, TextureSmoothing::Nearest];
+
+    fn name(self) -> &'static str {
+        match self {
+            TextureSmoothing::Auto => "авто",
+            TextureSmoothing::Linear => "включено",
+            TextureSmoothing::Nearest => "выключено",
+        }
+    }
+
+    fn options(self, image: &DynamicImage) -> egui::TextureOptions {
+        let nearest = match self {
+            TextureSmoothing::Auto => is_binary(image),
+            TextureSmoothing::Linear => false,
+            TextureSmoothing::Nearest => true,
+        };
+        // При увеличении больше 100% пиксели всегда видны четко
+        let minification = if nearest { egui::TextureFilter::Nearest } else { egui::TextureFilter::Linear };
+        egui::TextureOptions {
+            magnification: egui::TextureFilter::Nearest,
+            minification,
+            ..egui::TextureOptions::LINEAR
+        }
+    }
+}
+
+/// Бинарный результат: полутоновое изображение только из 0 и 255
+fn is_binary(image: &DynamicImage) -> bool {
+    match image {
+        DynamicImage::ImageLuma8(gray_image) => gray_image.iter().all(|&p| p == 0 || p == 255),
+        _ => false,
+    }
+}
+
 /// Вспомогательная функция для конвертации `DynamicImage` в `egui::TextureHandle`
-fn image_to_texture(image: &DynamicImage, name: &'static str, ctx: &egui::Context) -> egui::TextureHandle {
+fn image_to_texture(
+    image: &DynamicImage,
+    name: &'static str,
+    ctx: &egui::Context,
+    smoothing: TextureSmoothing,
+) -> egui::TextureHandle {
     let (width, height) = image.dimensions();
     let rgba_image = image.to_rgba8();
     let pixels = rgba_image.into_raw();
 
     let egui_image = egui::ColorImage::from_rgba_unmultiplied([width as usize, height as usize], &pixels);
 
-    ctx.load_texture(name, egui_image, Default::default())
+    ctx.load_texture(name, egui_image, smoothing.options(image))
 }
 
 
@@ -0,0 +1,1520 @@
+//! Операции панели алгоритмов: параметры, элементы управления и выполнение.
+//! Новая операция добавляется вариантом `Operation` и ветками в его методах.
+
+use std::sync::Arc;
+
+use eframe::egui;
+use image::DynamicImage;
+
+use crate::fft::{apply_frequency_filter, spectrum_image, FrequencyFilter};
+use crate::kernel_file::load_kernels;
+use crate::lut_file::load_lut;
+use crate::processing::*;
+
+/// Изображение, к которому применяется операция
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Source {
+    Original,
+    /// Текущий результат - для операций над уже бинаризованным изображением
+    Processed,
+}
+
+/// Результат операции: новое изображение и необязательный текстовый отчет
+pub struct OpOutput {
+    pub image: DynamicImage,
+    pub report: Option<String>,
+    /// Найденный глобальный порог - переносится в ручной порог
+    pub threshold: Option<u8>,
+    /// Палитра квантованного изображения с долями цветов - показывается под результатом
+    pub palette: Option<Vec<([u8; 3], f32)>>,
+}
+
+impl From<DynamicImage> for OpOutput {
+    fn from(image: DynamicImage) -> Self {
+        Self { image, report: None, threshold: None, palette: None }
+    }
+}
+
+/// Способ изменения яркости
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BrightnessMode {
+    /// Одинаковый сдвиг R, G и B - у насыщенных цветов смещается тон
+    Rgb,
+    /// Сдвиг только V, тон и насыщенность сохраняются
+    Hsv,
+}
+
+impl BrightnessMode {
+    const ALL: [BrightnessMode; 2] = [BrightnessMode::Rgb, BrightnessMode::Hsv];
+
+    fn name(self) -> &'static str {
+        match self {
+            BrightnessMode::Rgb => "RGB",
+            BrightnessMode::Hsv => "HSV",
+        }
+    }
+}
+
+/// Вид результата бинаризации: черно-белая маска или исходные цвета
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MaskStyle {
+    keep_color: bool,
+    /// Цвет пикселей, не прошедших порог, при сохранении цвета
+    fill: [u8; 3],
+}
+
+impl MaskStyle {
+    const BINARY: MaskStyle = MaskStyle { keep_color: false, fill: [0, 0, 0] };
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.keep_color, "сохранить цвет");
+        if self.keep_color {
+            ui.label("фон:");
+            ui.color_edit_button_srgb(&mut self.fill);
+        }
+    }
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        if self.keep_color {
+            let [r, g, b] = self.fill;
+            vec![("сохранить цвет", "true".to_string()), ("фон", format!("#{r:02x}{g:02x}{b:02x}"))]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Бинарное изображение `binary` либо исходные цвета под этой маской
+    fn apply(self, original: &DynamicImage, binary: DynamicImage) -> DynamicImage {
+        if self.keep_color { apply_color_mask(original, &binary, self.fill) } else { binary }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Operation {
+    LinearContrast { low_pct: f32, high_pct: f32, per_channel: bool },
+    Equalization(EqualizationSpace),
+    Otsu { mask: MaskStyle },
+    Triangle,
+    Kapur,
+    Isodata,
+    MultiOtsu { classes: u8 },
+    TiledOtsu { grid: u32, interpolate: bool },
+    ManualThreshold { threshold: u8, mask: MaskStyle },
+    Hysteresis { low: u8, high: u8 },
+    LocalThreshold { method: LocalMethod, window: u32, k: f32, c: f32, r: f32 },
+    HsvRange { range: HsvRange, mask: MaskStyle },
+    /// Волшебная палочка; точка задается щелчком по оригиналу
+    FloodSelect { seed: Option<(u32, u32)>, tolerance: u8, connectivity: Connectivity, by_color: bool },
+    RemoveSmallComponents { connectivity: Connectivity, min_area: u32 },
+    ColorizeComponents { connectivity: Connectivity, min_area: u32 },
+    Erode(StructuringElement),
+    Dilate(StructuringElement),
+    Open(StructuringElement),
+    Close(StructuringElement),
+    Skeletonize,
+    FillHoles,
+    ContourOverlay { color: [u8; 3], thickness: u32 },
+    Grayscale(GrayMethod),
+    ChannelView { plane: ChannelPlane, tinted: bool },
+    ChannelMap([ChannelSource; 3]),
+    GrayWorld,
+    Invert,
+    InvertValue,
+    Brightness { value: i16, mode: BrightnessMode },
+    Gamma(f32),
+    Contrast(f32),
+    /// Яркость, контраст и гамма одной объединенной таблицей
+    Tone { brightness: i16, contrast: f32, gamma: f32 },
+    Exposure(f32),
+    Saturation(f32),
+    Vibrance(f32),
+    HueRotate(f32),
+    HueReplace { center: f32, width: f32, target: f32, feather: f32 },
+    TemperatureTint { temperature: f32, tint: f32 },
+    Levels { black: u8, white: u8, gamma: f32, target: LutTarget },
+    Curves { points: Vec<(f32, f32)>, target: LutTarget },
+    GaussianNoise { sigma: f32, fixed_seed: bool, seed: u64 },
+    Median { radius: u8 },
+    Kuwahara { radius: u8 },
+    BoxBlur { radius: u32, iterations: u8 },
+    GaussianBlur { sigma: f32 },
+    MotionBlur { length: u32, angle: f32 },
+    Sharpen { strength: f32, show_laplacian: bool },
+    UnsharpMask { sigma: f32, amount: f32, threshold: u8 },
+    Emboss { direction: LightDirection, colored: bool },
+    Gradient { operator: GradientOperator, output: GradientOutput },
+    Canny { sigma: f32, low: u8, high: u8 },
+    HoughLines { theta_step: f32, threshold: u32, count: usize, output: HoughOutput, color: [u8; 3] },
+    Sepia(f32),
+    Solarize(u8),
+    Posterize { levels: u8, target: LutTarget },
+    Quantize { colors: u16 },
+    Pixelate { block: u32 },
+    Halftone { cell: u32, angle: f32 },
+    Vignette { strength: f32, radius: f32, feather: f32 },
+    Log { c: f32, target: LutTarget },
+    Exp { c: f32, base: f32, target: LutTarget },
+    AutoContrast { clip_percent: f32 },
+    Watermark(Watermark),
+    Border { left: u32, right: u32, top: u32, bottom: u32, uniform: bool, mode: BorderMode },
+    /// Замощение сеткой `columns`×`rows` текущего результата
+    Tile { columns: u32, rows: u32, mirror: bool },
+    /// Поворот на произвольный угол против часовой стрелки
+    Rotate { degrees: f32, interpolation: Interpolation, expand: bool, background: [u8; 3] },
+    /// Изменение размера; `source` - размеры исходного изображения для
+    /// сохранения пропорций
+    Resize { width: u32, height: u32, lock_aspect: bool, filter: ResizeFilter, source: (u32, u32) },
+    /// Таблица, загруженная из файла (CSV или .cube)
+    FileLut { name: String, lut: Option<Box<Lut>>, error: Option<String>, target: LutTarget },
+    KernelLibrary { kernels: Vec<Kernel>, selected: usize, error: Option<String> },
+    FrequencyFilter { filter: FrequencyFilter, cutoff: f32 },
+    Spectrum,
+    /// Градиентная карта: тени, светлые участки и необязательный средний тон
+    GradientMap { shadow: [u8; 3], highlight: [u8; 3], midtone: Option<([u8; 3], f32)> },
+    Pseudocolor(Colormap),
+    /// Разность оригинала и текущего результата
+    Difference { gain: f32, mode: DifferenceMode },
+    /// Смешивание текущего результата со вторым изображением (B),
+    /// которое загружается отдельно и передается из приложения
+    Blend { mode: BlendMode, opacity: f32, resize: bool, second: Option<Arc<DynamicImage>> },
+}
+
+impl Operation {
+    /// Операции панели с параметрами по умолчанию в порядке отображения
+    pub fn defaults() -> Vec<Operation> {
+        vec![
+            Operation::LinearContrast { low_pct: 2.0, high_pct: 98.0, per_channel: false },
+            Operation::Equalization(EqualizationSpace::HsvValue),
+            Operation::Otsu { mask: MaskStyle::BINARY },
+            Operation::Triangle,
+            Operation::Kapur,
+            Operation::Isodata,
+            Operation::MultiOtsu { classes: 3 },
+            Operation::TiledOtsu { grid: 4, interpolate: true },
+            Operation::ManualThreshold { threshold: 128, mask: MaskStyle::BINARY },
+            Operation::Hysteresis { low: 80, high: 160 },
+            Operation::LocalThreshold { method: LocalMethod::Niblack, window: 25, k: -0.2, c: 10.0, r: SAUVOLA_R },
+            Operation::HsvRange {
+                range: HsvRange { hue: (330.0, 20.0), saturation: (0.3, 1.0), value: (0.2, 1.0) },
+                mask: MaskStyle::BINARY,
+            },
+            Operation::FloodSelect { seed: None, tolerance: 20, connectivity: Connectivity::Four, by_color: false },
+            Operation::RemoveSmallComponents { connectivity: Connectivity::Eight, min_area: 20 },
+            Operation::ColorizeComponents { connectivity: Connectivity::Eight, min_area: 1 },
+            Operation::Erode(StructuringElement { radius: 1, shape: ElementShape::Square }),
+            Operation::Dilate(StructuringElement { radius: 1, shape: ElementShape::Square }),
+            Operation::Open(StructuringElement { radius: 1, shape: ElementShape::Square }),
+            Operation::Close(StructuringElement { radius: 1, shape: ElementShape::Square }),
+            Operation::Skeletonize,
+            Operation::FillHoles,
+            Operation::ContourOverlay { color: [255, 0, 0], thickness: 1 },
+            Operation::Grayscale(GrayMethod::Rec601),
+            Operation::ChannelView { plane: ChannelPlane::Red, tinted: false },
+            Operation::ChannelMap(ChannelSource::IDENTITY),
+            Operation::GrayWorld,
+            Operation::Invert,
+            Operation::InvertValue,
+            Operation::Brightness { value: 0, mode: BrightnessMode::Rgb },
+            Operation::Gamma(1.0),
+            Operation::Contrast(1.0),
+            Operation::Tone { brightness: 0, contrast: 1.0, gamma: 1.0 },
+            Operation::Exposure(0.0),
+            Operation::Saturation(1.0),
+            Operation::Vibrance(0.0),
+            Operation::HueRotate(0.0),
+            Operation::HueReplace { center: 0.0, width: 20.0, target: 120.0, feather: 10.0 },
+            Operation::TemperatureTint { temperature: 0.0, tint: 0.0 },
+            Operation::Levels { black: 0, white: 255, gamma: 1.0, target: LutTarget::Rgb },
+            Operation::Curves { points: IDENTITY_CURVE.to_vec(), target: LutTarget::Rgb },
+            Operation::GaussianNoise { sigma: 20.0, fixed_seed: false, seed: 1 },
+            Operation::Median { radius: 1 },
+            Operation::Kuwahara { radius: 2 },
+            Operation::BoxBlur { radius: 2, iterations: 1 },
+            Operation::GaussianBlur { sigma: 2.0 },
+            Operation::MotionBlur { length: 15, angle: 0.0 },
+            Operation::Sharpen { strength: 1.0, show_laplacian: false },
+            Operation::UnsharpMask { sigma: 2.0, amount: 100.0, threshold: 0 },
+            Operation::Emboss { direction: LightDirection::TopLeft, colored: false },
+            Operation::Gradient { operator: GradientOperator::Sobel, output: GradientOutput::Magnitude },
+            Operation::Canny { sigma: 1.4, low: 20, high: 50 },
+            Operation::HoughLines {
+                theta_step: 1.0,
+                threshold: 50,
+                count: 10,
+                output: HoughOutput::Lines,
+                color: [255, 0, 0],
+            },
+            Operation::Sepia(1.0),
+            Operation::Solarize(128),
+            Operation::Posterize { levels: 4, target: LutTarget::Rgb },
+            Operation::Quantize { colors: 16 },
+            Operation::Pixelate { block: 8 },
+            Operation::Halftone { cell: 8, angle: 45.0 },
+            Operation::Vignette { strength: 0.5, radius: 0.5, feather: 0.5 },
+            Operation::Log { c: default_log_coefficient(), target: LutTarget::Rgb },
+            Operation::Exp { c: default_log_coefficient(), base: std::f32::consts::E, target: LutTarget::Rgb },
+            Operation::AutoContrast { clip_percent: 0.5 },
+            Operation::Watermark(Watermark {
+                text: String::new(),
+                size: 24.0,
+                opacity: 0.7,
+                color: [255, 255, 255],
+                position: WatermarkPosition::BottomRight,
+            }),
+            Operation::Border {
+                left: 20,
+                right: 20,
+                top: 20,
+                bottom: 20,
+                uniform: true,
+                mode: BorderMode::Constant([255; 3]),
+            },
+            Operation::Tile { columns: 2, rows: 2, mirror: false },
+            Operation::Rotate {
+                degrees: 0.0,
+                interpolation: Interpolation::Bilinear,
+                expand: true,
+                background: [255; 3],
+            },
+            Operation::Resize {
+                width: 0,
+                height: 0,
+                lock_aspect: true,
+                filter: ResizeFilter::Bilinear,
+                source: (0, 0),
+            },
+            Operation::FileLut { name: String::new(), lut: None, error: None, target: LutTarget::Rgb },
+            Operation::KernelLibrary { kernels: Vec::new(), selected: 0, error: None },
+            Operation::FrequencyFilter { filter: FrequencyFilter::GaussianLow, cutoff: 30.0 },
+            Operation::Spectrum,
+            Operation::GradientMap { shadow: [40, 20, 90], highlight: [255, 200, 80], midtone: None },
+            Operation::Pseudocolor(Colormap::Viridis),
+            Operation::Difference { gain: 1.0, mode: DifferenceMode::Absolute },
+            Operation::Blend { mode: BlendMode::Add, opacity: 1.0, resize: true, second: None },
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Operation::LinearContrast { .. } => "Линейное контрастирование",
+            Operation::Otsu { .. } => "Порог (метод Оцу)",
+            Operation::Triangle => "Порог (треугольный метод)",
+            Operation::Kapur => "Порог (энтропия Капура)",
+            Operation::Isodata => "Порог (isodata)",
+            Operation::MultiOtsu { .. } => "Многоуровневый Оцу",
+            Operation::TiledOtsu { .. } => "Блочный Оцу",
+            Operation::ManualThreshold { .. } => "Ручной порог",
+            Operation::Hysteresis { .. } => "Двойной порог",
+            Operation::LocalThreshold { .. } => "Локальный порог",
+            Operation::HsvRange { .. } => "Выделить по цвету",
+            Operation::FloodSelect { .. } => "Волшебная палочка",
+            Operation::RemoveSmallComponents { .. } => "Удалить компоненты меньше N пикселей",
+            Operation::ColorizeComponents { .. } => "Раскрасить компоненты",
+            Operation::Erode(_) => "Эрозия",
+            Operation::Dilate(_) => "Дилатация",
+            Operation::Open(_) => "Размыкание",
+            Operation::Close(_) => "Замыкание",
+            Operation::Skeletonize => "Скелетизация",
+            Operation::FillHoles => "Заполнить отверстия",
+            Operation::ContourOverlay { .. } => "Контуры поверх оригинала",
+            Operation::Grayscale(_) => "В оттенки серого",
+            Operation::ChannelView { .. } => "Показать канал",
+            Operation::ChannelMap(_) => "Переставить каналы",
+            Operation::GrayWorld => "Баланс белого (серый мир)",
+            Operation::Invert => "Инверсия",
+            Operation::InvertValue => "Инверсия яркости",
+            Operation::Brightness { .. } => "Яркость",
+            Operation::Gamma(_) => "Гамма-коррекция",
+            Operation::Contrast(_) => "Контраст",
+            Operation::Tone { .. } => "Тон за один проход",
+            Operation::Exposure(_) => "Экспозиция",
+            Operation::Saturation(_) => "Насыщенность",
+            Operation::Vibrance(_) => "Сочность",
+            Operation::HueRotate(_) => "Поворот тона",
+            Operation::HueReplace { .. } => "Замена тона",
+            Operation::TemperatureTint { .. } => "Температура и оттенок",
+            Operation::Levels { .. } => "Уровни",
+            Operation::Curves { .. } => "Кривые",
+            Operation::GaussianNoise { .. } => "Гауссов шум",
+            Operation::Median { .. } => "Медианный фильтр",
+            Operation::Kuwahara { .. } => "Фильтр Кувахары",
+            Operation::BoxBlur { .. } => "Усредняющий фильтр",
+            Operation::GaussianBlur { .. } => "Размытие по Гауссу",
+            Operation::MotionBlur { .. } => "Размытие в движении",
+            Operation::Sharpen { .. } => "Резкость (лапласиан)",
+            Operation::UnsharpMask { .. } => "Нерезкая маска",
+            Operation::Emboss { .. } => "Тиснение",
+            Operation::Gradient { .. } => "Выделить границы",
+            Operation::Canny { .. } => "Детектор Кэнни",
+            Operation::HoughLines { .. } => "Прямые Хафа",
+            Operation::Sepia(_) => "Сепия",
+            Operation::Solarize(_) => "Соляризация",
+            Operation::Posterize { .. } => "Постеризация",
+            Operation::Quantize { .. } => "Квантование",
+            Operation::Pixelate { .. } => "Пикселизация",
+            Operation::Halftone { .. } => "Растрирование",
+            Operation::Vignette { .. } => "Виньетка",
+            Operation::Log { .. } => "Логарифм",
+            Operation::Exp { .. } => "Экспонента",
+            Operation::AutoContrast { .. } => "Автокоррекция",
+            Operation::Equalization(_) => "Эквализация гистограммы",
+            Operation::Watermark(_) => "Наложить надпись",
+            Operation::Border { .. } => "Добавить поля",
+            Operation::Tile { .. } => "Замостить",
+            Operation::Rotate { .. } => "Повернуть",
+            Operation::Resize { .. } => "Изменить размер",
+            Operation::FileLut { .. } => "Применить LUT",
+            Operation::KernelLibrary { .. } => "Применить ядро",
+            Operation::FrequencyFilter { .. } => "Частотный фильтр",
+            Operation::Spectrum => "Показать спектр",
+            Operation::GradientMap { .. } => "Градиентная карта",
+            Operation::Pseudocolor(_) => "Псевдоцвет",
+            Operation::Difference { .. } => "Разность",
+            Operation::Blend { .. } => "Смешать с B",
+        }
+    }
+
+    pub fn source(&self) -> Source {
+        match self {
+            Operation::RemoveSmallComponents { .. }
+            | Operation::ColorizeComponents { .. }
+            | Operation::Erode(_)
+            | Operation::Dilate(_)
+            | Operation::Open(_)
+            | Operation::Close(_)
+            | Operation::Skeletonize
+            | Operation::FillHoles
+            | Operation::ContourOverlay { .. }
+            | Operation::Difference { .. }
+            | Operation::Tile { .. }
+            | Operation::Blend { .. } => Source::Processed,
+            _ => Source::Original,
+        }
+    }
+
+    /// Выводится ли операция в одной строке с предыдущей
+    pub fn inline(&self) -> bool {
+        matches!(
+            self,
+            Operation::Equalization(_)
+                | Operation::Otsu { .. }
+                | Operation::Triangle
+                | Operation::Kapur
+                | Operation::Isodata
+                | Operation::MultiOtsu { .. }
+                | Operation::Brightness { .. }
+                | Operation::Gamma(_)
+                | Operation::Contrast(_)
+                | Operation::Exp { .. }
+                | Operation::Spectrum
+                | Operation::Dilate(_)
+                | Operation::Open(_)
+                | Operation::Close(_)
+                | Operation::Skeletonize
+                | Operation::FillHoles
+        )
+    }
+
+    /// Запускается ли операция кнопкой; волшебная палочка срабатывает по щелчку
+    pub fn has_button(&self) -> bool {
+        !matches!(self, Operation::FloodSelect { .. })
+    }
+
+    /// Предпросмотр при изменении параметров. Без него - операции с
+    /// загруженными из файла данными и смешивание: изменения параметров
+    /// отслеживаются сравнением копий, а их копирование и сравнение дорого
+    pub fn previewable(&self) -> bool {
+        self.has_button()
+            && !matches!(
+                self,
+                Operation::FileLut { .. } | Operation::KernelLibrary { .. } | Operation::Blend { .. }
+            )
+    }
+
+    /// Подставляет порог, найденный автоматическим методом, в ручной порог,
+    /// чтобы его можно было подстроить слайдером
+    pub fn seed_threshold(&mut self, value: u8) {
+        if let Operation::ManualThreshold { threshold, .. } = self {
+            *threshold = value;
+        }
+    }
+
+    /// Текущее значение ручного порога - отмечается на гистограмме
+    pub fn manual_threshold(&self) -> Option<u8> {
+        match self {
+            Operation::ManualThreshold { threshold, .. } => Some(*threshold),
+            _ => None,
+        }
+    }
+
+    /// Структурный элемент задается в строке морфологии у эрозии
+    pub fn structuring_element(&self) -> Option<StructuringElement> {
+        match self {
+            Operation::Erode(element) => Some(*element),
+            _ => None,
+        }
+    }
+
+    /// Переносит общий структурный элемент в остальные операции морфологии
+    pub fn share_element(&mut self, shared: StructuringElement) {
+        if let Operation::Dilate(element) | Operation::Open(element) | Operation::Close(element) = self {
+            *element = shared;
+        }
+    }
+
+    /// Коэффициент c задается одним полем у логарифма
+    pub fn log_coefficient(&self) -> Option<f32> {
+        match self {
+            Operation::Log { c, .. } => Some(*c),
+            _ => None,
+        }
+    }
+
+    /// Переносит общий коэффициент c в экспоненциальное преобразование
+    pub fn share_log_coefficient(&mut self, shared: f32) {
+        if let Operation::Exp { c, .. } = self {
+            *c = shared;
+        }
+    }
+
+    /// Размеры исходного изображения: при смене изображения поля размера
+    /// заполняются его размерами
+    pub fn set_source_size(&mut self, dimensions: (u32, u32)) {
+        if let Operation::Resize { width, height, source, .. } = self
+            && *source != dimensions
+        {
+            *source = dimensions;
+            (*width, *height) = dimensions;
+        }
+    }
+
+    /// Передает в смешивание изображение B, загруженное в приложении
+    pub fn set_second_image(&mut self, image: Option<&Arc<DynamicImage>>) {
+        if let Operation::Blend { second, .. } = self {
+            *second = image.cloned();
+        }
+    }
+
+    /// Элементы управления параметрами, выводятся перед кнопкой операции
+    pub fn params_ui(&mut self, ui: &mut egui::Ui) {
+        match self {
+            Operation::Triangle
+            | Operation::Kapur
+            | Operation::Isodata
+            | Operation::GrayWorld
+            | Operation::Invert
+            | Operation::InvertValue
+            | Operation::Dilate(_)
+            | Operation::Open(_)
+            | Operation::Close(_)
+            | Operation::Skeletonize
+            | Operation::FillHoles => {}
+            Operation::Erode(element) => {
+                ui.label("Морфология (по результату):");
+                ui.add(egui::Slider::new(&mut element.radius, 1..=20).text("радиус"));
+                egui::ComboBox::from_id_salt("element_shape")
+                    .selected_text(element.shape.name())
+                    .show_ui(ui, |ui| {
+                        for shape in ElementShape::ALL {
+                            ui.selectable_value(&mut element.shape, shape, shape.name());
+                        }
+                    });
+            }
+            Operation::ContourOverlay { color, thickness } => {
+                ui.label("Контуры маски (по результату):");
+                ui.color_edit_button_srgb(color);
+                ui.add(egui::Slider::new(thickness, 1..=5).text("толщина"));
+            }
+            Operation::Grayscale(method) => {
+                egui::ComboBox::from_label("Способ")
+                    .selected_text(method.name())
+                    .show_ui(ui, |ui| {
+                        for m in GrayMethod::ALL {
+                            ui.selectable_value(method, m, m.name());
+                        }
+                    });
+            }
+            Operation::ChannelView { plane, tinted } => {
+                egui::ComboBox::from_label("Канал")
+                    .selected_text(plane.name())
+                    .show_ui(ui, |ui| {
+                        for p in ChannelPlane::ALL {
+                            ui.selectable_value(plane, p, p.name());
+                        }
+                    });
+                ui.add_enabled(
+                    matches!(plane, ChannelPlane::Red | ChannelPlane::Green | ChannelPlane::Blue),
+                    egui::Checkbox::new(tinted, "цветной канал"),
+                );
+            }
+            Operation::ChannelMap(map) => {
+                for (slot, source) in ["R ←", "G ←", "B ←"].into_iter().zip(map.iter_mut()) {
+                    egui::ComboBox::from_label(slot)
+                        .selected_text(source.name())
+                        .show_ui(ui, |ui| {
+                            for c in ChannelSource::ALL {
+                                ui.selectable_value(source, c, c.name());
+                            }
+                        });
+                }
+                if ui.button("RGB→BGR").clicked() {
+                    *map = [ChannelSource::Blue, ChannelSource::Green, ChannelSource::Red];
+                }
+                if ui.button("только красный").clicked() {
+                    *map = [ChannelSource::Red, ChannelSource::Zero, ChannelSource::Zero];
+                }
+            }
+            Operation::LinearContrast { low_pct, high_pct, per_channel } => {
+                ui.label("процентили:");
+                ui.add(egui::DragValue::new(low_pct).speed(0.1).range(0.0..=49.0).suffix("%"));
+                ui.add(egui::DragValue::new(high_pct).speed(0.1).range(51.0..=100.0).suffix("%"));
+                ui.checkbox(per_channel, "по каналам");
+            }
+            Operation::MultiOtsu { classes } => {
+                ui.add(egui::Slider::new(classes, 2..=4).text("классов"));
+            }
+            Operation::Otsu { mask } => mask.ui(ui),
+            Operation::TiledOtsu { grid, interpolate } => {
+                ui.add(egui::Slider::new(grid, 1..=16).text("сетка"));
+                ui.checkbox(interpolate, "интерполяция");
+            }
+            Operation::ManualThreshold { threshold, mask } => {
+                ui.add(egui::Slider::new(threshold, 0..=255).text("порог"));
+                mask.ui(ui);
+            }
+            Operation::Hysteresis { low, high } => {
+                ui.add(egui::Slider::new(low, 0..=255).text("Нижний порог"));
+                ui.add(egui::Slider::new(high, 0..=255).text("Верхний порог"));
+                *low = (*low).min(*high);
+            }
+            Operation::LocalThreshold { method, window, k, c, r } => {
+                egui::ComboBox::from_label("Локальный метод")
+                    .selected_text(method.name())
+                    .show_ui(ui, |ui| {
+                        for m in LocalMethod::ALL {
+                            ui.selectable_value(method, m, m.name());
+                        }
+                    });
+                ui.add(egui::Slider::new(window, 3..=99).text("Окно"));
+                // Окно с центром в пикселе должно быть нечетным
+                *window |= 1;
+                if method.uses_k() {
+                    ui.add(egui::Slider::new(k, -1.0..=1.0).text("k"));
+                    if *method == LocalMethod::Sauvola {
+                        ui.add(egui::Slider::new(r, 1.0..=255.0).text("R"));
+                    }
+                } else {
+                    ui.add(egui::Slider::new(c, -50.0..=50.0).text("C"));
+                }
+            }
+            Operation::HsvRange { range, mask } => {
+                ui.label("H:");
+                ui.add(egui::Slider::new(&mut range.hue.0, 0.0..=360.0).text("от"));
+                ui.add(egui::Slider::new(&mut range.hue.1, 0.0..=360.0).text("до"));
+                ui.label("S:");
+                ui.add(egui::Slider::new(&mut range.saturation.0, 0.0..=1.0).text("от"));
+                ui.add(egui::Slider::new(&mut range.saturation.1, 0.0..=1.0).text("до"));
+                ui.label("V:");
+                ui.add(egui::Slider::new(&mut range.value.0, 0.0..=1.0).text("от"));
+                ui.add(egui::Slider::new(&mut range.value.1, 0.0..=1.0).text("до"));
+                // Тон может переходить через 0°, а S и V - нет
+                range.saturation.0 = range.saturation.0.min(range.saturation.1);
+                range.value.0 = range.value.0.min(range.value.1);
+                mask.ui(ui);
+            }
+            Operation::FloodSelect { tolerance, connectivity, by_color, .. } => {
+                ui.label("Волшебная палочка (щелчок по оригиналу):");
+                ui.add(egui::Slider::new(tolerance, 0..=255).text("Допуск"));
+                connectivity_ui(ui, connectivity);
+                ui.checkbox(by_color, "по цвету");
+            }
+            Operation::RemoveSmallComponents { connectivity, min_area } => {
+                ui.label("Компоненты (по результату):");
+                connectivity_ui(ui, connectivity);
+                ui.add(egui::Slider::new(min_area, 1..=1000).text("N"));
+            }
+            Operation::ColorizeComponents { connectivity, min_area } => {
+                ui.label("Компоненты (по результату):");
+                connectivity_ui(ui, connectivity);
+                ui.add(egui::Slider::new(min_area, 1..=1000).text("мин. площадь"));
+            }
+            Operation::Brightness { value, mode } => {
+                ui.add(egui::Slider::new(value, -255..=255).text("Сдвиг яркости"));
+                egui::ComboBox::from_id_salt("brightness_mode")
+                    .selected_text(mode.name())
+                    .show_ui(ui, |ui| {
+                        for m in BrightnessMode::ALL {
+                            ui.selectable_value(mode, m, m.name());
+                        }
+                    });
+            }
+            Operation::Gamma(gamma) => {
+                ui.add(egui::Slider::new(gamma, 0.1..=5.0).logarithmic(true).text("γ"));
+            }
+            Operation::Contrast(factor) => {
+                ui.add(egui::Slider::new(factor, 0.1..=3.0).text("коэффициент"));
+            }
+            Operation::Tone { brightness, contrast, gamma } => {
+                ui.add(egui::Slider::new(brightness, -255..=255).text("сдвиг"));
+                ui.add(egui::Slider::new(contrast, 0.1..=3.0).text("контраст"));
+                ui.add(egui::Slider::new(gamma, 0.1..=5.0).logarithmic(true).text("γ"));
+            }
+            Operation::Exposure(ev) => {
+                ui.add(egui::Slider::new(ev, -4.0..=4.0).step_by(0.1).text("EV"));
+            }
+            Operation::Saturation(factor) => {
+                ui.add(egui::Slider::new(factor, 0.0..=3.0).text("множитель S"));
+            }
+            Operation::Vibrance(amount) => {
+                ui.add(egui::Slider::new(amount, -1.0..=1.0).text("сила"));
+            }
+            Operation::HueRotate(degrees) => {
+                ui.add(egui::Slider::new(degrees, -180.0..=180.0).text("градусов"));
+            }
+            Operation::HueReplace { center, width, target, feather } => {
+                ui.add(egui::Slider::new(center, 0.0..=360.0).text("тон"));
+                ui.add(egui::Slider::new(width, 0.0..=180.0).text("±"));
+                ui.add(egui::Slider::new(target, 0.0..=360.0).text("новый тон"));
+                ui.add(egui::Slider::new(feather, 0.0..=90.0).text("растушевка"));
+            }
+            Operation::TemperatureTint { temperature, tint } => {
+                ui.add(egui::Slider::new(temperature, -100.0..=100.0).text("температура"));
+                ui.add(egui::Slider::new(tint, -100.0..=100.0).text("оттенок"));
+            }
+            Operation::Levels { black, white, gamma, target } => {
+                ui.add(egui::Slider::new(black, 0..=254).text("черная точка"));
+                ui.add(egui::Slider::new(white, 1..=255).text("белая точка"));
+                // Точки не могут пересечься: двигается та, что осталась позади
+                if *black >= *white {
+                    *white = black.saturating_add(1);
+                }
+                ui.add(egui::Slider::new(gamma, 0.1..=5.0).logarithmic(true).text("γ"));
+                target_ui(ui, target);
+                let lut = levels_lut(*black, *white, *gamma);
+                let middle = ((*black as u16 + *white as u16) / 2) as u8;
+                ui.label(format!("{black}→0, {middle}→{}, {white}→255", lut[middle as usize]));
+            }
+            Operation::Curves { points, target } => {
+                curve_editor(ui, points);
+                ui.vertical(|ui| {
+                    ui.radio_value(target, LutTarget::Rgb, "RGB");
+                    ui.radio_value(target, LutTarget::Value, "канал V");
+                    if ui.button("Сбросить кривую").clicked() {
+                        *points = IDENTITY_CURVE.to_vec();
+                    }
+                });
+            }
+            Operation::GaussianNoise { sigma, fixed_seed, seed } => {
+                ui.label("Шум:");
+                ui.add(egui::Slider::new(sigma, 0.0..=100.0).text("σ"));
+                ui.checkbox(fixed_seed, "фиксированное зерно");
+                ui.add_enabled(*fixed_seed, egui::DragValue::new(seed));
+            }
+            Operation::Median { radius } => {
+                ui.label("Фильтры:");
+                ui.add(egui::Slider::new(radius, 1..=10).text("радиус"));
+            }
+            Operation::Kuwahara { radius } => {
+                ui.add(egui::Slider::new(radius, 1..=8).text("радиус"));
+            }
+            Operation::BoxBlur { radius, iterations } => {
+                ui.add(egui::Slider::new(radius, 1..=50).text("радиус"));
+                ui.add(egui::Slider::new(iterations, 1..=5).text("итераций"));
+            }
+            Operation::GaussianBlur { sigma } => {
+                ui.add(egui::Slider::new(sigma, 0.5..=25.0).text("σ"));
+            }
+            Operation::MotionBlur { length, angle } => {
+                ui.add(egui::Slider::new(length, 3..=101).text("длина"));
+                // Ядро симметрично относительно пикселя
+                *length |= 1;
+                ui.add(egui::Slider::new(angle, 0.0..=180.0).text("угол"));
+            }
+            Operation::Sharpen { strength, show_laplacian } => {
+                ui.add(egui::Slider::new(strength, 0.0..=3.0).text("сила"));
+                ui.checkbox(show_laplacian, "показать лапласиан");
+            }
+            Operation::UnsharpMask { sigma, amount, threshold } => {
+                ui.add(egui::Slider::new(sigma, 0.5..=25.0).text("радиус (σ)"));
+                ui.add(egui::Slider::new(amount, 0.0..=500.0).text("сила, %"));
+                ui.add(egui::Slider::new(threshold, 0..=255).text("порог"));
+            }
+            Operation::Emboss { direction, colored } => {
+                egui::ComboBox::from_label("Свет")
+                    .selected_text(direction.name())
+                    .show_ui(ui, |ui| {
+                        for d in LightDirection::ALL {
+                            ui.selectable_value(direction, d, d.name());
+                        }
+                    });
+                ui.checkbox(colored, "цветное");
+            }
+            Operation::Gradient { operator, output } => {
+                egui::ComboBox::from_label("Оператор")
+                    .selected_text(operator.name())
+                    .show_ui(ui, |ui| {
+                        for o in GradientOperator::ALL {
+                            ui.selectable_value(operator, o, o.name());
+                        }
+                    });
+                egui::ComboBox::from_label("Вывод")
+                    .selected_text(output.name())
+                    .show_ui(ui, |ui| {
+                        for o in GradientOutput::ALL {
+                            ui.selectable_value(output, o, o.name());
+                        }
+                    });
+            }
+            Operation::Canny { sigma, low, high } => {
+                ui.add(egui::Slider::new(sigma, 0.5..=10.0).text("σ"));
+                ui.add(egui::Slider::new(low, 0..=255).text("нижний"));
+                ui.add(egui::Slider::new(high, 0..=255).text("верхний"));
+                *low = (*low).min(*high);
+            }
+            Operation::HoughLines { theta_step, threshold, count, output, color } => {
+                ui.add(egui::Slider::new(theta_step, 0.25..=5.0).text("шаг θ"));
+                ui.add(egui::Slider::new(threshold, 1..=1000).text("мин. голосов"));
+                ui.add(egui::Slider::new(count, 1..=50).text("прямых"));
+                egui::ComboBox::from_id_salt("hough_output")
+                    .selected_text(output.name())
+                    .show_ui(ui, |ui| {
+                        for mode in HoughOutput::ALL {
+                            ui.selectable_value(output, mode, mode.name());
+                        }
+                    });
+                ui.color_edit_button_srgb(color);
+            }
+            Operation::Sepia(intensity) => {
+                ui.label("Эффекты:");
+                ui.add(egui::Slider::new(intensity, 0.0..=1.0).text("интенсивность"));
+            }
+            Operation::Solarize(threshold) => {
+                ui.add(egui::Slider::new(threshold, 0..=255).text("порог"));
+            }
+            Operation::Posterize { levels, target } => {
+                ui.add(egui::Slider::new(levels, 2..=64).text("уровней"));
+                target_ui(ui, target);
+            }
+            Operation::Quantize { colors } => {
+                ui.add(egui::Slider::new(colors, 2..=256).text("цветов"));
+            }
+            Operation::Pixelate { block } => {
+                ui.add(egui::Slider::new(block, 2..=128).text("размер клетки"));
+            }
+            Operation::Halftone { cell, angle } => {
+                ui.add(egui::Slider::new(cell, 4..=64).text("клетка"));
+                ui.add(egui::Slider::new(angle, 0.0..=90.0).text("угол растра"));
+            }
+            Operation::Vignette { strength, radius, feather } => {
+                ui.add(egui::Slider::new(strength, -1.0..=1.0).text("сила"));
+                ui.add(egui::Slider::new(radius, 0.0..=1.0).text("радиус"));
+                ui.add(egui::Slider::new(feather, 0.0..=1.0).text("растушевка"));
+            }
+            Operation::Log { c, target } => {
+                ui.label("c:");
+                ui.add(egui::DragValue::new(c).speed(0.5).range(0.01..=1000.0))
+                    .on_hover_text("Общий для логарифма и экспоненты");
+                target_ui(ui, target);
+            }
+            Operation::Exp { base, target, .. } => {
+                ui.label("основание:");
+                ui.add(egui::DragValue::new(base).speed(0.05).range(1.01..=100.0));
+                target_ui(ui, target);
+            }
+            Operation::AutoContrast { clip_percent } => {
+                ui.label("отсечение, %:");
+                ui.add(egui::DragValue::new(clip_percent).speed(0.1).range(0.0..=10.0));
+            }
+            Operation::Equalization(space) => {
+                egui::ComboBox::from_label("Пространство")
+                    .selected_text(space.name())
+                    .show_ui(ui, |ui| {
+                        for s in EqualizationSpace::ALL {
+                            ui.selectable_value(space, s, s.name());
+                        }
+                    });
+            }
+            Operation::Watermark(watermark) => {
+                ui.label("Надпись:");
+                ui.text_edit_singleline(&mut watermark.text);
+                ui.add(egui::Slider::new(&mut watermark.size, 8.0..=200.0).text("размер"));
+                ui.add(egui::Slider::new(&mut watermark.opacity, 0.0..=1.0).text("непрозрачность"));
+                ui.color_edit_button_srgb(&mut watermark.color);
+                egui::ComboBox::from_id_salt("watermark_position")
+                    .selected_text(watermark.position.name())
+                    .show_ui(ui, |ui| {
+                        for position in WatermarkPosition::ALL {
+                            ui.selectable_value(&mut watermark.position, position, position.name());
+                        }
+                    });
+            }
+            Operation::Border { left, right, top, bottom, uniform, mode } => {
+                ui.checkbox(uniform, "одинаковые");
+                if *uniform {
+                    ui.add(egui::Slider::new(left, 0..=500).text("поля"));
+                    (*right, *top, *bottom) = (*left, *left, *left);
+                } else {
+                    for (side, name) in [(left, "слева"), (right, "справа"), (top, "сверху"), (bottom, "снизу")] {
+                        ui.label(name);
+                        ui.add(egui::DragValue::new(side).range(0..=5000));
+                    }
+                }
+                let fill = match *mode {
+                    BorderMode::Constant(color) => color,
+                    _ => [255; 3],
+                };
+                egui::ComboBox::from_id_salt("border_mode")
+                    .selected_text(mode.name())
+                    .show_ui(ui, |ui| {
+                        for kind in [BorderMode::Constant(fill), BorderMode::Replicate, BorderMode::Mirror] {
+                            ui.selectable_value(mode, kind, kind.name());
+                        }
+                    });
+                if let BorderMode::Constant(color) = mode {
+                    ui.color_edit_button_srgb(color);
+                }
+            }
+            Operation::Tile { columns, rows, mirror } => {
+                ui.label("столбцов");
+                ui.add(egui::DragValue::new(columns).range(1..=8));
+                ui.label("строк");
+                ui.add(egui::DragValue::new(rows).range(1..=8));
+                ui.checkbox(mirror, "с отражением");
+            }
+            Operation::Rotate { degrees, interpolation, expand, background } => {
+                ui.add(egui::Slider::new(degrees, -180.0..=180.0).text("угол").suffix("°"));
+                egui::ComboBox::from_id_salt("rotate_interpolation")
+                    .selected_text(interpolation.name())
+                    .show_ui(ui, |ui| {
+                        for kind in Interpolation::ALL {
+                            ui.selectable_value(interpolation, kind, kind.name());
+                        }
+                    });
+                ui.checkbox(expand, "расширить холст");
+                ui.label("фон");
+                ui.color_edit_button_srgb(background);
+            }
+            Operation::Resize { width, height, lock_aspect, filter, source } => {
+                let aspect = source.1 as f32 / source.0.max(1) as f32;
+                ui.label("ширина");
+                let width_changed = ui.add(egui::DragValue::new(width).range(1..=2 * MAX_DIMENSION)).changed();
+                ui.label("высота");
+                let height_changed = ui.add(egui::DragValue::new(height).range(1..=2 * MAX_DIMENSION)).changed();
+                ui.checkbox(lock_aspect, "сохранять пропорции");
+                if *lock_aspect && width_changed {
+                    *height = ((*width as f32 * aspect).round() as u32).max(1);
+                } else if *lock_aspect && height_changed {
+                    *width = ((*height as f32 / aspect).round() as u32).max(1);
+                }
+                egui::ComboBox::from_id_salt("resize_filter")
+                    .selected_text(filter.name())
+                    .show_ui(ui, |ui| {
+                        for kind in ResizeFilter::ALL {
+                            ui.selectable_value(filter, kind, kind.name());
+                        }
+                    });
+            }
+            Operation::FileLut { name, lut, error, target } => {
+                if ui.button("Загрузить LUT").clicked()
+                    && let Some(path) = rfd::FileDialog::new().add_filter("LUT", &["csv", "txt", "cube"]).pick_file()
+                {
+                    match load_lut(&path) {
+                        Ok(loaded) => {
+                            *name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                            *lut = Some(Box::new(loaded));
+                            *error = None;
+                        }
+                        Err(message) => *error = Some(message),
+                    }
+                }
+                ui.label(if lut.is_some() { name.as_str() } else { "(не загружен)" });
+                target_ui(ui, target);
+                if let Some(message) = error {
+                    ui.colored_label(egui::Color32::RED, message.as_str());
+                }
+            }
+            Operation::KernelLibrary { kernels, selected, error } => {
+                if ui.button("Загрузить ядра").clicked()
+                    && let Some(path) = rfd::FileDialog::new().add_filter("Ядра", &["txt"]).pick_file()
+                {
+                    match load_kernels(&path) {
+                        Ok(loaded) => {
+                            *kernels = loaded;
+                            *selected = 0;
+                            *error = None;
+                        }
+                        Err(message) => *error = Some(message),
+                    }
+                }
+                if let Some(current) = kernels.get(*selected) {
+                    egui::ComboBox::from_label("Ядро")
+                        .selected_text(current.name.as_str())
+                        .show_ui(ui, |ui| {
+                            for (i, kernel) in kernels.iter().enumerate() {
+                                ui.selectable_value(selected, i, kernel.name.as_str());
+                            }
+                        });
+                } else {
+                    ui.label("(ядра не загружены)");
+                }
+                if let Some(message) = error {
+                    ui.colored_label(egui::Color32::RED, message.as_str());
+                }
+            }
+            Operation::FrequencyFilter { filter, cutoff } => {
+                egui::ComboBox::from_label("Фильтр")
+                    .selected_text(filter.name())
+                    .show_ui(ui, |ui| {
+                        for f in FrequencyFilter::ALL {
+                            ui.selectable_value(filter, f, f.name());
+                        }
+                    });
+                ui.add(egui::Slider::new(cutoff, 1.0..=512.0).logarithmic(true).text("радиус среза"));
+            }
+            Operation::Spectrum => {}
+            Operation::GradientMap { shadow, highlight, midtone } => {
+                ui.label("тени");
+                ui.color_edit_button_srgb(shadow);
+                let mut use_midtone = midtone.is_some();
+                ui.checkbox(&mut use_midtone, "средний тон");
+                match (use_midtone, midtone.as_mut()) {
+                    (true, Some((color, position))) => {
+                        ui.color_edit_button_srgb(color);
+                        ui.add(egui::Slider::new(position, 0.05..=0.95).text("положение"));
+                    }
+                    (true, None) => {
+                        let middle = std::array::from_fn(|c| ((shadow[c] as u16 + highlight[c] as u16) / 2) as u8);
+                        *midtone = Some((middle, 0.5));
+                    }
+                    (false, _) => *midtone = None,
+                }
+                ui.label("света");
+                ui.color_edit_button_srgb(highlight);
+            }
+            Operation::Pseudocolor(map) => {
+                egui::ComboBox::from_label("Палитра")
+                    .selected_text(map.name())
+                    .show_ui(ui, |ui| {
+                        for m in Colormap::ALL {
+                            ui.selectable_value(map, m, m.name());
+                        }
+                    });
+            }
+            Operation::Difference { gain, mode } => {
+                ui.label("Сравнение с оригиналом:");
+                ui.add(egui::Slider::new(gain, 1.0..=20.0).text("усиление"));
+                egui::ComboBox::from_id_salt("difference_mode")
+                    .selected_text(mode.name())
+                    .show_ui(ui, |ui| {
+                        for m in DifferenceMode::ALL {
+                            ui.selectable_value(mode, m, m.name());
+                        }
+                    });
+            }
+            Operation::Blend { mode, opacity, resize, .. } => {
+                egui::ComboBox::from_id_salt("blend_mode")
+                    .selected_text(mode.name())
+                    .show_ui(ui, |ui| {
+                        for kind in BlendMode::ALL {
+                            ui.selectable_value(mode, kind, kind.name());
+                        }
+                    });
+                ui.add(egui::Slider::new(opacity, 0.0..=1.0).text("непрозрачность"));
+                ui.checkbox(resize, "растянуть B");
+            }
+        }
+    }
+
+    /// Параметры для журнала обработки
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Operation::Triangle
+            | Operation::Kapur
+            | Operation::Isodata
+            | Operation::GrayWorld
+            | Operation::Invert
+            | Operation::InvertValue
+            | Operation::Skeletonize
+            | Operation::FillHoles => Vec::new(),
+            Operation::Erode(element)
+            | Operation::Dilate(element)
+            | Operation::Open(element)
+            | Operation::Close(element) => {
+                vec![("радиус", element.radius.to_string()), ("элемент", element.shape.name().to_string())]
+            }
+            Operation::ContourOverlay { thickness, .. } => vec![("толщина", thickness.to_string())],
+            Operation::Grayscale(method) => vec![("способ", method.name().to_string())],
+            Operation::ChannelView { plane, tinted } => {
+                vec![("канал", plane.name().to_string()), ("цветной", tinted.to_string())]
+            }
+            Operation::ChannelMap(map) => {
+                vec![("каналы", map.iter().map(|c| c.name()).collect::<Vec<_>>().join(""))]
+            }
+            Operation::Otsu { mask } => mask.params(),
+            Operation::LinearContrast { low_pct, high_pct, per_channel } => vec![
+                ("нижний, %", format!("{low_pct:.1}")),
+                ("верхний, %", format!("{high_pct:.1}")),
+                ("по каналам", per_channel.to_string()),
+            ],
+            Operation::MultiOtsu { classes } => vec![("классов", classes.to_string())],
+            Operation::TiledOtsu { grid, interpolate } => {
+                vec![("сетка", format!("{grid}×{grid}")), ("интерполяция", interpolate.to_string())]
+            }
+            Operation::ManualThreshold { threshold, mask } => {
+                let mut params = vec![("порог", threshold.to_string())];
+                params.extend(mask.params());
+                params
+            }
+            Operation::Hysteresis { low, high } => vec![("нижний", low.to_string()), ("верхний", high.to_string())],
+            Operation::LocalThreshold { method, window, k, c, r } => {
+                let param = if method.uses_k() { ("k", format!("{k:.2}")) } else { ("C", format!("{c:.2}")) };
+                let mut params = vec![("метод", method.name().to_string()), ("окно", window.to_string()), param];
+                if *method == LocalMethod::Sauvola {
+                    params.push(("R", format!("{r:.0}")));
+                }
+                params
+            }
+            Operation::HsvRange { range, mask } => {
+                let mut params = vec![
+                    ("H", format!("{:.0}..{:.0}", range.hue.0, range.hue.1)),
+                    ("S", format!("{:.2}..{:.2}", range.saturation.0, range.saturation.1)),
+                    ("V", format!("{:.2}..{:.2}", range.value.0, range.value.1)),
+                ];
+                params.extend(mask.params());
+                params
+            }
+            Operation::FloodSelect { seed, tolerance, connectivity, by_color } => {
+                let seed = seed.map(|(x, y)| format!("{x},{y}")).unwrap_or_else(|| "-".to_string());
+                vec![
+                    ("точка", seed),
+                    ("допуск", tolerance.to_string()),
+                    ("связность", format!("{connectivity:?}")),
+                    ("по цвету", by_color.to_string()),
+                ]
+            }
+            Operation::RemoveSmallComponents { connectivity, min_area } => {
+                vec![("N", min_area.to_string()), ("связность", format!("{connectivity:?}"))]
+            }
+            Operation::ColorizeComponents { connectivity, min_area } => {
+                vec![("связность", format!("{connectivity:?}")), ("мин. площадь", min_area.to_string())]
+            }
+            Operation::Brightness { value, mode } => {
+                vec![("сдвиг", value.to_string()), ("режим", mode.name().to_string())]
+            }
+            Operation::Gamma(gamma) => vec![("γ", format!("{gamma:.2}"))],
+            Operation::Contrast(factor) => vec![("коэффициент", format!("{factor:.2}"))],
+            Operation::Tone { brightness, contrast, gamma } => vec![
+                ("сдвиг", brightness.to_string()),
+                ("контраст", format!("{contrast:.2}")),
+                ("γ", format!("{gamma:.2}")),
+            ],
+            Operation::Exposure(ev) => vec![("EV", format!("{ev:+.1}"))],
+            Operation::Saturation(factor) => vec![("множитель", format!("{factor:.2}"))],
+            Operation::Vibrance(amount) => vec![("сила", format!("{amount:+.2}"))],
+            Operation::HueRotate(degrees) => vec![("угол", format!("{degrees:+.0}"))],
+            Operation::HueReplace { center, width, target, feather } => vec![
+                ("тон", format!("{center:.0}")),
+                ("ширина", format!("{width:.0}")),
+                ("новый тон", format!("{target:.0}")),
+                ("растушевка", format!("{feather:.0}")),
+            ],
+            Operation::TemperatureTint { temperature, tint } => {
+                vec![("температура", format!("{temperature:+.0}")), ("оттенок", format!("{tint:+.0}"))]
+            }
+            Operation::Levels { black, white, gamma, target } => vec![
+                ("черная точка", black.to_string()),
+                ("белая точка", white.to_string()),
+                ("γ", format!("{gamma:.2}")),
+                ("канал", format!("{target:?}")),
+            ],
+            Operation::Curves { points, target } => {
+                let points: Vec<String> = points.iter().map(|(x, y)| format!("{x:.0}:{y:.0}")).collect();
+                vec![("точки", points.join(" ")), ("канал", format!("{target:?}"))]
+            }
+            Operation::GaussianNoise { sigma, fixed_seed, seed } => {
+                let seed = if *fixed_seed { seed.to_string() } else { "случайное".to_string() };
+                vec![("σ", format!("{sigma:.1}")), ("зерно", seed)]
+            }
+            Operation::Median { radius } => vec![("радиус", radius.to_string())],
+            Operation::Kuwahara { radius } => vec![("радиус", radius.to_string())],
+            Operation::BoxBlur { radius, iterations } => {
+                vec![("радиус", radius.to_string()), ("итераций", iterations.to_string())]
+            }
+            Operation::GaussianBlur { sigma } => vec![("σ", format!("{sigma:.1}"))],
+            Operation::MotionBlur { length, angle } => {
+                vec![("длина", length.to_string()), ("угол", format!("{angle:.1}"))]
+            }
+            Operation::Sharpen { strength, show_laplacian } => {
+                vec![("сила", format!("{strength:.2}")), ("лапласиан", show_laplacian.to_string())]
+            }
+            Operation::UnsharpMask { sigma, amount, threshold } => vec![
+                ("σ", format!("{sigma:.1}")),
+                ("сила, %", format!("{amount:.0}")),
+                ("порог", threshold.to_string()),
+            ],
+            Operation::Emboss { direction, colored } => {
+                vec![("свет", direction.name().to_string()), ("цветное", colored.to_string())]
+            }
+            Operation::Gradient { operator, output } => {
+                vec![("оператор", operator.name().to_string()), ("вывод", output.name().to_string())]
+            }
+            Operation::Canny { sigma, low, high } => vec![
+                ("σ", format!("{sigma:.1}")),
+                ("нижний", low.to_string()),
+                ("верхний", high.to_string()),
+            ],
+            Operation::HoughLines { theta_step, threshold, count, output, .. } => vec![
+                ("шаг θ", format!("{theta_step:.2}")),
+                ("мин. голосов", threshold.to_string()),
+                ("прямых", count.to_string()),
+                ("вывод", output.name().to_string()),
+            ],
+            Operation::Sepia(intensity) => vec![("интенсивность", format!("{intensity:.2}"))],
+            Operation::Solarize(threshold) => vec![("порог", threshold.to_string())],
+            Operation::Posterize { levels, target } => {
+                vec![("уровней", levels.to_string()), ("канал", format!("{target:?}"))]
+            }
+            Operation::Quantize { colors } => vec![("цветов", colors.to_string())],
+            Operation::Pixelate { block } => vec![("клетка", block.to_string())],
+            Operation::Halftone { cell, angle } => vec![("клетка", cell.to_string()), ("угол", format!("{angle:.0}"))],
+            Operation::Vignette { strength, radius, feather } => vec![
+                ("сила", format!("{strength:.2}")),
+                ("радиус", format!("{radius:.2}")),
+                ("растушевка", format!("{feather:.2}")),
+            ],
+            Operation::Log { c, target } => vec![("c", format!("{c:.3}")), ("канал", format!("{target:?}"))],
+            Operation::Exp { c, base, target } => vec![
+                ("c", format!("{c:.3}")),
+                ("основание", format!("{base:.3}")),
+                ("канал", format!("{target:?}")),
+            ],
+            Operation::AutoContrast { clip_percent } => vec![("отсечение, %", format!("{clip_percent:.2}"))],
+            Operation::Equalization(space) => vec![("пространство", space.name().to_string())],
+            Operation::Watermark(watermark) => vec![
+                ("текст", watermark.text.clone()),
+                ("размер", format!("{:.0}", watermark.size)),
+                ("непрозрачность", format!("{:.2}", watermark.opacity)),
+                ("место", watermark.position.name().to_string()),
+            ],
+            Operation::Border { left, right, top, bottom, mode, .. } => vec![
+                ("поля", format!("{left}, {right}, {top}, {bottom}")),
+                ("заполнение", mode.name().to_string()),
+            ],
+            Operation::Tile { columns, rows, mirror } => vec![
+                ("сетка", format!("{columns}×{rows}")),
+                ("отражение", mirror.to_string()),
+            ],
+            Operation::Rotate { degrees, interpolation, expand, .. } => vec![
+                ("угол", format!("{degrees:+.1}°")),
+                ("интерполяция", interpolation.name().to_string()),
+                ("холст", if *expand { "расширен" } else { "исходный" }.to_string()),
+            ],
+            Operation::Resize { width, height, filter, .. } => {
+                vec![("размер", format!("{width}×{height}")), ("фильтр", filter.name().to_string())]
+            }
+            Operation::FileLut { name, target, .. } => {
+                vec![("файл", name.clone()), ("канал", format!("{target:?}"))]
+            }
+            Operation::KernelLibrary { kernels, selected, .. } => {
+                let name = kernels.get(*selected).map(|k| k.name.clone()).unwrap_or_default();
+                vec![("ядро", name)]
+            }
+            Operation::FrequencyFilter { filter, cutoff } => {
+                vec![("фильтр", filter.name().to_string()), ("радиус среза", format!("{cutoff:.0}"))]
+            }
+            Operation::Spectrum => Vec::new(),
+            Operation::GradientMap { shadow, highlight, midtone } => {
+                let hex = |[r, g, b]: [u8; 3]| format!("#{r:02X}{g:02X}{b:02X}");
+                let mut params = vec![("тени", hex(*shadow)), ("света", hex(*highlight))];
+                if let Some((color, position)) = midtone {
+                    params.push(("средний тон", format!("{} @ {position:.2}", hex(*color))));
+                }
+                params
+            }
+            Operation::Pseudocolor(map) => vec![("палитра", map.name().to_string())],
+            Operation::Difference { gain, mode } => {
+                vec![("усиление", format!("{gain:.1}")), ("вид", mode.name().to_string())]
+            }
+            Operation::Blend { mode, opacity, resize, .. } => vec![
+                ("режим", mode.name().to_string()),
+                ("непрозрачность", format!("{opacity:.2}")),
+                ("растянуть", resize.to_string()),
+            ],
+        }
+    }
+
+    /// Выполняет операцию над `image`; `original` - загруженное изображение,
+    /// на которое накладываются результаты анализа маски
+    pub fn execute(&self, image: &DynamicImage, original: &DynamicImage) -> Result<OpOutput, String> {
+        let output = match *self {
+            Operation::LinearContrast { low_pct, high_pct, per_channel } => {
+                if per_channel {
+                    apply_linear_contrast_rgb(image, low_pct, high_pct).into()
+                } else {
+                    apply_linear_contrast_clipped(image, low_pct, high_pct).into()
+                }
+            }
+            Operation::Otsu { mask } => {
+                let (binary, threshold) = apply_otsu_threshold(image);
+                threshold_output((mask.apply(image, binary), threshold))
+            }
+            Operation::Triangle => threshold_output(apply_triangle_threshold(image)),
+            Operation::Kapur => threshold_output(apply_kapur_threshold(image)),
+            Operation::Isodata => threshold_output(apply_isodata_threshold(image)),
+            Operation::MultiOtsu { classes } => {
+                let (image, thresholds) = apply_multi_otsu(image, classes);
+                let thresholds: Vec<String> = thresholds.iter().map(|t| t.to_string()).collect();
+                let report = format!("Пороги: {}", thresholds.join(", "));
+                OpOutput { image, report: Some(report), threshold: None, palette: None }
+            }
+            Operation::TiledOtsu { grid, interpolate } => apply_tiled_otsu(image, grid, interpolate).into(),
+            Operation::ManualThreshold { threshold, mask } => {
+                mask.apply(image, apply_manual_threshold(image, threshold)).into()
+            }
+            Operation::Hysteresis { low, high } => apply_hysteresis_threshold(image, low, high).into(),
+            Operation::LocalThreshold { method, window, k, c, r } => {
+                let param = if method.uses_k() { k } else { c };
+                apply_local_threshold(image, method, window, param, r).into()
+            }
+            Operation::HsvRange { range, mask } => mask.apply(image, apply_hsv_range_mask(image, &range)).into(),
+            Operation::FloodSelect { seed, tolerance, connectivity, by_color } => {
+                let seed = seed.ok_or("не выбрана начальная точка")?;
+                apply_flood_select(image, seed, tolerance, connectivity, by_color)
+                    .ok_or("точка вне изображения")?
+                    .into()
+            }
+            Operation::RemoveSmallComponents { connectivity, min_area } => {
+                let (mask, was_binary) = binary_mask(image);
+                let (image, kept) = remove_small_components(&mask, connectivity, min_area);
+                OpOutput { image, report: Some(component_status(kept, was_binary)), threshold: None, palette: None }
+            }
+            Operation::ColorizeComponents { connectivity, min_area } => {
+                let (mask, was_binary) = binary_mask(image);
+                let (components, label_map) = label_components(&mask, connectivity);
+                let kept = components.iter().filter(|c| c.area >= min_area).count();
+                OpOutput {
+                    image: colorize_components(&label_map, &components, min_area),
+                    report: Some(component_status(kept, was_binary)),
+                    threshold: None,
+                    palette: None,
+                }
+            }
+            Operation::Erode(element) => apply_erode(image, element).into(),
+            Operation::Dilate(element) => apply_dilate(image, element).into(),
+            Operation::Open(element) => apply_open(image, element).into(),
+            Operation::Close(element) => apply_close(image, element).into(),
+            Operation::Skeletonize => {
+                let (image, iterations) = apply_skeletonize(image);
+                OpOutput { image, report: Some(format!("Итераций: {iterations}")), threshold: None, palette: None }
+            }
+            Operation::FillHoles => apply_fill_holes(image).into(),
+            Operation::ContourOverlay { color, thickness } => {
+                let (image, contours, length) = apply_contour_overlay(image, original, color, thickness)
+                    .ok_or("размеры маски и оригинала не совпадают")?;
+                let report = format!("Контуров: {contours}, длина: {length} пикс.");
+                OpOutput { image, report: Some(report), threshold: None, palette: None }
+            }
+            Operation::Grayscale(method) => apply_grayscale(image, method).into(),
+            Operation::ChannelView { plane, tinted } => apply_channel_view(image, plane, tinted).into(),
+            Operation::ChannelMap(map) => apply_channel_map(image, map).into(),
+            Operation::GrayWorld => {
+                let (image, [r, g, b]) = apply_gray_world_wb(image);
+                let report = format!("R×{r:.2} G×{g:.2} B×{b:.2}");
+                OpOutput { image, report: Some(report), threshold: None, palette: None }
+            }
+            Operation::Invert => apply_inversion(image).into(),
+            Operation::InvertValue => apply_value_inversion(image).into(),
+            Operation::Brightness { value, mode } => match mode {
+                BrightnessMode::Rgb => apply_brightness(image, value).into(),
+                BrightnessMode::Hsv => apply_brightness_hsv(image, value as f32 / 255.0).into(),
+            },
+            Operation::Gamma(gamma) => apply_gamma(image, gamma).into(),
+            Operation::Contrast(factor) => apply_contrast(image, factor).into(),
+            Operation::Tone { brightness, contrast, gamma } => apply_tone(image, brightness, contrast, gamma).into(),
+            Operation::Exposure(ev) => apply_exposure(image, ev).into(),
+            Operation::Saturation(factor) => apply_saturation(image, factor).into(),
+            Operation::Vibrance(amount) => apply_vibrance(image, amount).into(),
+            Operation::HueRotate(degrees) => apply_hue_rotate(image, degrees).into(),
+            Operation::HueReplace { center, width, target, feather } => {
+                apply_hue_replace(image, center, width, target, feather).into()
+            }
+            Operation::TemperatureTint { temperature, tint } => apply_temperature_tint(image, temperature, tint).into(),
+            Operation::Levels { black, white, gamma, target } => {
+                apply_levels(image, black, white, gamma, target).into()
+            }
+            Operation::Curves { ref points, target } => {
+                apply_curve(image, &curve_lut(points), target).into()
+            }
+            Operation::GaussianNoise { sigma, fixed_seed, seed } => {
+                apply_gaussian_noise(image, sigma, fixed_seed.then_some(seed)).into()
+            }
+            Operation::Median { radius } => apply_median_filter(image, radius).into(),
+            Operation::Kuwahara { radius } => apply_kuwahara(image, radius).into(),
+            Operation::BoxBlur { radius, iterations } => apply_box_blur(image, radius, iterations).into(),
+            Operation::GaussianBlur { sigma } => apply_gaussian_blur(image, sigma).into(),
+            Operation::MotionBlur { length, angle } => apply_motion_blur(image, length, angle).into(),
+            Operation::Sharpen { strength, show_laplacian } => {
+                apply_laplacian_sharpen(image, strength, show_laplacian).into()
+            }
+            Operation::UnsharpMask { sigma, amount, threshold } => {
+                apply_unsharp_mask(image, sigma, amount / 100.0, threshold).into()
+            }
+            Operation::Emboss { direction, colored } => apply_emboss(image, direction, colored).into(),
+            Operation::Gradient { operator, output } => apply_gradient(image, operator, output).into(),
+            Operation::Canny { sigma, low, high } => apply_canny(image, sigma, low, high).into(),
+            Operation::HoughLines { theta_step, threshold, count, output, color } => {
+                let (image, lines) = apply_hough_lines(image, theta_step, threshold, count, output, color);
+                let angles: Vec<String> = lines.iter().map(|line| format!("{:.1}°", line.theta)).collect();
+                let report = format!("Прямых: {} (θ: {})", lines.len(), angles.join(", "));
+                OpOutput { image, report: Some(report), threshold: None, palette: None }
+            }
+            Operation::Sepia(intensity) => apply_sepia(image, intensity).into(),
+            Operation::Solarize(threshold) => apply_solarize(image, threshold).into(),
+            Operation::Posterize { levels, target } => apply_posterize(image, levels, target).into(),
+            Operation::Quantize { colors } => {
+                let (image, palette) = apply_quantize(image, colors);
+                let report = format!("Цветов: {}", palette.len());
+                OpOutput { image, report: Some(report), threshold: None, palette: Some(palette) }
+            }
+            Operation::Pixelate { block } => apply_pixelate(image, block).into(),
+            Operation::Halftone { cell, angle } => apply_halftone(image, cell, angle).into(),
+            Operation::Vignette { strength, radius, feather } => {
+                apply_vignette(image, strength, radius, feather).into()
+            }
+            Operation::Log { c, target } => apply_log_transform(image, c, target).into(),
+            Operation::Exp { c, base, target } => apply_exp_transform(image, c, base, target).into(),
+            Operation::AutoContrast { clip_percent } => apply_auto_contrast(image, clip_percent).into(),
+            Operation::Equalization(space) => apply_histogram_equalization(image, space).into(),
+            Operation::Watermark(ref watermark) => apply_watermark(image, watermark).into(),
+            Operation::Border { left, right, top, bottom, mode, .. } => {
+                apply_border(image, left, right, top, bottom, mode).into()
+            }
+            Operation::Tile { columns, rows, mirror } => apply_tile(image, columns, rows, mirror)?.into(),
+            Operation::Rotate { degrees, interpolation, expand, background } => {
+                apply_rotate(image, degrees, interpolation, expand, background).into()
+            }
+            Operation::Resize { width, height, filter, .. } => apply_resize(image, width, height, filter)?.into(),
+            Operation::FileLut { ref lut, target, .. } => {
+                let lut = lut.as_ref().ok_or("LUT не загружен")?;
+                apply_lut(image, lut, target).into()
+            }
+            Operation::KernelLibrary { ref kernels, selected, .. } => {
+                let kernel = kernels.get(selected).ok_or("ядра не загружены")?;
+                apply_kernel(image, kernel).into()
+            }
+            Operation::FrequencyFilter { filter, cutoff } => apply_frequency_filter(image, filter, cutoff).into(),
+            Operation::Spectrum => spectrum_image(image).into(),
+            Operation::GradientMap { shadow, highlight, midtone } => {
+                let mut stops = vec![(0.0, shadow), (1.0, highlight)];
+                stops.extend(midtone.map(|(color, position)| (position, color)));
+                apply_gradient_map(image, &stops).into()
+            }
+            Operation::Pseudocolor(map) => apply_colormap(image, map).into(),
+            Operation::Difference { gain, mode } => apply_difference(original, image, gain, mode)
+                .ok_or("размеры оригинала и результата не совпадают")?
+                .into(),
+            Operation::Blend { mode, opacity, resize, ref second } => {
+                let second = second.as_ref().ok_or("изображение B не загружено")?;
+                apply_blend(image, second, mode, opacity, resize).into()
+            }
+        };
+        Ok(output)
+    }
+}
+
+/// Редактор кривой: перетаскивание точек, щелчок добавляет точку,
+/// щелчок правой кнопкой по точке удаляет ее (две последние остаются)
+fn curve_editor(ui: &mut egui::Ui, points: &mut Vec<(f32, f32)>) {
+    const SIZE: f32 = 200.0;
+    const PICK_RADIUS: f32 = 8.0;
+
+    let (response, painter) = ui.allocate_painter(egui::vec2(SIZE, SIZE), egui::Sense::click_and_drag());
+    let rect = response.rect;
+    let to_screen = |(x, y): (f32, f32)| egui::pos2(rect.min.x + x / 255.0 * SIZE, rect.max.y - y / 255.0 * SIZE);
+    let from_screen = |pos: egui::Pos2| {
+        let x = (pos.x - rect.min.x) / SIZE * 255.0;
+        let y = (rect.max.y - pos.y) / SIZE * 255.0;
+        (x.clamp(0.0, 255.0), y.clamp(0.0, 255.0))
+    };
+    let nearest = |points: &[(f32, f32)], pos: egui::Pos2| {
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| (i, to_screen(p).distance(pos)))
+            .filter(|&(_, d)| d <= PICK_RADIUS)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    };
+
+    let pointer = response.interact_pointer_pos();
+    if response.drag_started()
+        && let Some(index) = pointer.and_then(|pos| nearest(points, pos))
+    {
+        ui.data_mut(|d| d.insert_temp(response.id, index));
+    }
+    if response.dragged() {
+        let dragging = ui.data(|d| d.get_temp::<usize>(response.id));
+        if let (Some(index), Some(pos)) = (dragging, pointer) {
+            // Точка не может обогнать соседей по x
+            let (x, y) = from_screen(pos);
+            let min_x = if index > 0 { points[index - 1].0 + 1.0 } else { 0.0 };
+            let max_x = points.get(index + 1).map_or(255.0, |p| p.0 - 1.0);
+            points[index] = (x.clamp(min_x, max_x.max(min_x)), y);
+        }
+    }
+    if response.drag_stopped() {
+        ui.data_mut(|d| d.remove::<usize>(response.id));
+    }
+    if response.clicked()
+        && let Some(pos) = pointer
+        && nearest(points, pos).is_none()
+    {
+        let point = from_screen(pos);
+        let index = points.partition_point(|p| p.0 < point.0);
+        if points.get(index).is_none_or(|p| p.0 != point.0) {
+            points.insert(index, point);
+        }
+    }
+    if response.secondary_clicked()
+        && points.len() > 2
+        && let Some(index) = pointer.and_then(|pos| nearest(points, pos))
+    {
+        points.remove(index);
+    }
+
+    let stroke_color = ui.visuals().text_color();
+    let weak_color = ui.visuals().weak_text_color();
+    painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, weak_color));
+    painter.line_segment([to_screen((0.0, 0.0)), to_screen((255.0, 255.0))], egui::Stroke::new(1.0, weak_color));
+    let lut = curve_lut(points);
+    let curve: Vec<egui::Pos2> = lut.iter().enumerate().map(|(x, &y)| to_screen((x as f32, y as f32))).collect();
+    painter.add(egui::Shape::line(curve, egui::Stroke::new(1.5, stroke_color)));
+    for &point in points.iter() {
+        painter.circle_filled(to_screen(point), 4.0, egui::Color32::LIGHT_BLUE);
+    }
+}
+
+fn connectivity_ui(ui: &mut egui::Ui, connectivity: &mut Connectivity) {
+    ui.radio_value(connectivity, Connectivity::Four, "4-связность");
+    ui.radio_value(connectivity, Connectivity::Eight, "8-связность");
+}
+
+fn target_ui(ui: &mut egui::Ui, target: &mut LutTarget) {
+    let mut on_value = *target == LutTarget::Value;
+    ui.checkbox(&mut on_value, "по каналу V");
+    *target = if on_value { LutTarget::Value } else { LutTarget::Rgb };
+}
+
+fn component_status(count: usize, was_binary: bool) -> String {
+    if was_binary {
+        format!("Компонент: {count}")
+    } else {
+        format!("Компонент: {count} (изображение бинаризовано по порогу 128)")
+    }
+}
+
+/// Результат глобальной бинаризации с выбранным порогом в отчете
+fn threshold_output((image, threshold): (DynamicImage, Option<u8>)) -> OpOutput {
+    let report = threshold.map_or_else(|| "Пустое изображение".to_string(), |t| format!("Порог: {t}"));
+    OpOutput { image, report: Some(report), threshold, palette: None }
+}
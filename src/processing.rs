@@ -0,0 +1,5773 @@
+//! Алгоритмы обработки изображений: цветовые модели, поточечные операции,
+//! бинаризация, фильтры и статистика
+
+use image::{DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage, RgbaImage};
+use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
+
+use crate::bitmap_font;
+
+fn rgb_to_hsv(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+    let r_ = r as f32 / 255.0;
+    let g_ = g as f32 / 255.0;
+    let b_ = b as f32 / 255.0;
+
+    let c_max = r_.max(g_).max(b_);
+    let c_min = r_.min(g_).min(b_);
+    let delta = c_max - c_min;
+    
+    let hue = if delta == 0.0 {
+        0.0
+    } else if c_max == r_ {
+        60.0 * (((g_ - b_) / delta) % 6.0)
+    } else if c_max == g_ {
+        60.0 * (((b_ - r_) / delta) + 2.0)
+    } else { // c_max == b_
+        60.0 * (((r_ - g_) / delta) + 4.0)
+    };
+    let h = if hue < 0.0 { hue + 360.0 } else { hue };
+    
+    let s = if c_max == 0.0 { 0.0 } else { delta / c_max };
+    
+    let v = c_max;
+
+    (h, s, v)
+}
+
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
+    // Приводим тон к [0, 360): 360.0 и мелкие отрицательные значения после
+    // вычислений с плавающей точкой иначе попадают не в тот сектор
+    let h = h.rem_euclid(360.0);
+    let h = if h >= 360.0 { 0.0 } else { h };
+
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+
+    let (r_, g_, b_) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x), // 300.0..360.0
+    };
+
+    // Округляем, а не отбрасываем дробную часть, иначе круговое
+    // преобразование rgb -> hsv -> rgb постепенно затемняет изображение
+    let r = ((r_ + m) * 255.0).round() as u8;
+    let g = ((g_ + m) * 255.0).round() as u8;
+    let b = ((b_ + m) * 255.0).round() as u8;
+
+    (r, g, b)
+}
+
+/// Перевод в YCbCr (BT.601, полный диапазон, как в JPEG)
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> (f32, f32, f32) {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = 128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b;
+    let cr = 128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b;
+    (y, cb, cr)
+}
+
+fn ycbcr_to_rgb(y: f32, cb: f32, cr: f32) -> (u8, u8, u8) {
+    let cb = cb - 128.0;
+    let cr = cr - 128.0;
+    let r = y + 1.402 * cr;
+    let g = y - 0.344136 * cb - 0.714136 * cr;
+    let b = y + 1.772 * cb;
+    let to_u8 = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+/// Линейные значения всех 256 уровней sRGB, вычисляются один раз
+fn srgb_decode_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [0.0f32; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            *entry = srgb_decode(i as f32 / 255.0);
+        }
+        table
+    })
+}
+
+/// Декодирование sRGB для непрерывного значения 0..=1
+fn srgb_decode(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Декодирование sRGB в линейную яркость 0..=1
+fn srgb_to_linear(value: u8) -> f32 {
+    srgb_decode_table()[value as usize]
+}
+
+/// Кодирование линейной яркости в sRGB: ближайший уровень ищется двоичным
+/// поиском по таблице декодирования, значения вне 0..=1 зажимаются
+fn linear_to_srgb(linear: f32) -> u8 {
+    let table = srgb_decode_table();
+    if linear.is_nan() || linear <= 0.0 {
+        return 0;
+    }
+    let upper = table.partition_point(|&v| v < linear);
+    if upper == 0 {
+        return 0;
+    }
+    if upper == table.len() {
+        return 255;
+    }
+    // Сравниваем с соседним уровнем снизу в пространстве sRGB
+    let lower = upper - 1;
+    let encode = |l: f32| if l <= 0.0031308 { 12.92 * l } else { 1.055 * l.powf(1.0 / 2.4) - 0.055 };
+    let position = encode(linear) * 255.0;
+    if position - lower as f32 <= upper as f32 - position { lower as u8 } else { upper as u8 }
+}
+
+/// Таблица преобразования значения канала (0..=255) для поточечных операций
+pub type Lut = [u8; 256];
+
+fn brightness_lut(value: i16) -> Lut {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = (i as i16 + value).clamp(0, 255) as u8;
+    }
+    lut
+}
+
+fn invert_lut() -> Lut {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = 255 - i as u8;
+    }
+    lut
+}
+
+fn threshold_lut(threshold: u8) -> Lut {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = if i as u8 > threshold { 255 } else { 0 };
+    }
+    lut
+}
+
+/// Объединяет две таблицы в одну: сначала применяется `first`, затем `second`
+fn compose_luts(first: &Lut, second: &Lut) -> Lut {
+    let mut lut = [0u8; 256];
+    for (entry, &v) in lut.iter_mut().zip(first.iter()) {
+        *entry = second[v as usize];
+    }
+    lut
+}
+
+fn apply_lut_rgb(img: &mut RgbImage, lut: &Lut) {
+    for v in img.iter_mut() {
+        *v = lut[*v as usize];
+    }
+}
+
+fn apply_lut_rgba(img: &mut RgbaImage, lut: &Lut) {
+    // Альфа-канал не трогаем
+    for pixel in img.chunks_exact_mut(4) {
+        for v in &mut pixel[..3] {
+            *v = lut[*v as usize];
+        }
+    }
+}
+
+fn apply_lut_luma(img: &mut GrayImage, lut: &Lut) {
+    for v in img.iter_mut() {
+        *v = lut[*v as usize];
+    }
+}
+
+/// Применяет таблицу к изображению, сохраняя его формат (Luma8, Rgba8 или Rgb8)
+fn apply_lut_channels(image: &DynamicImage, lut: &Lut) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma8(gray) => {
+            let mut img = gray.clone();
+            apply_lut_luma(&mut img, lut);
+            DynamicImage::ImageLuma8(img)
+        }
+        DynamicImage::ImageRgba8(rgba) => {
+            let mut img = rgba.clone();
+            apply_lut_rgba(&mut img, lut);
+            DynamicImage::ImageRgba8(img)
+        }
+        _ => {
+            let mut img = image.to_rgb8();
+            apply_lut_rgb(&mut img, lut);
+            DynamicImage::ImageRgb8(img)
+        }
+    }
+}
+
+/// К чему применяется таблица: к каждому каналу RGB или только к яркости V
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LutTarget {
+    Rgb,
+    Value,
+}
+
+/// Применяет таблицу к каналу V модели HSV, сохраняя тон и насыщенность
+fn apply_lut_value(image: &DynamicImage, lut: &Lut) -> DynamicImage {
+    if let DynamicImage::ImageLuma8(_) = image {
+        // У серого изображения V совпадает с яркостью
+        return apply_lut_channels(image, lut);
+    }
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        let (h, s, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        let v = lut[(v * 255.0).round() as usize] as f32 / 255.0;
+        let (r, g, b) = hsv_to_rgb(h, s, v);
+        pixel[0] = r;
+        pixel[1] = g;
+        pixel[2] = b;
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+pub fn apply_lut(image: &DynamicImage, lut: &Lut, target: LutTarget) -> DynamicImage {
+    match target {
+        LutTarget::Rgb => apply_lut_channels(image, lut),
+        LutTarget::Value => apply_lut_value(image, lut),
+    }
+}
+
+/// Строит таблицу по функции; результат округляется и зажимается в 0..=255,
+/// бесконечность дает 255, NaN оставляет значение без изменений
+fn lut_from_fn(f: impl Fn(f32) -> f32) -> Lut {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        let v = f(i as f32);
+        *entry = if v.is_nan() { i as u8 } else { v.round().clamp(0.0, 255.0) as u8 };
+    }
+    lut
+}
+
+/// Коэффициент, при котором логарифмическое преобразование переводит 0..=255 в 0..=255
+pub fn default_log_coefficient() -> f32 {
+    255.0 / 256f32.ln()
+}
+
+/// s = c * ln(1 + r); при недопустимом коэффициенте - тождественная таблица
+fn log_lut(c: f32) -> Lut {
+    if !c.is_finite() || c <= 0.0 {
+        return lut_from_fn(|v| v);
+    }
+    lut_from_fn(|v| c * v.ln_1p())
+}
+
+/// s = base^(r / c) - 1 - обратное к логарифмическому преобразование
+fn exp_lut(c: f32, base: f32) -> Lut {
+    if !c.is_finite() || c <= 0.0 || !base.is_finite() || base <= 1.0 {
+        return lut_from_fn(|v| v);
+    }
+    lut_from_fn(|v| base.powf(v / c) - 1.0)
+}
+
+pub fn apply_log_transform(image: &DynamicImage, c: f32, target: LutTarget) -> DynamicImage {
+    apply_lut(image, &log_lut(c), target)
+}
+
+pub fn apply_exp_transform(image: &DynamicImage, c: f32, base: f32, target: LutTarget) -> DynamicImage {
+    apply_lut(image, &exp_lut(c, base), target)
+}
+
+/// Уровни: [black, white] растягивается на 0..=255 с гамма-кривой
+/// на полутонах, значения вне диапазона зажимаются. При black >= white
+/// таблица вырождается в порог по black.
+pub fn levels_lut(black: u8, white: u8, gamma: f32) -> Lut {
+    if white <= black {
+        return threshold_lut(black.saturating_sub(1));
+    }
+    let gamma = if gamma.is_finite() && gamma > 0.0 { gamma } else { 1.0 };
+    let (black, white) = (black as f32, white as f32);
+    lut_from_fn(|v| {
+        let t = ((v - black) / (white - black)).clamp(0.0, 1.0);
+        255.0 * t.powf(1.0 / gamma)
+    })
+}
+
+pub fn apply_levels(image: &DynamicImage, black: u8, white: u8, gamma: f32, target: LutTarget) -> DynamicImage {
+    apply_lut(image, &levels_lut(black, white, gamma), target)
+}
+
+/// Тождественная кривая - диагональ
+pub const IDENTITY_CURVE: [(f32, f32); 2] = [(0.0, 0.0), (255.0, 255.0)];
+
+/// Кривая по опорным точкам монотонным кубическим сплайном Фрича – Карлсона:
+/// между соседними точками сплайн не выходит за их значения, поэтому кривая
+/// не дает выбросов. Левее первой и правее последней точки кривая постоянна.
+pub fn curve_lut(points: &[(f32, f32)]) -> Lut {
+    let mut points: Vec<(f32, f32)> = points.to_vec();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    points.dedup_by(|b, a| a.0 == b.0);
+    match points.len() {
+        0 => return lut_from_fn(|v| v),
+        1 => return lut_from_fn(|_| points[0].1),
+        _ => {}
+    }
+
+    let n = points.len();
+    let slopes: Vec<f32> = points
+        .windows(2)
+        .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+        .collect();
+    let mut tangents = vec![0.0f32; n];
+    tangents[0] = slopes[0];
+    tangents[n - 1] = slopes[n - 2];
+    for k in 1..n - 1 {
+        tangents[k] = if slopes[k - 1] * slopes[k] <= 0.0 { 0.0 } else { (slopes[k - 1] + slopes[k]) / 2.0 };
+    }
+    // Ограничение касательных сохраняет монотонность на каждом отрезке
+    for k in 0..n - 1 {
+        if slopes[k] == 0.0 {
+            tangents[k] = 0.0;
+            tangents[k + 1] = 0.0;
+            continue;
+        }
+        let a = tangents[k] / slopes[k];
+        let b = tangents[k + 1] / slopes[k];
+        let s = a * a + b * b;
+        if s > 9.0 {
+            let t = 3.0 / s.sqrt();
+            tangents[k] = t * a * slopes[k];
+            tangents[k + 1] = t * b * slopes[k];
+        }
+    }
+
+    lut_from_fn(|x| {
+        if x <= points[0].0 {
+            return points[0].1;
+        }
+        if x >= points[n - 1].0 {
+            return points[n - 1].1;
+        }
+        let k = points.partition_point(|p| p.0 <= x) - 1;
+        let (x0, y0) = points[k];
+        let (x1, y1) = points[k + 1];
+        let h = x1 - x0;
+        let t = (x - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * tangents[k]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * tangents[k + 1]
+    })
+}
+
+pub fn apply_curve(image: &DynamicImage, lut: &Lut, target: LutTarget) -> DynamicImage {
+    apply_lut(image, lut, target)
+}
+
+/// Растягивает канал V с [min_v, max_v] на [0, 1]; значения вне диапазона зажимаются
+fn stretch_value(img: &mut RgbImage, min_v: f32, max_v: f32) {
+    for pixel in img.pixels_mut() {
+        let (h, s, mut v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        
+        if max_v > min_v {
+            v = ((v - min_v) / (max_v - min_v)).clamp(0.0, 1.0);
+        }
+
+        let (r, g, b) = hsv_to_rgb(h, s, v);
+        pixel[0] = r;
+        pixel[1] = g;
+        pixel[2] = b;
+    }
+}
+
+/// Линейное контрастирование, устойчивое к выбросам: растягивается диапазон
+/// между процентилями `low_pct` и `high_pct` гистограммы V. При 0 и 100
+/// растягивается весь диапазон от минимального до максимального V.
+pub fn apply_linear_contrast_clipped(image: &DynamicImage, low_pct: f32, high_pct: f32) -> DynamicImage {
+    let mut img = image.to_rgb8();
+
+    let mut histogram = [0u64; 256];
+    for pixel in img.pixels() {
+        histogram[pixel[0].max(pixel[1]).max(pixel[2]) as usize] += 1;
+    }
+
+    let total = img.width() as f64 * img.height() as f64;
+    let low_pct = low_pct.clamp(0.0, 100.0) as f64;
+    let high_pct = high_pct.clamp(low_pct as f32, 100.0) as f64;
+    let (low, high) = clipped_range(&histogram, total * low_pct / 100.0, total * (100.0 - high_pct) / 100.0);
+
+    stretch_value(&mut img, low as f32 / 255.0, high as f32 / 255.0);
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Линейное контрастирование по каналам: R, G и B растягиваются каждый
+/// на своем диапазоне между процентилями, что убирает цветовой оттенок.
+/// Постоянный канал не меняется; серое изображение обрабатывается так же,
+/// как в режиме по V, чтобы результаты режимов для него совпадали.
+pub fn apply_linear_contrast_rgb(image: &DynamicImage, low_pct: f32, high_pct: f32) -> DynamicImage {
+    let mut img = image.to_rgb8();
+    if img.pixels().all(|p| p[0] == p[1] && p[1] == p[2]) {
+        return apply_linear_contrast_clipped(image, low_pct, high_pct);
+    }
+
+    let mut histograms = [[0u64; 256]; 3];
+    for pixel in img.pixels() {
+        for c in 0..3 {
+            histograms[c][pixel[c] as usize] += 1;
+        }
+    }
+
+    let total = img.width() as f64 * img.height() as f64;
+    let low_pct = low_pct.clamp(0.0, 100.0) as f64;
+    let high_pct = high_pct.clamp(low_pct as f32, 100.0) as f64;
+    let luts: Vec<Lut> = histograms
+        .iter()
+        .map(|histogram| {
+            let (low, high) =
+                clipped_range(histogram, total * low_pct / 100.0, total * (100.0 - high_pct) / 100.0);
+            stretch_lut(low, high)
+        })
+        .collect();
+
+    for pixel in img.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = luts[c][pixel[c] as usize];
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Границы значимого диапазона гистограммы: снизу отбрасывается не более
+/// `low_count` пикселей, сверху - не более `high_count`
+fn clipped_range(histogram: &[u64; 256], low_count: f64, high_count: f64) -> (u8, u8) {
+    let mut low = 0;
+    let mut cumulative = 0u64;
+    for (v, &count) in histogram.iter().enumerate() {
+        cumulative += count;
+        if cumulative as f64 > low_count {
+            low = v;
+            break;
+        }
+    }
+
+    let mut high = 255;
+    cumulative = 0;
+    for (v, &count) in histogram.iter().enumerate().rev() {
+        cumulative += count;
+        if cumulative as f64 > high_count {
+            high = v;
+            break;
+        }
+    }
+
+    (low as u8, high as u8)
+}
+
+/// Линейно растягивает [low, high] на весь диапазон; вырожденный диапазон
+/// дает тождественную таблицу
+fn stretch_lut(low: u8, high: u8) -> Lut {
+    if high <= low {
+        return lut_from_fn(|v| v);
+    }
+    let (low, high) = (low as f32, high as f32);
+    lut_from_fn(|v| (v - low) * 255.0 / (high - low))
+}
+
+/// Автокоррекция: каждый канал RGB растягивается на полный диапазон после
+/// отсечения `clip_percent` процентов самых темных и самых светлых пикселей
+pub fn apply_auto_contrast(image: &DynamicImage, clip_percent: f32) -> DynamicImage {
+    let mut img = image.to_rgb8();
+
+    let mut histograms = [[0u64; 256]; 3];
+    for pixel in img.pixels() {
+        for c in 0..3 {
+            histograms[c][pixel[c] as usize] += 1;
+        }
+    }
+
+    let total = img.width() as f64 * img.height() as f64;
+    let clip_count = total * clip_percent.clamp(0.0, 50.0) as f64 / 100.0;
+    let luts: Vec<Lut> = histograms
+        .iter()
+        .map(|histogram| {
+            let (low, high) = clipped_range(histogram, clip_count, clip_count);
+            stretch_lut(low, high)
+        })
+        .collect();
+
+    for pixel in img.pixels_mut() {
+        for c in 0..3 {
+            pixel[c] = luts[c][pixel[c] as usize];
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Пространство, в котором выравнивается гистограмма
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EqualizationSpace {
+    /// Только яркость V модели HSV, тон и насыщенность сохраняются
+    HsvValue,
+    /// Каждый канал RGB независимо (цвета смещаются)
+    RgbPerChannel,
+    /// Только яркостная составляющая Y модели YCbCr
+    YCbCrLuma,
+}
+
+impl EqualizationSpace {
+    pub const ALL: [EqualizationSpace; 3] =
+        [EqualizationSpace::HsvValue, EqualizationSpace::RgbPerChannel, EqualizationSpace::YCbCrLuma];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EqualizationSpace::HsvValue => "HSV (V)",
+            EqualizationSpace::RgbPerChannel => "RGB по каналам",
+            EqualizationSpace::YCbCrLuma => "YCbCr (Y)",
+        }
+    }
+}
+
+/// Таблица выравнивания по накопленной гистограмме; для однотонного
+/// изображения - тождественная
+fn equalization_lut(histogram: &[u64; 256]) -> Lut {
+    let total: u64 = histogram.iter().sum();
+    let cdf_min = histogram.iter().copied().find(|&c| c > 0).unwrap_or(0);
+    if total == cdf_min {
+        return lut_from_fn(|v| v);
+    }
+
+    let mut lut = [0u8; 256];
+    let mut cumulative = 0u64;
+    for (entry, &count) in lut.iter_mut().zip(histogram.iter()) {
+        cumulative += count;
+        let scaled = cumulative.saturating_sub(cdf_min) as f64 * 255.0 / (total - cdf_min) as f64;
+        *entry = scaled.round() as u8;
+    }
+    lut
+}
+
+/// Эквализация гистограммы в выбранном пространстве
+pub fn apply_histogram_equalization(image: &DynamicImage, space: EqualizationSpace) -> DynamicImage {
+    if let DynamicImage::ImageLuma8(gray_image) = image {
+        // У серого изображения все три варианта совпадают
+        return apply_lut_channels(image, &equalization_lut(&gray_histogram(gray_image)));
+    }
+
+    let mut img = image.to_rgb8();
+    match space {
+        EqualizationSpace::HsvValue => {
+            let mut histogram = [0u64; 256];
+            for pixel in img.pixels() {
+                histogram[pixel[0].max(pixel[1]).max(pixel[2]) as usize] += 1;
+            }
+            return apply_lut_value(&DynamicImage::ImageRgb8(img), &equalization_lut(&histogram));
+        }
+        EqualizationSpace::RgbPerChannel => {
+            let mut histograms = [[0u64; 256]; 3];
+            for pixel in img.pixels() {
+                for c in 0..3 {
+                    histograms[c][pixel[c] as usize] += 1;
+                }
+            }
+            let luts: Vec<Lut> = histograms.iter().map(equalization_lut).collect();
+            for pixel in img.pixels_mut() {
+                for c in 0..3 {
+                    pixel[c] = luts[c][pixel[c] as usize];
+                }
+            }
+        }
+        EqualizationSpace::YCbCrLuma => {
+            let mut histogram = [0u64; 256];
+            for pixel in img.pixels() {
+                let (y, _, _) = rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]);
+                histogram[y.round() as usize] += 1;
+            }
+            let lut = equalization_lut(&histogram);
+            for pixel in img.pixels_mut() {
+                let (y, cb, cr) = rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]);
+                let (r, g, b) = ycbcr_to_rgb(lut[y.round() as usize] as f32, cb, cr);
+                pixel.0 = [r, g, b];
+            }
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+pub fn apply_manual_threshold(image: &DynamicImage, threshold: u8) -> DynamicImage {
+    let mut gray_image = image.to_luma8();
+    // Выше порога - белый, иначе черный
+    apply_lut_luma(&mut gray_image, &threshold_lut(threshold));
+    DynamicImage::ImageLuma8(gray_image)
+}
+
+/// Диапазон цветов в HSV. Тон в градусах; если `hue.0 > hue.1`, диапазон
+/// проходит через 0° (например, 330°..20° - красные). S и V в [0, 1].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HsvRange {
+    pub hue: (f32, f32),
+    pub saturation: (f32, f32),
+    pub value: (f32, f32),
+}
+
+impl HsvRange {
+    fn contains(&self, h: f32, s: f32, v: f32) -> bool {
+        let (h_min, h_max) = self.hue;
+        let hue_ok = if h_min <= h_max { (h_min..=h_max).contains(&h) } else { h >= h_min || h <= h_max };
+        hue_ok
+            && (self.saturation.0..=self.saturation.1).contains(&s)
+            && (self.value.0..=self.value.1).contains(&v)
+    }
+}
+
+/// Маска пикселей, цвет которых попадает в диапазон HSV: Luma8 со значениями 0/255
+pub fn apply_hsv_range_mask(image: &DynamicImage, range: &HsvRange) -> DynamicImage {
+    let rgb_image = image.to_rgb8();
+    let mask = GrayImage::from_fn(rgb_image.width(), rgb_image.height(), |x, y| {
+        let [r, g, b] = rgb_image.get_pixel(x, y).0;
+        let (h, s, v) = rgb_to_hsv(r, g, b);
+        Luma([if range.contains(h, s, v) { 255 } else { 0 }])
+    });
+    DynamicImage::ImageLuma8(mask)
+}
+
+/// Накладывает бинарную маску на исходное изображение: пиксели, прошедшие
+/// порог, сохраняют цвет, остальные заливаются `fill`. Результат - Rgb8.
+pub fn apply_color_mask(image: &DynamicImage, mask: &DynamicImage, fill: [u8; 3]) -> DynamicImage {
+    let mut rgb_image = image.to_rgb8();
+    let mask = mask.to_luma8();
+    for (pixel, m) in rgb_image.pixels_mut().zip(mask.pixels()) {
+        if m[0] == 0 {
+            pixel.0 = fill;
+        }
+    }
+    DynamicImage::ImageRgb8(rgb_image)
+}
+
+fn gray_histogram(gray: &GrayImage) -> [u64; 256] {
+    let mut histogram = [0u64; 256];
+    for &p in gray.as_raw() {
+        histogram[p as usize] += 1;
+    }
+    histogram
+}
+
+/// Порог Оцу по гистограмме яркости; `None` для пустой гистограммы
+fn otsu_threshold(histogram: &[u64; 256]) -> Option<u8> {
+    let total_pixels: u64 = histogram.iter().sum();
+    if total_pixels == 0 {
+        return None;
+    }
+
+    let mut sum = 0.0;
+    for (i, &h) in histogram.iter().enumerate() {
+        sum += (i as f64) * (h as f64);
+    }
+
+    let mut sum_b = 0.0;
+    let mut w_b = 0.0;
+    let mut w_f;
+
+    let mut max_variance = 0.0;
+    let mut optimal_threshold = 0;
+
+    for (t, &count) in histogram.iter().enumerate() {
+        w_b += count as f64;
+        if w_b == 0.0 { continue; }
+
+        w_f = (total_pixels as f64) - w_b;
+        if w_f == 0.0 { break; }
+
+        sum_b += (t as f64) * (count as f64);
+
+        let mean_b = sum_b / w_b;
+        let mean_f = (sum - sum_b) / w_f;
+        
+        let variance = w_b * w_f * (mean_b - mean_f).powi(2);
+        
+        if variance > max_variance {
+            max_variance = variance;
+            optimal_threshold = t as u8;
+        }
+    }
+
+    Some(optimal_threshold)
+}
+
+pub fn apply_otsu_threshold(image: &DynamicImage) -> (DynamicImage, Option<u8>) {
+    let histogram = gray_histogram(&image.to_luma8());
+    let threshold = otsu_threshold(&histogram);
+    match threshold {
+        Some(threshold) => (apply_manual_threshold(image, threshold), Some(threshold)),
+        None => (image.clone(), None),
+    }
+}
+
+/// Порог треугольным методом: прямая проводится от пика гистограммы до
+/// самого дальнего непустого бина, порог - бин с наибольшим расстоянием
+/// до этой прямой. Если длинный хвост слева от пика, гистограмма
+/// отражается. `None` для пустой гистограммы.
+fn triangle_threshold(histogram: &[u64; 256]) -> Option<u8> {
+    let first = histogram.iter().position(|&h| h > 0)?;
+    let last = histogram.iter().rposition(|&h| h > 0)?;
+    let peak = (first..=last).max_by_key(|&i| (histogram[i], std::cmp::Reverse(i)))?;
+
+    let mirrored = peak - first > last - peak;
+    let mut bins = *histogram;
+    if mirrored {
+        bins.reverse();
+    }
+    let (peak, end) = if mirrored { (255 - peak, 255 - first) } else { (peak, last) };
+
+    let dx = (end - peak) as f64;
+    let dy = bins[end] as f64 - bins[peak] as f64;
+    let mut best = (0.0, peak);
+    for t in peak..=end {
+        // Расстояние до прямой с точностью до постоянного множителя
+        let distance = (dy * (t - peak) as f64 - dx * (bins[t] as f64 - bins[peak] as f64)).abs();
+        if distance > best.0 {
+            best = (distance, t);
+        }
+    }
+
+    let threshold = best.1 as u8;
+    // После отражения "выше порога" соответствует фону, а не хвосту
+    Some(if mirrored { (254 - threshold as i16).max(0) as u8 } else { threshold })
+}
+
+pub fn apply_triangle_threshold(image: &DynamicImage) -> (DynamicImage, Option<u8>) {
+    let histogram = gray_histogram(&image.to_luma8());
+    match triangle_threshold(&histogram) {
+        Some(threshold) => (apply_manual_threshold(image, threshold), Some(threshold)),
+        None => (image.clone(), None),
+    }
+}
+
+/// Порог по максимуму суммы энтропий фона и объекта (метод Капура).
+/// Нулевые бины пропускаются, чтобы не считать ln(0); для однотонного
+/// изображения порогом становится его единственный уровень.
+fn kapur_threshold(histogram: &[u64; 256]) -> Option<u8> {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    // Накопленные вероятности и суммы p·ln(p)
+    let mut cum_p = [0f64; 256];
+    let mut cum_plogp = [0f64; 256];
+    let mut p_sum = 0.0;
+    let mut plogp_sum = 0.0;
+    for (i, &count) in histogram.iter().enumerate() {
+        if count > 0 {
+            let p = count as f64 / total as f64;
+            p_sum += p;
+            plogp_sum += p * p.ln();
+        }
+        cum_p[i] = p_sum;
+        cum_plogp[i] = plogp_sum;
+    }
+
+    let mut best: Option<(f64, u8)> = None;
+    for t in 0..255 {
+        let p_b = cum_p[t];
+        let p_f = p_sum - p_b;
+        if p_b <= 0.0 || p_f <= 1e-12 {
+            continue;
+        }
+        // H = ln(P) - Σ p·ln(p) / P для каждого из двух классов
+        let h_b = p_b.ln() - cum_plogp[t] / p_b;
+        let h_f = p_f.ln() - (plogp_sum - cum_plogp[t]) / p_f;
+        let entropy = h_b + h_f;
+        if best.is_none_or(|(max, _)| entropy > max) {
+            best = Some((entropy, t as u8));
+        }
+    }
+
+    best.map(|(_, t)| t)
+        .or_else(|| histogram.iter().position(|&h| h > 0).map(|i| i as u8))
+}
+
+pub fn apply_kapur_threshold(image: &DynamicImage) -> (DynamicImage, Option<u8>) {
+    let histogram = gray_histogram(&image.to_luma8());
+    match kapur_threshold(&histogram) {
+        Some(threshold) => (apply_manual_threshold(image, threshold), Some(threshold)),
+        None => (image.clone(), None),
+    }
+}
+
+/// Гистограмма яркости прямоугольника [x0, x1) × [y0, y1)
+fn region_histogram(gray: &GrayImage, x0: u32, y0: u32, x1: u32, y1: u32) -> [u64; 256] {
+    let mut histogram = [0u64; 256];
+    let width = gray.width() as usize;
+    for y in y0 as usize..y1 as usize {
+        let row = &gray.as_raw()[y * width..(y + 1) * width];
+        for &p in &row[x0 as usize..x1 as usize] {
+            histogram[p as usize] += 1;
+        }
+    }
+    histogram
+}
+
+/// Блочный метод Оцу: изображение делится на сетку `grid`×`grid`, в каждом
+/// блоке свой порог Оцу. Однотонные блоки берут глобальный порог. При
+/// `interpolate` порог билинейно интерполируется между центрами блоков,
+/// иначе каждый блок бинаризуется своим порогом (видны швы).
+pub fn apply_tiled_otsu(image: &DynamicImage, grid: u32, interpolate: bool) -> DynamicImage {
+    let mut gray_image = image.to_luma8();
+    let (width, height) = gray_image.dimensions();
+    let Some(global) = otsu_threshold(&gray_histogram(&gray_image)) else {
+        return image.clone();
+    };
+    let grid = grid.clamp(1, width.min(height)) as usize;
+    let bounds = |i: usize, size: u32| (i as u32 * size / grid as u32, (i as u32 + 1) * size / grid as u32);
+
+    let mut thresholds = vec![global as f32; grid * grid];
+    for ty in 0..grid {
+        let (y0, y1) = bounds(ty, height);
+        for tx in 0..grid {
+            let (x0, x1) = bounds(tx, width);
+            let histogram = region_histogram(&gray_image, x0, y0, x1, y1);
+            if histogram.iter().filter(|&&h| h > 0).count() > 1 {
+                thresholds[ty * grid + tx] = otsu_threshold(&histogram).unwrap_or(global) as f32;
+            }
+        }
+    }
+
+    // Координата пикселя в системе центров блоков: индекс блока и доля до следующего
+    let tile_position = |v: u32, size: u32| {
+        let f = ((v as f32 + 0.5) * grid as f32 / size as f32 - 0.5).clamp(0.0, (grid - 1) as f32);
+        let i = (f as usize).min(grid - 1);
+        (i, (i + 1).min(grid - 1), f - i as f32)
+    };
+
+    for (x, y, pixel) in gray_image.enumerate_pixels_mut() {
+        let threshold = if interpolate {
+            let (x0, x1, tx) = tile_position(x, width);
+            let (y0, y1, ty) = tile_position(y, height);
+            let top = thresholds[y0 * grid + x0] * (1.0 - tx) + thresholds[y0 * grid + x1] * tx;
+            let bottom = thresholds[y1 * grid + x0] * (1.0 - tx) + thresholds[y1 * grid + x1] * tx;
+            top * (1.0 - ty) + bottom * ty
+        } else {
+            let tx = (x as usize * grid / width as usize).min(grid - 1);
+            let ty = (y as usize * grid / height as usize).min(grid - 1);
+            thresholds[ty * grid + tx]
+        };
+        pixel[0] = if pixel[0] as f32 > threshold { 255 } else { 0 };
+    }
+    DynamicImage::ImageLuma8(gray_image)
+}
+
+/// Итеративный порог Ридлера–Калварда (isodata): начиная со среднего,
+/// порог заменяется полусуммой средних двух групп, пока не перестанет
+/// меняться (не более 100 итераций). `None` для пустой гистограммы.
+fn isodata_threshold(histogram: &[u64; 256]) -> Option<u8> {
+    let group_mean = |range: std::ops::RangeInclusive<usize>| {
+        let (count, sum) = range.fold((0u64, 0u64), |(count, sum), i| {
+            (count + histogram[i], sum + i as u64 * histogram[i])
+        });
+        (count > 0).then(|| sum as f64 / count as f64)
+    };
+
+    let mut threshold = group_mean(0..=255)?.round() as usize;
+    for _ in 0..100 {
+        let below = group_mean(0..=threshold);
+        let above = if threshold < 255 { group_mean(threshold + 1..=255) } else { None };
+        // Пустая группа (например, у однотонного изображения) заменяется другой
+        let next = match (below, above) {
+            (Some(b), Some(a)) => (b + a) / 2.0,
+            (Some(m), None) | (None, Some(m)) => m,
+            (None, None) => break,
+        };
+        let next = next.round() as usize;
+        if next == threshold {
+            break;
+        }
+        threshold = next;
+    }
+    Some(threshold as u8)
+}
+
+pub fn apply_isodata_threshold(image: &DynamicImage) -> (DynamicImage, Option<u8>) {
+    let histogram = gray_histogram(&image.to_luma8());
+    match isodata_threshold(&histogram) {
+        Some(threshold) => (apply_manual_threshold(image, threshold), Some(threshold)),
+        None => (image.clone(), None),
+    }
+}
+
+/// Пороги многоуровневого метода Оцу для `classes` классов (2..=4).
+/// Класс `k` - значения из (t[k-1], t[k]]; перебор всех наборов порогов
+/// с накопленными суммами, максимизируется межклассовая дисперсия.
+fn multi_otsu_thresholds(histogram: &[u64; 256], classes: u8) -> Vec<u8> {
+    // Классов не может быть больше, чем различных уровней серого:
+    // иначе часть порогов попадает в пустые участки гистограммы
+    let distinct = histogram.iter().filter(|&&h| h > 0).count();
+    if distinct < 2 {
+        return otsu_threshold(histogram).into_iter().collect();
+    }
+    let classes = (classes.clamp(2, 4) as usize).min(distinct);
+    if classes == 2 {
+        // Для двух классов в точности повторяем обычный метод Оцу
+        return otsu_threshold(histogram).into_iter().collect();
+    }
+
+    let mut cum_w = [0f64; 257];
+    let mut cum_s = [0f64; 257];
+    for (i, &count) in histogram.iter().enumerate() {
+        cum_w[i + 1] = cum_w[i] + count as f64;
+        cum_s[i + 1] = cum_s[i] + (i as f64) * (count as f64);
+    }
+    if cum_w[256] == 0.0 {
+        return Vec::new();
+    }
+
+    // Вклад класса [a, b] в межклассовую дисперсию (с точностью до константы)
+    let class_score = |a: usize, b: usize| {
+        let w = cum_w[b + 1] - cum_w[a];
+        if w == 0.0 {
+            0.0
+        } else {
+            let s = cum_s[b + 1] - cum_s[a];
+            s * s / w
+        }
+    };
+
+    pub fn search(
+        first: usize,
+        left: usize,
+        score: f64,
+        current: &mut Vec<u8>,
+        best: &mut (f64, Vec<u8>),
+        class_score: &dyn Fn(usize, usize) -> f64,
+    ) {
+        if left == 0 {
+            let total = score + class_score(first, 255);
+            if total > best.0 {
+                *best = (total, current.clone());
+            }
+            return;
+        }
+        for t in first..(256 - left) {
+            current.push(t as u8);
+            search(t + 1, left - 1, score + class_score(first, t), current, best, class_score);
+            current.pop();
+        }
+    }
+
+    let mut best = (-1.0, Vec::new());
+    search(0, classes - 1, 0.0, &mut Vec::with_capacity(classes - 1), &mut best, &class_score);
+    best.1
+}
+
+/// Разбивает изображение на `classes` классов многоуровневым методом Оцу;
+/// классы отображаются в равномерно распределенные уровни серого.
+pub fn apply_multi_otsu(image: &DynamicImage, classes: u8) -> (DynamicImage, Vec<u8>) {
+    let mut gray_image = image.to_luma8();
+    let thresholds = multi_otsu_thresholds(&gray_histogram(&gray_image), classes);
+    if thresholds.is_empty() {
+        return (image.clone(), thresholds);
+    }
+
+    let levels = thresholds.len();
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        let class = thresholds.iter().filter(|&&t| i as u8 > t).count();
+        // Округление дает середину 128 для трех классов
+        *entry = ((class * 255 + levels / 2) / levels) as u8;
+    }
+    apply_lut_luma(&mut gray_image, &lut);
+
+    (DynamicImage::ImageLuma8(gray_image), thresholds)
+}
+
+const HYSTERESIS_NONE: u8 = 0;
+const HYSTERESIS_WEAK: u8 = 1;
+const HYSTERESIS_STRONG: u8 = 2;
+
+/// Гистерезисная связность: каждый слабый пиксель, 8-связно соединенный
+/// с сильным, становится сильным. Обход идет явным стеком, а не рекурсией,
+/// чтобы большие однородные области не переполняли стек вызовов.
+fn hysteresis_link(width: usize, height: usize, levels: &mut [u8]) {
+    let mut stack: Vec<usize> = levels
+        .iter()
+        .enumerate()
+        .filter(|&(_, &l)| l == HYSTERESIS_STRONG)
+        .map(|(i, _)| i)
+        .collect();
+
+    while let Some(index) = stack.pop() {
+        let x = index % width;
+        let y = index / width;
+        for dy in -1i64..=1 {
+            for dx in -1i64..=1 {
+                let nx = x as i64 + dx;
+                let ny = y as i64 + dy;
+                if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                    continue;
+                }
+                let neighbor = ny as usize * width + nx as usize;
+                if levels[neighbor] == HYSTERESIS_WEAK {
+                    levels[neighbor] = HYSTERESIS_STRONG;
+                    stack.push(neighbor);
+                }
+            }
+        }
+    }
+}
+
+/// Двойной порог: выше `high` - объект, выше `low` - объект только при связи
+/// с пикселем выше `high`, остальное - фон
+pub fn apply_hysteresis_threshold(image: &DynamicImage, low: u8, high: u8) -> DynamicImage {
+    let mut gray_image = image.to_luma8();
+    let (width, height) = gray_image.dimensions();
+    let low = low.min(high);
+
+    let mut levels: Vec<u8> = gray_image
+        .iter()
+        .map(|&p| {
+            if p > high {
+                HYSTERESIS_STRONG
+            } else if p > low {
+                HYSTERESIS_WEAK
+            } else {
+                HYSTERESIS_NONE
+            }
+        })
+        .collect();
+    hysteresis_link(width as usize, height as usize, &mut levels);
+
+    for (p, &l) in gray_image.iter_mut().zip(levels.iter()) {
+        *p = if l == HYSTERESIS_STRONG { 255 } else { 0 };
+    }
+    DynamicImage::ImageLuma8(gray_image)
+}
+
+/// Связность пикселей при обходе областей
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    pub fn offsets(self) -> &'static [(i64, i64)] {
+        match self {
+            Connectivity::Four => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+            Connectivity::Eight => &[(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)],
+        }
+    }
+}
+
+/// Выделение "волшебной палочкой": белым отмечаются все пиксели, связанные
+/// с затравкой и отличающиеся от нее не более чем на `tolerance` - по яркости
+/// или, при `by_color`, по евклидову расстоянию в RGB. Заливка идет очередью,
+/// поэтому выделение всего большого снимка не переполняет стек.
+/// Для затравки за пределами изображения возвращает `None`.
+pub fn apply_flood_select(
+    image: &DynamicImage,
+    seed: (u32, u32),
+    tolerance: u8,
+    connectivity: Connectivity,
+    by_color: bool,
+) -> Option<DynamicImage> {
+    let (width, height) = image.dimensions();
+    if seed.0 >= width || seed.1 >= height {
+        return None;
+    }
+
+    let similar: Box<dyn Fn(u32, u32) -> bool> = if by_color {
+        let rgb_image = image.to_rgb8();
+        let seed_color = rgb_image.get_pixel(seed.0, seed.1).0;
+        let limit = (tolerance as u32).pow(2);
+        Box::new(move |x, y| {
+            let color = rgb_image.get_pixel(x, y).0;
+            let distance: u32 = (0..3).map(|c| (color[c] as i32 - seed_color[c] as i32).pow(2) as u32).sum();
+            distance <= limit
+        })
+    } else {
+        let gray_image = image.to_luma8();
+        let seed_value = gray_image.get_pixel(seed.0, seed.1)[0];
+        Box::new(move |x, y| gray_image.get_pixel(x, y)[0].abs_diff(seed_value) <= tolerance)
+    };
+
+    let mut mask = GrayImage::new(width, height);
+    let mut queue = VecDeque::from([seed]);
+    mask.put_pixel(seed.0, seed.1, Luma([255]));
+
+    while let Some((x, y)) = queue.pop_front() {
+        for &(dx, dy) in connectivity.offsets() {
+            let nx = x as i64 + dx;
+            let ny = y as i64 + dy;
+            if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                continue;
+            }
+            let (nx, ny) = (nx as u32, ny as u32);
+            if mask.get_pixel(nx, ny)[0] == 0 && similar(nx, ny) {
+                mask.put_pixel(nx, ny, Luma([255]));
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    Some(DynamicImage::ImageLuma8(mask))
+}
+
+/// Связная компонента бинарного изображения
+#[derive(Clone, Debug)]
+pub struct ComponentInfo {
+    pub area: u32,
+    /// Ограничивающий прямоугольник: (min_x, min_y, max_x, max_y) включительно
+    pub bounding_box: (u32, u32, u32, u32),
+    pub centroid: (f32, f32),
+}
+
+/// Метки пикселей: 0 - фон, i + 1 - компонента с индексом i
+pub struct LabelMap {
+    pub width: u32,
+    pub height: u32,
+    pub labels: Vec<u32>,
+}
+
+/// Система непересекающихся множеств для слияния эквивалентных меток
+fn find_root(parents: &mut [u32], mut label: u32) -> u32 {
+    while parents[label as usize] != label {
+        parents[label as usize] = parents[parents[label as usize] as usize];
+        label = parents[label as usize];
+    }
+    label
+}
+
+/// Бинарная маска изображения; если в нем были не только 0 и 255,
+/// оно бинаризуется по порогу 128 и второй элемент равен `false`
+pub fn binary_mask(image: &DynamicImage) -> (GrayImage, bool) {
+    let mut gray_image = image.to_luma8();
+    let was_binary = gray_image.iter().all(|&p| p == 0 || p == 255);
+    if !was_binary {
+        for p in gray_image.iter_mut() {
+            *p = if *p >= 128 { 255 } else { 0 };
+        }
+    }
+    (gray_image, was_binary)
+}
+
+/// Разметка связных компонент белых пикселей в два прохода: первый
+/// назначает предварительные метки и объединяет эквивалентные, второй
+/// заменяет их итоговыми номерами и собирает статистику компонент
+pub fn label_components(image: &GrayImage, connectivity: Connectivity) -> (Vec<ComponentInfo>, LabelMap) {
+    let (width, height) = image.dimensions();
+    let mut labels = vec![0u32; (width * height) as usize];
+    let mut parents: Vec<u32> = vec![0];
+
+    // Уже просмотренные соседи: слева и сверху (и по диагоналям сверху)
+    let previous: &[(i64, i64)] = match connectivity {
+        Connectivity::Four => &[(-1, 0), (0, -1)],
+        Connectivity::Eight => &[(-1, 0), (-1, -1), (0, -1), (1, -1)],
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            if image.get_pixel(x, y)[0] == 0 {
+                continue;
+            }
+            let mut current = 0u32;
+            for &(dx, dy) in previous {
+                let nx = x as i64 + dx;
+                let ny = y as i64 + dy;
+                if nx < 0 || ny < 0 || nx >= width as i64 {
+                    continue;
+                }
+                let neighbor = labels[(ny as u32 * width + nx as u32) as usize];
+                if neighbor == 0 {
+                    continue;
+                }
+                if current == 0 {
+                    current = neighbor;
+                } else {
+                    let a = find_root(&mut parents, current);
+                    let b = find_root(&mut parents, neighbor);
+                    parents[a.max(b) as usize] = a.min(b);
+                }
+            }
+            if current == 0 {
+                current = parents.len() as u32;
+                parents.push(current);
+            }
+            labels[(y * width + x) as usize] = current;
+        }
+    }
+
+    let mut final_labels = vec![0u32; parents.len()];
+    let mut components: Vec<ComponentInfo> = Vec::new();
+    let mut sums: Vec<(u64, u64)> = Vec::new();
+    for (index, label) in labels.iter_mut().enumerate() {
+        if *label == 0 {
+            continue;
+        }
+        let root = find_root(&mut parents, *label) as usize;
+        if final_labels[root] == 0 {
+            components.push(ComponentInfo { area: 0, bounding_box: (u32::MAX, u32::MAX, 0, 0), centroid: (0.0, 0.0) });
+            sums.push((0, 0));
+            final_labels[root] = components.len() as u32;
+        }
+        *label = final_labels[root];
+
+        let x = index as u32 % width;
+        let y = index as u32 / width;
+        let component = &mut components[*label as usize - 1];
+        component.area += 1;
+        let (min_x, min_y, max_x, max_y) = component.bounding_box;
+        component.bounding_box = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+        let sum = &mut sums[*label as usize - 1];
+        sum.0 += x as u64;
+        sum.1 += y as u64;
+    }
+    for (component, (sum_x, sum_y)) in components.iter_mut().zip(sums) {
+        let area = component.area as f32;
+        component.centroid = (sum_x as f32 / area, sum_y as f32 / area);
+    }
+
+    (components, LabelMap { width, height, labels })
+}
+
+/// Стирает компоненты площадью меньше `min_area`; возвращает результат
+/// и число оставшихся компонент
+pub fn remove_small_components(image: &GrayImage, connectivity: Connectivity, min_area: u32) -> (DynamicImage, usize) {
+    let (components, label_map) = label_components(image, connectivity);
+    let mut result = image.clone();
+    for (p, &label) in result.iter_mut().zip(label_map.labels.iter()) {
+        if label != 0 && components[label as usize - 1].area < min_area {
+            *p = 0;
+        }
+    }
+    let kept = components.iter().filter(|c| c.area >= min_area).count();
+    (DynamicImage::ImageLuma8(result), kept)
+}
+
+/// Раскрашивает каждую компоненту своим цветом (тон сдвигается на золотой угол);
+/// компоненты площадью меньше `min_area` не выводятся
+pub fn colorize_components(label_map: &LabelMap, components: &[ComponentInfo], min_area: u32) -> DynamicImage {
+    let mut rgb_image = RgbImage::new(label_map.width, label_map.height);
+    for (pixel, &label) in rgb_image.pixels_mut().zip(label_map.labels.iter()) {
+        if label != 0 && components[label as usize - 1].area >= min_area {
+            let (r, g, b) = hsv_to_rgb(label as f32 * 137.508, 0.75, 0.95);
+            pixel.0 = [r, g, b];
+        }
+    }
+    DynamicImage::ImageRgb8(rgb_image)
+}
+
+/// Форма структурного элемента морфологии
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ElementShape {
+    Square,
+    Disk,
+}
+
+impl ElementShape {
+    pub const ALL: [ElementShape; 2] = [ElementShape::Square, ElementShape::Disk];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ElementShape::Square => "Квадрат",
+            ElementShape::Disk => "Круг",
+        }
+    }
+}
+
+/// Структурный элемент: квадрат (2r + 1)×(2r + 1) или круг радиуса r
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StructuringElement {
+    pub radius: u32,
+    pub shape: ElementShape,
+}
+
+/// Минимум (или максимум при `dilate`) в окне [i - radius, i + radius],
+/// обрезанном границами строки, за O(n) с монотонной очередью
+fn sliding_extremum(line: &[u8], radius: usize, dilate: bool, out: &mut [u8]) {
+    let dominates = |a: u8, b: u8| if dilate { a >= b } else { a <= b };
+    let mut window: VecDeque<usize> = VecDeque::new();
+    let mut next = 0;
+    for (i, value) in out.iter_mut().enumerate().take(line.len()) {
+        while next < line.len() && next <= i + radius {
+            while let Some(&back) = window.back()
+                && dominates(line[next], line[back])
+            {
+                window.pop_back();
+            }
+            window.push_back(next);
+            next += 1;
+        }
+        while window.front().is_some_and(|&front| front + radius < i) {
+            window.pop_front();
+        }
+        *value = line[window[0]];
+    }
+}
+
+/// Минимум или максимум по строкам окном полуширины `radius`
+fn horizontal_extremum(gray: &GrayImage, radius: usize, dilate: bool) -> GrayImage {
+    let mut result = gray.clone();
+    let width = gray.width() as usize;
+    if width == 0 {
+        return result;
+    }
+    for (row, out) in gray.chunks_exact(width).zip(result.chunks_exact_mut(width)) {
+        sliding_extremum(row, radius, dilate, out);
+    }
+    result
+}
+
+/// Полутоновая морфология: эрозия - минимум, дилатация - максимум яркости
+/// под элементом; пиксели за границей изображения не учитываются.
+/// Квадрат раскладывается на проходы по строкам и столбцам, круг - на
+/// горизонтальные отрезки, по одному проходу на каждую строку элемента.
+fn morphology(gray: &GrayImage, element: StructuringElement, dilate: bool) -> GrayImage {
+    let radius = element.radius as usize;
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    if radius == 0 || width == 0 || height == 0 {
+        return gray.clone();
+    }
+    let pick = |a: u8, b: u8| if dilate { a.max(b) } else { a.min(b) };
+    match element.shape {
+        ElementShape::Square => {
+            let mut result = horizontal_extremum(gray, radius, dilate);
+            let data: &mut [u8] = &mut result;
+            let mut column = vec![0u8; height];
+            let mut filtered = vec![0u8; height];
+            for x in 0..width {
+                for y in 0..height {
+                    column[y] = data[y * width + x];
+                }
+                sliding_extremum(&column, radius, dilate, &mut filtered);
+                for y in 0..height {
+                    data[y * width + x] = filtered[y];
+                }
+            }
+            result
+        }
+        ElementShape::Disk => {
+            let mut result = gray.clone();
+            let data: &mut [u8] = &mut result;
+            for dy in 0..=radius {
+                let half_width = ((radius * radius - dy * dy) as f64).sqrt() as usize;
+                let rows = horizontal_extremum(gray, half_width, dilate);
+                let rows: &[u8] = &rows;
+                for y in 0..height {
+                    let out = &mut data[y * width..(y + 1) * width];
+                    for source in [y.checked_sub(dy), Some(y + dy).filter(|&s| s < height)].into_iter().flatten() {
+                        let row = &rows[source * width..(source + 1) * width];
+                        for (value, &other) in out.iter_mut().zip(row) {
+                            *value = pick(*value, other);
+                        }
+                    }
+                }
+            }
+            result
+        }
+    }
+}
+
+/// Эрозия: минимум яркости под элементом. На бинарной маске белые
+/// области сужаются, объекты меньше элемента исчезают.
+pub fn apply_erode(image: &DynamicImage, element: StructuringElement) -> DynamicImage {
+    DynamicImage::ImageLuma8(morphology(&image.to_luma8(), element, false))
+}
+
+/// Дилатация: максимум яркости под элементом. На бинарной маске белые
+/// области расширяются, одиночный пиксель становится формой элемента.
+pub fn apply_dilate(image: &DynamicImage, element: StructuringElement) -> DynamicImage {
+    DynamicImage::ImageLuma8(morphology(&image.to_luma8(), element, true))
+}
+
+/// Размыкание: эрозия, затем дилатация. Убирает светлые детали меньше
+/// элемента (шум после бинаризации), остальное сохраняет; повторное
+/// размыкание ничего не меняет.
+pub fn apply_open(image: &DynamicImage, element: StructuringElement) -> DynamicImage {
+    apply_dilate(&apply_erode(image, element), element)
+}
+
+/// Замыкание: дилатация, затем эрозия. Заполняет темные разрывы и
+/// отверстия меньше элемента.
+pub fn apply_close(image: &DynamicImage, element: StructuringElement) -> DynamicImage {
+    apply_erode(&apply_dilate(image, element), element)
+}
+
+/// Скелетизация бинарной маски (яркость > 127 - объект) утончением
+/// Чжана - Суэня: каждая итерация из двух подшагов снимает граничные
+/// пиксели, не разрывающие 8-связность и не укорачивающие концы линий,
+/// пока маска не перестанет меняться. Возвращает скелет толщиной в пиксель
+/// и число итераций, изменивших маску.
+pub fn apply_skeletonize(image: &DynamicImage) -> (DynamicImage, usize) {
+    let gray = image.to_luma8();
+    let (width, height) = (gray.width() as i64, gray.height() as i64);
+    let mut foreground: Vec<bool> = gray.iter().map(|&p| p > 127).collect();
+    // Каждая итерация снимает хотя бы слой толщиной в пиксель
+    let max_iterations = (width + height) as usize;
+
+    // Соседи P2..P9 по часовой стрелке, начиная с верхнего
+    const NEIGHBORS: [(i64, i64); 8] = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)];
+    let mut removed = Vec::new();
+    let mut iterations = 0;
+    while iterations < max_iterations {
+        let mut changed = false;
+        for step in 0..2 {
+            removed.clear();
+            for y in 0..height {
+                for x in 0..width {
+                    if !foreground[(y * width + x) as usize] {
+                        continue;
+                    }
+                    let p = NEIGHBORS.map(|(dx, dy)| {
+                        let (nx, ny) = (x + dx, y + dy);
+                        nx >= 0 && ny >= 0 && nx < width && ny < height && foreground[(ny * width + nx) as usize]
+                    });
+                    let count = p.iter().filter(|&&set| set).count();
+                    let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+                    // Произведение трех соседей равно нулю; индексы: 0 - P2 (север),
+                    // 2 - P4 (восток), 4 - P6 (юг), 6 - P8 (запад)
+                    let any_clear = |a: usize, b: usize, c: usize| !(p[a] && p[b] && p[c]);
+                    let outer = if step == 0 {
+                        any_clear(0, 2, 4) && any_clear(2, 4, 6)
+                    } else {
+                        any_clear(0, 2, 6) && any_clear(0, 4, 6)
+                    };
+                    if (2..=6).contains(&count) && transitions == 1 && outer {
+                        removed.push((y * width + x) as usize);
+                    }
+                }
+            }
+            for &index in &removed {
+                foreground[index] = false;
+            }
+            changed |= !removed.is_empty();
+        }
+        if !changed {
+            break;
+        }
+        iterations += 1;
+    }
+
+    let data = foreground.iter().map(|&set| if set { 255 } else { 0 }).collect();
+    let skeleton = GrayImage::from_raw(width as u32, height as u32, data).unwrap();
+    (DynamicImage::ImageLuma8(skeleton), iterations)
+}
+
+/// Заполнение отверстий бинарной маски (яркость > 127 - объект): фон
+/// заливается от границ изображения по 4-связности, все недостигнутые
+/// черные пиксели становятся объектом. Отверстия, выходящие к краю,
+/// отверстиями не считаются. Заливка идет явным стеком.
+pub fn apply_fill_holes(image: &DynamicImage) -> DynamicImage {
+    let gray = image.to_luma8();
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    if width == 0 || height == 0 {
+        return DynamicImage::ImageLuma8(gray);
+    }
+    let foreground: Vec<bool> = gray.iter().map(|&p| p > 127).collect();
+    let mut outside = vec![false; width * height];
+    let mut stack = Vec::new();
+    let rows = (0..width).flat_map(|x| [(x, 0), (x, height - 1)]);
+    let columns = (0..height).flat_map(|y| [(0, y), (width - 1, y)]);
+    for (x, y) in rows.chain(columns) {
+        let index = y * width + x;
+        if !foreground[index] && !outside[index] {
+            outside[index] = true;
+            stack.push((x, y));
+        }
+    }
+    while let Some((x, y)) = stack.pop() {
+        let neighbors = [
+            x.checked_sub(1).map(|nx| (nx, y)),
+            (x + 1 < width).then_some((x + 1, y)),
+            y.checked_sub(1).map(|ny| (x, ny)),
+            (y + 1 < height).then_some((x, y + 1)),
+        ];
+        for (nx, ny) in neighbors.into_iter().flatten() {
+            let index = ny * width + nx;
+            if !foreground[index] && !outside[index] {
+                outside[index] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+    let data = outside.iter().map(|&reached| if reached { 0 } else { 255 }).collect();
+    DynamicImage::ImageLuma8(GrayImage::from_raw(width as u32, height as u32, data).unwrap())
+}
+
+/// Граница бинарной маски (яркость > 127 - объект): пиксели объекта, у
+/// которых хотя бы один 4-сосед - фон или край изображения. Внешние
+/// контуры и контуры отверстий получаются одинаково.
+pub fn contour_mask(gray: &GrayImage) -> GrayImage {
+    let (width, height) = gray.dimensions();
+    let is_object = |x: i64, y: i64| {
+        x >= 0 && y >= 0 && x < width as i64 && y < height as i64 && gray.get_pixel(x as u32, y as u32)[0] > 127
+    };
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let boundary = is_object(x, y)
+            && [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|&(dx, dy)| !is_object(x + dx, y + dy));
+        Luma([if boundary { 255 } else { 0 }])
+    })
+}
+
+/// Контуры маски `mask`, нарисованные цветом `color` толщиной `thickness`
+/// поверх копии `original`. Возвращает изображение, число контуров
+/// (8-связных компонент границы) и их общую длину в пикселях; `None`,
+/// если размеры маски и оригинала различаются.
+pub fn apply_contour_overlay(
+    mask: &DynamicImage,
+    original: &DynamicImage,
+    color: [u8; 3],
+    thickness: u32,
+) -> Option<(DynamicImage, usize, usize)> {
+    if mask.dimensions() != original.dimensions() {
+        return None;
+    }
+    let contours = contour_mask(&mask.to_luma8());
+    let (components, _) = label_components(&contours, Connectivity::Eight);
+    let length = contours.iter().filter(|&&p| p != 0).count();
+
+    let mut img = original.to_rgb8();
+    let (width, height) = img.dimensions();
+    // Линия толщиной t занимает смещения -(t - 1) / 2 ..= t / 2 от контура
+    let thickness = thickness.max(1) as i64;
+    let (before, after) = ((thickness - 1) / 2, thickness / 2);
+    for (x, y, pixel) in contours.enumerate_pixels() {
+        if pixel[0] == 0 {
+            continue;
+        }
+        for dy in -before..=after {
+            for dx in -before..=after {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx >= 0 && ny >= 0 && nx < width as i64 && ny < height as i64 {
+                    img.get_pixel_mut(nx as u32, ny as u32).0 = color;
+                }
+            }
+        }
+    }
+    Some((DynamicImage::ImageRgb8(img), components.len(), length))
+}
+
+/// Интегральное изображение (таблица сумм) размера (w + 1) × (h + 1):
+/// сумма по любому прямоугольнику считается за O(1)
+struct IntegralImage {
+    width: usize,
+    data: Vec<u64>,
+}
+
+impl IntegralImage {
+    fn new(width: usize, height: usize, values: impl IntoIterator<Item = u64>) -> Self {
+        let stride = width + 1;
+        let mut data = vec![0u64; stride * (height + 1)];
+        let mut values = values.into_iter();
+        for y in 0..height {
+            let mut row_sum = 0u64;
+            for x in 0..width {
+                row_sum += values.next().unwrap_or(0);
+                data[(y + 1) * stride + x + 1] = data[y * stride + x + 1] + row_sum;
+            }
+        }
+        Self { width, data }
+    }
+
+    /// Сумма по прямоугольнику [x0, x1) × [y0, y1)
+    fn sum(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> u64 {
+        let stride = self.width + 1;
+        self.data[y1 * stride + x1] + self.data[y0 * stride + x0]
+            - self.data[y0 * stride + x1]
+            - self.data[y1 * stride + x0]
+    }
+}
+
+/// Методы локальной бинаризации
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LocalMethod {
+    /// Среднее по окну минус константа C
+    Mean,
+    /// Взвешенное гауссовым ядром среднее по окну минус константа C
+    Gaussian,
+    /// Ниблэк: среднее + k·σ
+    Niblack,
+    /// Саувола: среднее·(1 + k·(σ/R − 1))
+    Sauvola,
+}
+
+impl LocalMethod {
+    pub const ALL: [LocalMethod; 4] =
+        [LocalMethod::Mean, LocalMethod::Gaussian, LocalMethod::Niblack, LocalMethod::Sauvola];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LocalMethod::Mean => "Локальное среднее",
+            LocalMethod::Gaussian => "Гауссово среднее",
+            LocalMethod::Niblack => "Ниблэк",
+            LocalMethod::Sauvola => "Саувола",
+        }
+    }
+
+    /// Использует ли метод коэффициент k (иначе - константу C)
+    pub fn uses_k(self) -> bool {
+        matches!(self, LocalMethod::Niblack | LocalMethod::Sauvola)
+    }
+}
+
+/// Динамический диапазон σ для метода Сауволы по умолчанию (для 8 бит)
+pub const SAUVOLA_R: f32 = 128.0;
+
+/// Локальные статистики яркости: интегральные изображения сумм и сумм
+/// квадратов дают среднее и СКО любого окна за O(1)
+struct LocalThreshold {
+    width: usize,
+    height: usize,
+    sum: IntegralImage,
+    sum_sq: IntegralImage,
+    /// Средние, взвешенные гауссовым ядром (только для LocalMethod::Gaussian)
+    gaussian_mean: Option<Vec<f32>>,
+}
+
+impl LocalThreshold {
+    fn new(gray_image: &GrayImage, method: LocalMethod, window: usize) -> Self {
+        let width = gray_image.width() as usize;
+        let height = gray_image.height() as usize;
+        let sum = IntegralImage::new(width, height, gray_image.iter().map(|&p| p as u64));
+        let sum_sq = IntegralImage::new(width, height, gray_image.iter().map(|&p| (p as u64).pow(2)));
+        let gaussian_mean = (method == LocalMethod::Gaussian).then(|| gaussian_window_mean(gray_image, window));
+        Self { width, height, sum, sum_sq, gaussian_mean }
+    }
+
+    /// Среднее и СКО в окне `window`×`window` с центром в (x, y);
+    /// окно обрезается по границам изображения
+    fn window_stats(&self, x: usize, y: usize, window: usize) -> (f32, f32) {
+        let half = window / 2;
+        let x0 = x.saturating_sub(half);
+        let y0 = y.saturating_sub(half);
+        let x1 = (x + half + 1).min(self.width);
+        let y1 = (y + half + 1).min(self.height);
+        let count = ((x1 - x0) * (y1 - y0)) as f64;
+
+        let mean = self.sum.sum(x0, y0, x1, y1) as f64 / count;
+        let mean_sq = self.sum_sq.sum(x0, y0, x1, y1) as f64 / count;
+        // Ошибка округления может дать отрицательную дисперсию
+        let variance = (mean_sq - mean * mean).max(0.0);
+        (mean as f32, variance.sqrt() as f32)
+    }
+
+    /// Порог для пикселя; `param` - это k для Ниблэка и Сауволы и C для средних,
+    /// `sauvola_r` - динамический диапазон σ (используется только Сауволой)
+    fn threshold(&self, x: usize, y: usize, window: usize, method: LocalMethod, param: f32, sauvola_r: f32) -> f32 {
+        let (mean, std_dev) = self.window_stats(x, y, window);
+        match method {
+            LocalMethod::Mean => mean - param,
+            LocalMethod::Gaussian => match &self.gaussian_mean {
+                Some(weighted) => weighted[y * self.width + x] - param,
+                None => mean - param,
+            },
+            LocalMethod::Niblack => mean + param * std_dev,
+            LocalMethod::Sauvola => mean * (1.0 + param * (std_dev / sauvola_r - 1.0)),
+        }
+    }
+}
+
+/// Локальная бинаризация выбранным методом, результат - Luma8 со значениями 0/255
+pub fn apply_local_threshold(
+    image: &DynamicImage,
+    method: LocalMethod,
+    window: u32,
+    param: f32,
+    sauvola_r: f32,
+) -> DynamicImage {
+    let mut gray_image = image.to_luma8();
+    let (width, height) = gray_image.dimensions();
+    let window = window.clamp(1, width.max(height).max(1)) as usize;
+    let local = LocalThreshold::new(&gray_image, method, window);
+
+    for (x, y, pixel) in gray_image.enumerate_pixels_mut() {
+        let threshold = local.threshold(x as usize, y as usize, window, method, param, sauvola_r);
+        pixel[0] = if pixel[0] as f32 > threshold { 255 } else { 0 };
+    }
+    DynamicImage::ImageLuma8(gray_image)
+}
+
+/// Нормированное гауссово ядро длины 2·`radius` + 1
+fn gaussian_weights(sigma: f32, radius: usize) -> Vec<f32> {
+    let radius = radius as isize;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+/// Гауссово ядро длины `window` (нечетной); σ выбирается по размеру окна
+/// так же, как в OpenCV
+fn gaussian_kernel(window: usize) -> Vec<f32> {
+    let sigma = 0.3 * ((window as f32 - 1.0) * 0.5 - 1.0) + 0.8;
+    gaussian_weights(sigma, window / 2)
+}
+
+/// Взвешенное гауссовым ядром среднее по окну для каждого пикселя.
+/// Свертка раздельная (строки, затем столбцы); у границ веса
+/// перенормируются по части окна внутри изображения.
+fn gaussian_window_mean(gray_image: &GrayImage, window: usize) -> Vec<f32> {
+    let source: Vec<f32> = gray_image.iter().map(|&p| p as f32).collect();
+    let (width, height) = gray_image.dimensions();
+    gaussian_plane_mean(&source, width as usize, height as usize, &gaussian_kernel(window | 1))
+}
+
+/// Раздельная свертка плоскости `source` ядром `kernel` с перенормировкой у границ
+fn gaussian_plane_mean(source: &[f32], width: usize, height: usize, kernel: &[f32]) -> Vec<f32> {
+    let half = kernel.len() / 2;
+
+    let convolve = |source: &[f32], len: usize, step: usize, out: &mut [f32]| {
+        for i in 0..len {
+            let from = i.saturating_sub(half);
+            let to = (i + half + 1).min(len);
+            let mut total = 0.0;
+            let mut weight = 0.0;
+            for j in from..to {
+                let w = kernel[j + half - i];
+                total += w * source[j * step];
+                weight += w;
+            }
+            out[i * step] = total / weight;
+        }
+    };
+
+    let mut rows = vec![0.0; source.len()];
+    for y in 0..height {
+        let line = y * width..(y + 1) * width;
+        convolve(&source[line.clone()], width, 1, &mut rows[line]);
+    }
+    let mut result = vec![0.0; source.len()];
+    for x in 0..width {
+        convolve(&rows[x..], height, width, &mut result[x..]);
+    }
+    result
+}
+
+/// Медианный фильтр с окном (2r+1)×(2r+1) по каждому каналу, края
+/// зажимаются. Для r ≤ 2 окно сортируется; для больших радиусов служит
+/// скользящая гистограмма (метод Хуанга): при сдвиге окна обновляются только
+/// крайние столбцы, а медиана смещается от предыдущего значения.
+pub fn apply_median_filter(image: &DynamicImage, radius: u8) -> DynamicImage {
+    if radius == 0 {
+        return image.clone();
+    }
+    let src = image.to_rgb8();
+    let (width, height) = src.dimensions();
+    let mut dst = RgbImage::new(width, height);
+    let r = radius as i64;
+    let at = |x: i64, y: i64, c: usize| {
+        src.get_pixel(x.clamp(0, width as i64 - 1) as u32, y.clamp(0, height as i64 - 1) as u32)[c]
+    };
+    // Медиана - элемент с индексом n/2 в отсортированном окне
+    let half = ((2 * r + 1) * (2 * r + 1) / 2) as usize;
+
+    if radius <= 2 {
+        let mut window = Vec::with_capacity((2 * r as usize + 1).pow(2));
+        for (x, y, pixel) in dst.enumerate_pixels_mut() {
+            for c in 0..3 {
+                window.clear();
+                for dy in -r..=r {
+                    for dx in -r..=r {
+                        window.push(at(x as i64 + dx, y as i64 + dy, c));
+                    }
+                }
+                pixel[c] = *window.select_nth_unstable(half).1;
+            }
+        }
+        return DynamicImage::ImageRgb8(dst);
+    }
+
+    for y in 0..height as i64 {
+        for c in 0..3 {
+            let mut histogram = [0usize; 256];
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    histogram[at(dx, y + dy, c) as usize] += 1;
+                }
+            }
+            // Текущая медиана и число значений окна меньше нее
+            let mut median = 0usize;
+            let mut below = 0usize;
+            for x in 0..width as i64 {
+                if x > 0 {
+                    for dy in -r..=r {
+                        let removed = at(x - r - 1, y + dy, c) as usize;
+                        histogram[removed] -= 1;
+                        if removed < median {
+                            below -= 1;
+                        }
+                        let added = at(x + r, y + dy, c) as usize;
+                        histogram[added] += 1;
+                        if added < median {
+                            below += 1;
+                        }
+                    }
+                }
+                while below > half {
+                    median -= 1;
+                    below -= histogram[median];
+                }
+                while below + histogram[median] <= half {
+                    below += histogram[median];
+                    median += 1;
+                }
+                dst.get_pixel_mut(x as u32, y as u32)[c] = median as u8;
+            }
+        }
+    }
+    DynamicImage::ImageRgb8(dst)
+}
+
+/// Фильтр Кувахары: из четырех перекрывающихся квадрантов (r+1)×(r+1) вокруг
+/// пикселя выбирается квадрант с наименьшей дисперсией яркости, и пиксель
+/// получает его средний цвет. Координаты за краем зажимаются: суммы
+/// считаются по изображению, расширенному на r повтором крайних пикселей,
+/// поэтому у границ все квадранты того же размера.
+pub fn apply_kuwahara(image: &DynamicImage, radius: u8) -> DynamicImage {
+    let rgb = image.to_rgb8();
+    if radius == 0 || rgb.width() == 0 || rgb.height() == 0 {
+        return DynamicImage::ImageRgb8(rgb);
+    }
+    let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+    let r = radius as usize;
+    let sides = (radius as u32, radius as u32, radius as u32, radius as u32);
+    let padded = pad_buffer(&rgb, sides, BorderMode::Replicate, Rgb([0; 3]));
+    let luma = pad_buffer(&image.to_luma8(), sides, BorderMode::Replicate, Luma([0]));
+    let (padded_width, padded_height) = (width + 2 * r, height + 2 * r);
+
+    let channel_sums: Vec<IntegralImage> = (0..3)
+        .map(|c| IntegralImage::new(padded_width, padded_height, padded.pixels().map(|p| p[c] as u64)))
+        .collect();
+    let luma_sum = IntegralImage::new(padded_width, padded_height, luma.iter().map(|&v| v as u64));
+    let luma_sq_sum =
+        IntegralImage::new(padded_width, padded_height, luma.iter().map(|&v| (v as u64) * (v as u64)));
+
+    // Пиксель (x, y) лежит в расширенном изображении в точке (x + r, y + r)
+    let n = ((r + 1) * (r + 1)) as f64;
+    let mut result = RgbImage::new(width as u32, height as u32);
+    for y in 0..height {
+        for x in 0..width {
+            let quadrants = [
+                (x, y, x + r + 1, y + r + 1),
+                (x + r, y, x + 2 * r + 1, y + r + 1),
+                (x, y + r, x + r + 1, y + 2 * r + 1),
+                (x + r, y + r, x + 2 * r + 1, y + 2 * r + 1),
+            ];
+
+            let mut best = quadrants[0];
+            let mut best_variance = f64::MAX;
+            for q in quadrants {
+                let mean = luma_sum.sum(q.0, q.1, q.2, q.3) as f64 / n;
+                let variance = luma_sq_sum.sum(q.0, q.1, q.2, q.3) as f64 / n - mean * mean;
+                if variance < best_variance {
+                    best_variance = variance;
+                    best = q;
+                }
+            }
+
+            let pixel = result.get_pixel_mut(x as u32, y as u32);
+            for (c, sums) in channel_sums.iter().enumerate() {
+                pixel[c] = (sums.sum(best.0, best.1, best.2, best.3) as f64 / n).round() as u8;
+            }
+        }
+    }
+
+    DynamicImage::ImageRgb8(result)
+}
+
+/// Скользящее среднее по окну из `length` отсчетов, начинающемуся за `before`
+/// отсчетов до текущего; индексы за краем строки зажимаются. Стоимость не
+/// зависит от длины окна.
+fn running_mean(line: &[u8], before: usize, length: usize, out: &mut [u8]) {
+    let n = line.len() as i64;
+    if n == 0 || length == 0 {
+        return;
+    }
+    let at = |i: i64| line[i.clamp(0, n - 1) as usize] as u64;
+    let before = before as i64;
+    let len = length as i64;
+
+    let mut sum: u64 = (0..len).map(|k| at(k - before)).sum();
+    for (i, o) in out.iter_mut().enumerate() {
+        let i = i as i64;
+        *o = ((sum + length as u64 / 2) / length as u64) as u8;
+        sum = sum + at(i - before + len) - at(i - before);
+    }
+}
+
+/// Скользящее среднее вдоль каждой строки, по каждому каналу отдельно
+fn blur_rows(img: &mut RgbImage, before: usize, length: usize) {
+    let (width, height) = img.dimensions();
+    let mut line = vec![0u8; width as usize];
+    let mut out = vec![0u8; width as usize];
+    for y in 0..height {
+        for c in 0..3 {
+            for x in 0..width {
+                line[x as usize] = img.get_pixel(x, y)[c];
+            }
+            running_mean(&line, before, length, &mut out);
+            for x in 0..width {
+                img.get_pixel_mut(x, y)[c] = out[x as usize];
+            }
+        }
+    }
+}
+
+/// Скользящее среднее вдоль каждого столбца, по каждому каналу отдельно
+fn blur_columns(img: &mut RgbImage, before: usize, length: usize) {
+    let (width, height) = img.dimensions();
+    let mut line = vec![0u8; height as usize];
+    let mut out = vec![0u8; height as usize];
+    for x in 0..width {
+        for c in 0..3 {
+            for y in 0..height {
+                line[y as usize] = img.get_pixel(x, y)[c];
+            }
+            running_mean(&line, before, length, &mut out);
+            for y in 0..height {
+                img.get_pixel_mut(x, y)[c] = out[y as usize];
+            }
+        }
+    }
+}
+
+/// Усредняющий фильтр с окном (2r+1)×(2r+1): раздельные проходы по строкам
+/// и столбцам скользящей суммой, края зажимаются. Три итерации дают
+/// хорошее приближение гауссова размытия.
+pub fn apply_box_blur(image: &DynamicImage, radius: u32, iterations: u8) -> DynamicImage {
+    // Нулевой радиус - точное тождество, без перевода в RGB
+    if radius == 0 || iterations == 0 {
+        return image.clone();
+    }
+    let mut img = image.to_rgb8();
+    let radius = radius as usize;
+    for _ in 0..iterations {
+        blur_rows(&mut img, radius, 2 * radius + 1);
+        blur_columns(&mut img, radius, 2 * radius + 1);
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Размытие по Гауссу: два раздельных одномерных прохода (строки, затем
+/// столбцы) с ядром радиуса ⌈3σ⌉; за границей изображения повторяются
+/// крайние пиксели. Результат - каналы RGB подряд в f32, без округления.
+fn gaussian_blur_f32(rgb_image: &RgbImage, sigma: f32) -> Vec<f32> {
+    let (width, height) = rgb_image.dimensions();
+    let source: Vec<f32> = rgb_image.as_raw().iter().map(|&v| v as f32).collect();
+    if sigma <= 0.0 || width == 0 || height == 0 {
+        return source;
+    }
+    let (width, height) = (width as usize, height as usize);
+    let radius = (3.0 * sigma).ceil() as usize;
+    let kernel = gaussian_weights(sigma, radius);
+    let row_len = width * 3;
+
+    // По строкам: строка дополняется крайними пикселями, и каждая пара
+    // симметричных отсчетов ядра добавляется сдвинутыми срезами - без
+    // проверок границ во внутреннем цикле
+    let mut rows = vec![0f32; source.len()];
+    let mut padded = vec![0f32; (width + 2 * radius) * 3];
+    for (src, dst) in source.chunks_exact(row_len).zip(rows.chunks_exact_mut(row_len)) {
+        for (i, px) in padded.chunks_exact_mut(3).enumerate() {
+            let x = i.saturating_sub(radius).min(width - 1);
+            px.copy_from_slice(&src[x * 3..x * 3 + 3]);
+        }
+        let center = &padded[radius * 3..radius * 3 + row_len];
+        for (d, &s) in dst.iter_mut().zip(center) {
+            *d = kernel[radius] * s;
+        }
+        for k in 1..=radius {
+            let left = &padded[(radius - k) * 3..(radius - k) * 3 + row_len];
+            let right = &padded[(radius + k) * 3..(radius + k) * 3 + row_len];
+            for ((d, &l), &r) in dst.iter_mut().zip(left).zip(right) {
+                *d += kernel[radius + k] * (l + r);
+            }
+        }
+    }
+
+    // По столбцам: строка результата - взвешенная сумма соседних строк
+    let row = |y: usize| &rows[y * row_len..(y + 1) * row_len];
+    let mut result = vec![0f32; source.len()];
+    for (y, dst) in result.chunks_exact_mut(row_len).enumerate() {
+        for (d, &s) in dst.iter_mut().zip(row(y)) {
+            *d = kernel[radius] * s;
+        }
+        for k in 1..=radius {
+            let above = row(y.saturating_sub(k));
+            let below = row((y + k).min(height - 1));
+            for ((d, &a), &b) in dst.iter_mut().zip(above).zip(below) {
+                *d += kernel[radius + k] * (a + b);
+            }
+        }
+    }
+
+    result
+}
+
+/// Размытие по Гауссу с σ = `sigma`, результат - Rgb8
+pub fn apply_gaussian_blur(image: &DynamicImage, sigma: f32) -> DynamicImage {
+    let rgb_image = image.to_rgb8();
+    let (width, height) = rgb_image.dimensions();
+    let data = gaussian_blur_f32(&rgb_image, sigma).iter().map(|&v| v.round().clamp(0.0, 255.0) as u8).collect();
+    DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, data).unwrap())
+}
+
+/// Нерезкое маскирование: к изображению добавляется `amount`·(I − G_σ * I),
+/// где разность считается по размытию в f32. Каналы, у которых |разность|
+/// не больше `threshold`, не меняются, чтобы не усиливать шум на ровных
+/// участках. `amount` = 1 (100 %) и `threshold` = 0 - классическая формула.
+pub fn apply_unsharp_mask(image: &DynamicImage, sigma: f32, amount: f32, threshold: u8) -> DynamicImage {
+    let mut rgb_image = image.to_rgb8();
+    let blurred = gaussian_blur_f32(&rgb_image, sigma);
+    for (value, blur) in rgb_image.iter_mut().zip(blurred) {
+        let difference = *value as f32 - blur;
+        if difference.abs() > threshold as f32 {
+            *value = (*value as f32 + amount * difference).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgb8(rgb_image)
+}
+
+/// Свертка каждого из `channels` чередующихся каналов буфера `data` с ядром
+/// нечетного размера `kernel_width`×(weights.len() / kernel_width), веса
+/// построчно; за границей повторяются крайние пиксели. Результат в f32.
+fn convolve(data: &[u8], width: usize, height: usize, channels: usize, kernel_width: usize, weights: &[f32]) -> Vec<f32> {
+    let rx = (kernel_width / 2) as i64;
+    let ry = (weights.len() / kernel_width / 2) as i64;
+    let at = |x: i64, y: i64, c: usize| {
+        let x = x.clamp(0, width as i64 - 1) as usize;
+        let y = y.clamp(0, height as i64 - 1) as usize;
+        data[(y * width + x) * channels + c] as f32
+    };
+    let mut result = Vec::with_capacity(data.len());
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            for c in 0..channels {
+                let mut sum = 0.0;
+                for (dy, row) in (-ry..=ry).zip(weights.chunks_exact(kernel_width)) {
+                    for (dx, &w) in (-rx..=rx).zip(row) {
+                        sum += w * at(x + dx, y + dy, c);
+                    }
+                }
+                result.push(sum);
+            }
+        }
+    }
+    result
+}
+
+fn convolve_3x3(data: &[u8], width: usize, height: usize, channels: usize, kernel: &[[f32; 3]; 3]) -> Vec<f32> {
+    convolve(data, width, height, channels, 3, kernel.as_flattened())
+}
+
+/// Произвольное ядро свертки: результат = Σ w·p / divisor + offset
+#[derive(Clone, PartialEq, Debug)]
+pub struct Kernel {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    /// Веса построчно, width × height значений
+    pub weights: Vec<f32>,
+    pub divisor: f32,
+    pub offset: f32,
+}
+
+/// Свертка каждого канала RGB с ядром `kernel`, края повторяются
+pub fn apply_kernel(image: &DynamicImage, kernel: &Kernel) -> DynamicImage {
+    let mut rgb_image = image.to_rgb8();
+    let (width, height) = rgb_image.dimensions();
+    let sums = convolve(rgb_image.as_raw(), width as usize, height as usize, 3, kernel.width, &kernel.weights);
+    for (value, sum) in rgb_image.iter_mut().zip(sums) {
+        *value = (sum / kernel.divisor + kernel.offset).round().clamp(0.0, 255.0) as u8;
+    }
+    DynamicImage::ImageRgb8(rgb_image)
+}
+
+const LAPLACIAN_KERNEL: [[f32; 3]; 3] = [[0.0, 1.0, 0.0], [1.0, -4.0, 1.0], [0.0, 1.0, 0.0]];
+
+/// Повышение резкости лапласианом: из каждого канала вычитается
+/// `strength`·∇², где ∇² - свертка с ядром [0 1 0; 1 −4 1; 0 1 0].
+/// При `show_laplacian` выводится сам отклик со сдвигом 128, чтобы были
+/// видны отрицательные значения. Края повторяются.
+pub fn apply_laplacian_sharpen(image: &DynamicImage, strength: f32, show_laplacian: bool) -> DynamicImage {
+    if strength == 0.0 && !show_laplacian {
+        return image.clone();
+    }
+    let mut rgb_image = image.to_rgb8();
+    let (width, height) = rgb_image.dimensions();
+    let laplacian = convolve_3x3(rgb_image.as_raw(), width as usize, height as usize, 3, &LAPLACIAN_KERNEL);
+    for (value, l) in rgb_image.iter_mut().zip(laplacian) {
+        let result = if show_laplacian { l + 128.0 } else { *value as f32 - strength * l };
+        *value = result.round().clamp(0.0, 255.0) as u8;
+    }
+    DynamicImage::ImageRgb8(rgb_image)
+}
+
+/// Направление света для тиснения
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LightDirection {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+}
+
+impl LightDirection {
+    pub const ALL: [LightDirection; 8] = [
+        LightDirection::TopLeft,
+        LightDirection::Top,
+        LightDirection::TopRight,
+        LightDirection::Right,
+        LightDirection::BottomRight,
+        LightDirection::Bottom,
+        LightDirection::BottomLeft,
+        LightDirection::Left,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LightDirection::TopLeft => "↖",
+            LightDirection::Top => "↑",
+            LightDirection::TopRight => "↗",
+            LightDirection::Right => "→",
+            LightDirection::BottomRight => "↘",
+            LightDirection::Bottom => "↓",
+            LightDirection::BottomLeft => "↙",
+            LightDirection::Left => "←",
+        }
+    }
+
+    /// Ядро тиснения: базовое ядро для света сверху слева, повернутое по
+    /// часовой стрелке на 45° за каждый шаг направления
+    fn emboss_kernel(self) -> [[f32; 3]; 3] {
+        const BASE: [[f32; 3]; 3] = [[-2.0, -1.0, 0.0], [-1.0, 0.0, 1.0], [0.0, 1.0, 2.0]];
+        const RING: [(usize, usize); 8] = [(0, 0), (0, 1), (0, 2), (1, 2), (2, 2), (2, 1), (2, 0), (1, 0)];
+        let steps = self as usize;
+        let mut kernel = BASE;
+        for (i, &(row, col)) in RING.iter().enumerate() {
+            let (to_row, to_col) = RING[(i + steps) % 8];
+            kernel[to_row][to_col] = BASE[row][col];
+        }
+        kernel
+    }
+}
+
+/// Тиснение: свертка яркости с направленным ядром и сдвиг на 128. Сумма
+/// весов ядра нулевая, поэтому ровные участки дают серый 128. При
+/// `colored` исходный цвет умножается на рельеф (128 - без изменений).
+pub fn apply_emboss(image: &DynamicImage, direction: LightDirection, colored: bool) -> DynamicImage {
+    let gray_image = image.to_luma8();
+    let (width, height) = gray_image.dimensions();
+    let relief: Vec<f32> = convolve_3x3(gray_image.as_raw(), width as usize, height as usize, 1, &direction.emboss_kernel())
+        .into_iter()
+        .map(|v| (v + 128.0).clamp(0.0, 255.0))
+        .collect();
+
+    if colored {
+        let mut rgb_image = image.to_rgb8();
+        for (pixel, &r) in rgb_image.pixels_mut().zip(&relief) {
+            for v in pixel.0.iter_mut() {
+                *v = (*v as f32 * r / 128.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        return DynamicImage::ImageRgb8(rgb_image);
+    }
+
+    let data = relief.iter().map(|&v| v.round() as u8).collect();
+    DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, data).unwrap())
+}
+
+/// Оператор оценки градиента яркости
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GradientOperator {
+    Sobel,
+    Prewitt,
+    Scharr,
+    /// Перекрестный оператор Робертса 2×2: его Gx и Gy - производные
+    /// по диагоналям, а не по осям
+    Roberts,
+}
+
+impl GradientOperator {
+    pub const ALL: [GradientOperator; 4] =
+        [GradientOperator::Sobel, GradientOperator::Prewitt, GradientOperator::Scharr, GradientOperator::Roberts];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GradientOperator::Sobel => "Собель",
+            GradientOperator::Prewitt => "Превитт",
+            GradientOperator::Scharr => "Шарр",
+            GradientOperator::Roberts => "Робертс",
+        }
+    }
+
+    /// Веса сглаживания поперек направления производной для ядер 3×3
+    /// (ядро Робертса их не использует)
+    fn smoothing(self) -> [f32; 3] {
+        match self {
+            GradientOperator::Sobel | GradientOperator::Roberts => [1.0, 2.0, 1.0],
+            GradientOperator::Prewitt => [1.0, 1.0, 1.0],
+            GradientOperator::Scharr => [3.0, 10.0, 3.0],
+        }
+    }
+}
+
+/// Градиент яркости: плоскости Gx и Gy построчно. За границей изображения
+/// повторяются крайние пиксели, поэтому последние строка и столбец
+/// обрабатываются и ядром Робертса 2×2.
+fn compute_gradient(gray_image: &GrayImage, operator: GradientOperator) -> (Vec<f32>, Vec<f32>) {
+    let (width, height) = gray_image.dimensions();
+    let at = |x: i64, y: i64| {
+        gray_image.get_pixel(x.clamp(0, width as i64 - 1) as u32, y.clamp(0, height as i64 - 1) as u32)[0] as f32
+    };
+    let weights = operator.smoothing();
+    let len = (width * height) as usize;
+    let mut gx = Vec::with_capacity(len);
+    let mut gy = Vec::with_capacity(len);
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            if operator == GradientOperator::Roberts {
+                gx.push(at(x, y) - at(x + 1, y + 1));
+                gy.push(at(x + 1, y) - at(x, y + 1));
+                continue;
+            }
+            let (mut dx, mut dy) = (0.0, 0.0);
+            for (k, w) in (-1..=1).zip(weights) {
+                dx += w * (at(x + 1, y + k) - at(x - 1, y + k));
+                dy += w * (at(x + k, y + 1) - at(x + k, y - 1));
+            }
+            gx.push(dx);
+            gy.push(dy);
+        }
+    }
+    (gx, gy)
+}
+
+/// Что выводит выделение границ
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GradientOutput {
+    /// Модуль градиента
+    Magnitude,
+    /// |Gx| - вертикальные границы
+    GradientX,
+    /// |Gy| - горизонтальные границы
+    GradientY,
+    /// Направление градиента как тон, модуль как яркость
+    Direction,
+}
+
+impl GradientOutput {
+    pub const ALL: [GradientOutput; 4] =
+        [GradientOutput::Magnitude, GradientOutput::GradientX, GradientOutput::GradientY, GradientOutput::Direction];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GradientOutput::Magnitude => "Модуль",
+            GradientOutput::GradientX => "|Gx|",
+            GradientOutput::GradientY => "|Gy|",
+            GradientOutput::Direction => "Направление",
+        }
+    }
+}
+
+/// Границы выбранным оператором; значения нормируются к 0..255 по
+/// максимуму. Результат - Luma8, для направления - Rgb8.
+pub fn apply_gradient(image: &DynamicImage, operator: GradientOperator, output: GradientOutput) -> DynamicImage {
+    let gray_image = image.to_luma8();
+    let (width, height) = gray_image.dimensions();
+    let (gx, gy) = compute_gradient(&gray_image, operator);
+    let values: Vec<f32> = match output {
+        GradientOutput::Magnitude | GradientOutput::Direction => gx.iter().zip(&gy).map(|(x, y)| x.hypot(*y)).collect(),
+        GradientOutput::GradientX => gx.iter().map(|v| v.abs()).collect(),
+        GradientOutput::GradientY => gy.iter().map(|v| v.abs()).collect(),
+    };
+    let max = values.iter().copied().fold(0.0f32, f32::max);
+    let scale = if max > 0.0 { 1.0 / max } else { 0.0 };
+
+    if output == GradientOutput::Direction {
+        let mut rgb_image = RgbImage::new(width, height);
+        for (i, pixel) in rgb_image.pixels_mut().enumerate() {
+            let hue = gy[i].atan2(gx[i]).to_degrees().rem_euclid(360.0);
+            let (r, g, b) = hsv_to_rgb(hue, 1.0, values[i] * scale);
+            pixel.0 = [r, g, b];
+        }
+        return DynamicImage::ImageRgb8(rgb_image);
+    }
+
+    let data = values.iter().map(|v| (v * scale * 255.0).round() as u8).collect();
+    DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, data).unwrap())
+}
+
+/// Подавление немаксимумов: модуль градиента сохраняется, только если он
+/// не меньше соседей вдоль направления градиента, округленного до 45°.
+/// При равенстве побеждает пиксель со стороны роста координат, чтобы
+/// плато давало линию в один пиксель. Соседи за границей считаются нулевыми.
+fn non_max_suppression(width: usize, height: usize, gx: &[f32], gy: &[f32]) -> Vec<f32> {
+    let magnitude: Vec<f32> = gx.iter().zip(gy).map(|(x, y)| x.hypot(*y)).collect();
+    let at = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+            0.0
+        } else {
+            magnitude[y as usize * width + x as usize]
+        }
+    };
+
+    let mut suppressed = vec![0.0; magnitude.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let angle = gy[i].atan2(gx[i]).to_degrees().rem_euclid(180.0);
+            let (dx, dy) = match angle {
+                a if !(22.5..157.5).contains(&a) => (1, 0),
+                a if a < 67.5 => (1, 1),
+                a if a < 112.5 => (0, 1),
+                _ => (-1, 1),
+            };
+            let (x, y) = (x as i64, y as i64);
+            let m = magnitude[i];
+            if m > at(x - dx, y - dy) && m >= at(x + dx, y + dy) {
+                suppressed[i] = m;
+            }
+        }
+    }
+    suppressed
+}
+
+/// Двойной порог по модулю градиента и связывание слабых границ с сильными
+fn edge_hysteresis(width: usize, height: usize, magnitude: &[f32], low: f32, high: f32) -> Vec<u8> {
+    let mut levels: Vec<u8> = magnitude
+        .iter()
+        .map(|&m| {
+            if m > high {
+                HYSTERESIS_STRONG
+            } else if m > low {
+                HYSTERESIS_WEAK
+            } else {
+                HYSTERESIS_NONE
+            }
+        })
+        .collect();
+    hysteresis_link(width, height, &mut levels);
+    levels
+}
+
+/// Детектор Кэнни: размытие по Гауссу, градиент Собеля, подавление
+/// немаксимумов и гистерезис. Пороги задаются в перепадах яркости: модуль
+/// градиента Собеля делится на 4, так что ступенька высотой h дает h.
+/// Результат - Luma8 со значениями 0/255.
+pub fn apply_canny(image: &DynamicImage, sigma: f32, low: u8, high: u8) -> DynamicImage {
+    let gray_image = apply_gaussian_blur(image, sigma).to_luma8();
+    let (width, height) = gray_image.dimensions();
+    let (width, height) = (width as usize, height as usize);
+    let (gx, gy) = compute_gradient(&gray_image, GradientOperator::Sobel);
+    let magnitude = non_max_suppression(width, height, &gx, &gy);
+    let low = low.min(high) as f32 * 4.0;
+    let levels = edge_hysteresis(width, height, &magnitude, low, high as f32 * 4.0);
+
+    let data = levels.iter().map(|&l| if l == HYSTERESIS_STRONG { 255 } else { 0 }).collect();
+    DynamicImage::ImageLuma8(GrayImage::from_raw(width as u32, height as u32, data).unwrap())
+}
+
+/// Прямая в нормальной форме x·cos θ + y·sin θ = ρ, θ в градусах 0..180
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HoughLine {
+    pub rho: f32,
+    pub theta: f32,
+    pub votes: u32,
+}
+
+/// Результат преобразования Хафа
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HoughOutput {
+    /// Найденные прямые поверх исходного изображения
+    Lines,
+    /// Аккумулятор: θ по горизонтали, ρ по вертикали
+    Accumulator,
+}
+
+impl HoughOutput {
+    pub const ALL: [HoughOutput; 2] = [HoughOutput::Lines, HoughOutput::Accumulator];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HoughOutput::Lines => "Прямые",
+            HoughOutput::Accumulator => "Аккумулятор",
+        }
+    }
+}
+
+/// Аккумулятор голосов: строки - ρ от -max_rho до max_rho с шагом 1,
+/// столбцы - θ с шагом `theta_step`
+struct HoughAccumulator {
+    votes: Vec<u32>,
+    thetas: usize,
+    rhos: usize,
+    max_rho: i64,
+    theta_step: f32,
+}
+
+impl HoughAccumulator {
+    fn new(edges: &GrayImage, theta_step: f32) -> Self {
+        let (width, height) = edges.dimensions();
+        let max_rho = (width as f64).hypot(height as f64).ceil() as i64;
+        let thetas = (180.0 / theta_step).ceil() as usize;
+        let rhos = 2 * max_rho as usize + 1;
+        let trig: Vec<(f32, f32)> = (0..thetas).map(|t| (t as f32 * theta_step).to_radians().sin_cos()).collect();
+        let mut votes = vec![0u32; thetas * rhos];
+        for (x, y, pixel) in edges.enumerate_pixels() {
+            if pixel[0] == 0 {
+                continue;
+            }
+            for (t, &(sin, cos)) in trig.iter().enumerate() {
+                let rho = (x as f32 * cos + y as f32 * sin).round() as i64;
+                votes[(rho + max_rho) as usize * thetas + t] += 1;
+            }
+        }
+        Self { votes, thetas, rhos, max_rho, theta_step }
+    }
+
+    /// Не более `count` сильнейших локальных максимумов с не меньше чем
+    /// `threshold` голосами. Окрестность подавления - ±5 по ρ и ±3° по θ;
+    /// за краем θ = 180° продолжается как θ = 0° с ρ противоположного знака.
+    fn peaks(&self, threshold: u32, count: usize) -> Vec<HoughLine> {
+        let rho_window = 5i64;
+        let theta_window = ((3.0 / self.theta_step).round() as i64).max(1);
+        let (thetas, rhos) = (self.thetas as i64, self.rhos as i64);
+        let mut peaks = Vec::new();
+        for r in 0..rhos {
+            for t in 0..thetas {
+                let index = (r * thetas + t) as usize;
+                let votes = self.votes[index];
+                if votes == 0 || votes < threshold {
+                    continue;
+                }
+                // Равные голоса подавляет сосед с меньшим индексом
+                let is_peak = (-rho_window..=rho_window).all(|dr| {
+                    (-theta_window..=theta_window).all(|dt| {
+                        let (mut nr, mut nt) = (r + dr, t + dt);
+                        if nt < 0 || nt >= thetas {
+                            nt = nt.rem_euclid(thetas);
+                            nr = rhos - 1 - nr;
+                        }
+                        if nr < 0 || nr >= rhos || (dr == 0 && dt == 0) {
+                            return true;
+                        }
+                        let other_index = (nr * thetas + nt) as usize;
+                        let other = self.votes[other_index];
+                        other < votes || (other == votes && other_index > index)
+                    })
+                });
+                if is_peak {
+                    peaks.push(HoughLine {
+                        rho: (r - self.max_rho) as f32,
+                        theta: t as f32 * self.theta_step,
+                        votes,
+                    });
+                }
+            }
+        }
+        peaks.sort_by_key(|peak| std::cmp::Reverse(peak.votes));
+        peaks.truncate(count);
+        peaks
+    }
+
+    /// Аккумулятор как полутоновое изображение, яркость пропорциональна голосам
+    fn to_image(&self) -> DynamicImage {
+        let max = self.votes.iter().copied().max().unwrap_or(0).max(1) as u64;
+        let data = self.votes.iter().map(|&v| (v as u64 * 255 / max) as u8).collect();
+        DynamicImage::ImageLuma8(GrayImage::from_raw(self.thetas as u32, self.rhos as u32, data).unwrap())
+    }
+}
+
+/// Рисует прямую x·cos θ + y·sin θ = ρ через все изображение
+fn draw_polar_line(img: &mut RgbImage, line: &HoughLine, color: [u8; 3]) {
+    let (width, height) = img.dimensions();
+    let (sin, cos) = line.theta.to_radians().sin_cos();
+    // Шагаем по оси, вдоль которой прямая идет круче, - без разрывов
+    if sin.abs() >= cos.abs() {
+        for x in 0..width {
+            let y = ((line.rho - x as f32 * cos) / sin).round();
+            if y >= 0.0 && y < height as f32 {
+                img.get_pixel_mut(x, y as u32).0 = color;
+            }
+        }
+    } else {
+        for y in 0..height {
+            let x = ((line.rho - y as f32 * sin) / cos).round();
+            if x >= 0.0 && x < width as f32 {
+                img.get_pixel_mut(x as u32, y).0 = color;
+            }
+        }
+    }
+}
+
+/// Поиск прямых преобразованием Хафа по границам Кэнни: голосование в
+/// пространстве (ρ, θ) с шагом θ `theta_step` градусов, не более `count`
+/// пиков с не меньше чем `threshold` голосами. Результат - прямые цветом
+/// `color` поверх изображения или сам аккумулятор; также список прямых.
+pub fn apply_hough_lines(
+    image: &DynamicImage,
+    theta_step: f32,
+    threshold: u32,
+    count: usize,
+    output: HoughOutput,
+    color: [u8; 3],
+) -> (DynamicImage, Vec<HoughLine>) {
+    let edges = apply_canny(image, 1.4, 20, 50).into_luma8();
+    let accumulator = HoughAccumulator::new(&edges, theta_step.clamp(0.1, 10.0));
+    let lines = accumulator.peaks(threshold, count);
+    let result = match output {
+        HoughOutput::Lines => {
+            let mut img = image.to_rgb8();
+            for line in &lines {
+                draw_polar_line(&mut img, line, color);
+            }
+            DynamicImage::ImageRgb8(img)
+        }
+        HoughOutput::Accumulator => accumulator.to_image(),
+    };
+    (result, lines)
+}
+
+/// Билинейная выборка цвета в дробной точке; координаты за краем зажимаются
+fn sample_bilinear(img: &RgbImage, x: f32, y: f32) -> [f32; 3] {
+    let (width, height) = img.dimensions();
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let x0 = x.floor() as u32;
+    let y0 = y.floor() as u32;
+    let x1 = (x0 + 1).min(width - 1);
+    let y1 = (y0 + 1).min(height - 1);
+    let fx = x - x0 as f32;
+    let fy = y - y0 as f32;
+
+    let p00 = img.get_pixel(x0, y0);
+    let p10 = img.get_pixel(x1, y0);
+    let p01 = img.get_pixel(x0, y1);
+    let p11 = img.get_pixel(x1, y1);
+    let mut out = [0.0; 3];
+    for (c, o) in out.iter_mut().enumerate() {
+        let top = p00[c] as f32 * (1.0 - fx) + p10[c] as f32 * fx;
+        let bottom = p01[c] as f32 * (1.0 - fx) + p11[c] as f32 * fx;
+        *o = top * (1.0 - fy) + bottom * fy;
+    }
+    out
+}
+
+/// Размытие в движении: усреднение `length` отсчетов вдоль прямой под углом
+/// `angle_degrees` (0 - горизонталь, 90 - вертикаль) через каждый пиксель
+pub fn apply_motion_blur(image: &DynamicImage, length: u32, angle_degrees: f32) -> DynamicImage {
+    if length <= 1 {
+        return image.clone();
+    }
+    let mut img = image.to_rgb8();
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return DynamicImage::ImageRgb8(img);
+    }
+    let length = length as usize;
+    let before = length / 2;
+
+    let angle = angle_degrees.rem_euclid(180.0);
+    let horizontal = angle < 1e-4 || 180.0 - angle < 1e-4;
+    let vertical = (angle - 90.0).abs() < 1e-4;
+
+    if horizontal {
+        // Быстрый целочисленный путь: скользящее среднее по строкам
+        blur_rows(&mut img, before, length);
+        return DynamicImage::ImageRgb8(img);
+    }
+
+    if vertical {
+        blur_columns(&mut img, before, length);
+        return DynamicImage::ImageRgb8(img);
+    }
+
+    // Ось y направлена вниз, поэтому угол откладываем против часовой стрелки
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let offsets: Vec<(f32, f32)> = (0..length)
+        .map(|i| {
+            let t = i as f32 - before as f32;
+            (t * cos, -t * sin)
+        })
+        .collect();
+
+    let mut result = RgbImage::new(width, height);
+    for (x, y, pixel) in result.enumerate_pixels_mut() {
+        let mut sum = [0.0f32; 3];
+        for &(dx, dy) in &offsets {
+            let sample = sample_bilinear(&img, x as f32 + dx, y as f32 + dy);
+            for c in 0..3 {
+                sum[c] += sample[c];
+            }
+        }
+        for c in 0..3 {
+            pixel[c] = (sum[c] / length as f32).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgb8(result)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Пикселизация (мозаика): изображение делится на клетки `block`×`block`,
+/// каждая заливается средним цветом своих пикселей. Неполные клетки у
+/// правого и нижнего края усредняются по фактическому числу пикселей.
+pub fn apply_pixelate(image: &DynamicImage, block: u32) -> DynamicImage {
+    if block <= 1 {
+        return image.clone();
+    }
+    let mut img = image.to_rgb8();
+    let (width, height) = img.dimensions();
+    for y0 in (0..height).step_by(block as usize) {
+        let y1 = (y0 + block).min(height);
+        for x0 in (0..width).step_by(block as usize) {
+            let x1 = (x0 + block).min(width);
+            let mut sum = [0u64; 3];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let pixel = img.get_pixel(x, y);
+                    for c in 0..3 {
+                        sum[c] += pixel[c] as u64;
+                    }
+                }
+            }
+            let count = ((x1 - x0) * (y1 - y0)) as u64;
+            let mean = sum.map(|s| ((s + count / 2) / count) as u8);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    img.get_pixel_mut(x, y).0 = mean;
+                }
+            }
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Радиус точки растра в долях клетки, при котором круг закрывает долю
+/// `coverage` площади клетки. Круг больше половины клетки обрезается ее
+/// сторонами; при радиусе 1/√2 закрыта вся клетка.
+fn halftone_radius(coverage: f32) -> f32 {
+    if coverage <= 0.0 {
+        return 0.0;
+    }
+    if coverage >= 1.0 {
+        return std::f32::consts::FRAC_1_SQRT_2;
+    }
+    let area = |r: f32| {
+        let circle = std::f32::consts::PI * r * r;
+        if r <= 0.5 {
+            circle
+        } else {
+            // Четыре сегмента круга за сторонами клетки
+            let segment = r * r * (0.5 / r).acos() - 0.5 * (r * r - 0.25).sqrt();
+            circle - 4.0 * segment
+        }
+    };
+    let (mut low, mut high) = (0.0f32, std::f32::consts::FRAC_1_SQRT_2);
+    for _ in 0..32 {
+        let middle = (low + high) / 2.0;
+        if area(middle) < coverage {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    high
+}
+
+/// Растрирование: яркость (Rec. 601) усредняется по клеткам растра
+/// размером `cell`, повернутого на `angle` градусов, и в каждой клетке
+/// рисуется черная точка на белом фоне, площадь которой равна темноте
+/// клетки. Белые клетки остаются чистыми, черные закрываются полностью.
+/// Края точек сглаживаются подвыборкой 4×4 на пиксель.
+pub fn apply_halftone(image: &DynamicImage, cell: u32, angle: f32) -> DynamicImage {
+    const SUBSAMPLES: u32 = 4;
+    let gray = apply_grayscale(image, GrayMethod::Rec601).into_luma8();
+    let (width, height) = gray.dimensions();
+    let cell = cell.max(2) as f32;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    // Координаты растра в клетках
+    let to_screen = |x: f32, y: f32| ((x * cos + y * sin) / cell, (y * cos - x * sin) / cell);
+
+    let corners = [(0.0, 0.0), (width as f32, 0.0), (0.0, height as f32), (width as f32, height as f32)];
+    let (mut u_min, mut v_min, mut u_max, mut v_max) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for (x, y) in corners {
+        let (u, v) = to_screen(x, y);
+        u_min = u_min.min(u);
+        v_min = v_min.min(v);
+        u_max = u_max.max(u);
+        v_max = v_max.max(v);
+    }
+    let (u0, v0) = (u_min.floor() as i64, v_min.floor() as i64);
+    let columns = (u_max.floor() as i64 - u0 + 1) as usize;
+    let rows = (v_max.floor() as i64 - v0 + 1) as usize;
+    let cell_index = |u: f32, v: f32| {
+        let column = (u.floor() as i64 - u0).clamp(0, columns as i64 - 1) as usize;
+        let row = (v.floor() as i64 - v0).clamp(0, rows as i64 - 1) as usize;
+        row * columns + column
+    };
+
+    // Средняя яркость клеток по центрам пикселей
+    let mut sums = vec![(0u64, 0u64); columns * rows];
+    for (x, y, pixel) in gray.enumerate_pixels() {
+        let (u, v) = to_screen(x as f32 + 0.5, y as f32 + 0.5);
+        let entry = &mut sums[cell_index(u, v)];
+        entry.0 += pixel[0] as u64;
+        entry.1 += 1;
+    }
+    let radii: Vec<f32> = (0..=255).map(|level| halftone_radius(1.0 - level as f32 / 255.0)).collect();
+    // Клетки без центров пикселей (у краев повернутого растра) берут яркость пикселя
+    let cell_radius: Vec<Option<f32>> = sums
+        .iter()
+        .map(|&(sum, count)| (count > 0).then(|| radii[((sum + count / 2) / count) as usize]))
+        .collect();
+
+    let step = 1.0 / SUBSAMPLES as f32;
+    let result = GrayImage::from_fn(width, height, |x, y| {
+        let mut covered = 0u32;
+        for sy in 0..SUBSAMPLES {
+            for sx in 0..SUBSAMPLES {
+                let (u, v) = to_screen(x as f32 + (sx as f32 + 0.5) * step, y as f32 + (sy as f32 + 0.5) * step);
+                let radius = cell_radius[cell_index(u, v)].unwrap_or(radii[gray.get_pixel(x, y)[0] as usize]);
+                let du = u - u.floor() - 0.5;
+                let dv = v - v.floor() - 0.5;
+                if du * du + dv * dv < radius * radius {
+                    covered += 1;
+                }
+            }
+        }
+        let total = SUBSAMPLES * SUBSAMPLES;
+        Luma([(255 - (255 * covered + total / 2) / total) as u8])
+    });
+    DynamicImage::ImageLuma8(result)
+}
+
+/// Наложение цвета `src` на `dst` с непрозрачностью `alpha` (0..=1)
+pub fn blend_over(dst: [u8; 3], src: [u8; 3], alpha: f32) -> [u8; 3] {
+    let alpha = alpha.clamp(0.0, 1.0);
+    std::array::from_fn(|c| (dst[c] as f32 + (src[c] as f32 - dst[c] as f32) * alpha).round() as u8)
+}
+
+/// Место надписи на изображении
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl WatermarkPosition {
+    pub const ALL: [WatermarkPosition; 5] = [
+        WatermarkPosition::TopLeft,
+        WatermarkPosition::TopRight,
+        WatermarkPosition::BottomLeft,
+        WatermarkPosition::BottomRight,
+        WatermarkPosition::Center,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WatermarkPosition::TopLeft => "Слева сверху",
+            WatermarkPosition::TopRight => "Справа сверху",
+            WatermarkPosition::BottomLeft => "Слева снизу",
+            WatermarkPosition::BottomRight => "Справа снизу",
+            WatermarkPosition::Center => "По центру",
+        }
+    }
+}
+
+/// Параметры надписи
+#[derive(Clone, PartialEq, Debug)]
+pub struct Watermark {
+    pub text: String,
+    /// Высота букв в пикселях
+    pub size: f32,
+    pub opacity: f32,
+    pub color: [u8; 3],
+    pub position: WatermarkPosition,
+}
+
+/// Надпись встроенным шрифтом 5×7, масштабированным до высоты `size`.
+/// Края букв сглаживаются подвыборкой 4×4. Надпись, не помещающаяся в
+/// изображение, уменьшается, а ее положение зажимается в границы кадра.
+pub fn apply_watermark(image: &DynamicImage, watermark: &Watermark) -> DynamicImage {
+    const SUBSAMPLES: u32 = 4;
+    let glyphs: Vec<[u8; 7]> = watermark.text.chars().map(bitmap_font::glyph).collect();
+    if glyphs.is_empty() || watermark.opacity <= 0.0 || watermark.size <= 0.0 {
+        return image.clone();
+    }
+    let mut img = image.to_rgb8();
+    let (width, height) = img.dimensions();
+
+    // Размер надписи в точках шрифта и масштаб - пикселей на точку
+    let text_width = glyphs.len() as u32 * bitmap_font::ADVANCE - 1;
+    let text_height = bitmap_font::GLYPH_HEIGHT;
+    let scale = (watermark.size / text_height as f32)
+        .min(width as f32 / text_width as f32)
+        .min(height as f32 / text_height as f32);
+    let box_width = ((text_width as f32 * scale).ceil() as u32).min(width);
+    let box_height = ((text_height as f32 * scale).ceil() as u32).min(height);
+
+    // Отступ от края - две точки шрифта
+    let margin = (2.0 * scale).round() as u32;
+    let right = width - box_width;
+    let bottom = height - box_height;
+    let (x0, y0) = match watermark.position {
+        WatermarkPosition::TopLeft => (margin, margin),
+        WatermarkPosition::TopRight => (right.saturating_sub(margin), margin),
+        WatermarkPosition::BottomLeft => (margin, bottom.saturating_sub(margin)),
+        WatermarkPosition::BottomRight => (right.saturating_sub(margin), bottom.saturating_sub(margin)),
+        WatermarkPosition::Center => (right / 2, bottom / 2),
+    };
+    let (x0, y0) = (x0.min(right), y0.min(bottom));
+
+    let covered = |fx: f32, fy: f32| {
+        let (fx, fy) = (fx as u32, fy as u32);
+        glyphs
+            .get((fx / bitmap_font::ADVANCE) as usize)
+            .is_some_and(|rows| bitmap_font::is_set(rows, fx % bitmap_font::ADVANCE, fy))
+    };
+    let step = 1.0 / SUBSAMPLES as f32;
+    for y in 0..box_height {
+        for x in 0..box_width {
+            let mut hits = 0;
+            for sy in 0..SUBSAMPLES {
+                for sx in 0..SUBSAMPLES {
+                    let fx = (x as f32 + (sx as f32 + 0.5) * step) / scale;
+                    let fy = (y as f32 + (sy as f32 + 0.5) * step) / scale;
+                    if covered(fx, fy) {
+                        hits += 1;
+                    }
+                }
+            }
+            if hits > 0 {
+                let alpha = watermark.opacity * hits as f32 / (SUBSAMPLES * SUBSAMPLES) as f32;
+                let pixel = img.get_pixel_mut(x0 + x, y0 + y);
+                pixel.0 = blend_over(pixel.0, watermark.color, alpha);
+            }
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Заполнение полей за границей изображения
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BorderMode {
+    /// Заливка цветом
+    Constant([u8; 3]),
+    /// Повторение крайних пикселей
+    Replicate,
+    /// Зеркальное отражение с повтором крайнего пикселя (cba|abc|cba);
+    /// поля шире изображения отражаются многократно
+    Mirror,
+}
+
+impl BorderMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            BorderMode::Constant(_) => "Цвет",
+            BorderMode::Replicate => "Повтор края",
+            BorderMode::Mirror => "Зеркало",
+        }
+    }
+
+    /// Индекс исходного пикселя для координаты `i` на оси длиной `n`;
+    /// None - пиксель поля заливается цветом
+    pub fn source_index(self, i: i64, n: u32) -> Option<u32> {
+        let n = n as i64;
+        if (0..n).contains(&i) {
+            return Some(i as u32);
+        }
+        match self {
+            BorderMode::Constant(_) => None,
+            BorderMode::Replicate => Some(i.clamp(0, n - 1) as u32),
+            BorderMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                Some(if m < n { m } else { 2 * n - 1 - m } as u32)
+            }
+        }
+    }
+}
+
+fn pad_buffer<P: Pixel>(
+    source: &ImageBuffer<P, Vec<P::Subpixel>>,
+    (left, right, top, bottom): (u32, u32, u32, u32),
+    mode: BorderMode,
+    fill: P,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = source.dimensions();
+    ImageBuffer::from_fn(width + left + right, height + top + bottom, |x, y| {
+        let sx = mode.source_index(x as i64 - left as i64, width);
+        let sy = mode.source_index(y as i64 - top as i64, height);
+        match (sx, sy) {
+            (Some(sx), Some(sy)) => *source.get_pixel(sx, sy),
+            _ => fill,
+        }
+    })
+}
+
+/// Расширение холста полями заданной ширины с каждой стороны. Полутоновое
+/// изображение остается полутоновым (цвет заливки переводится в яркость),
+/// остальные приводятся к RGB. Без полей возвращается исходное изображение.
+pub fn apply_border(
+    image: &DynamicImage,
+    left: u32,
+    right: u32,
+    top: u32,
+    bottom: u32,
+    mode: BorderMode,
+) -> DynamicImage {
+    if left == 0 && right == 0 && top == 0 && bottom == 0 {
+        return image.clone();
+    }
+    let color = match mode {
+        BorderMode::Constant(color) => color,
+        _ => [0; 3],
+    };
+    // У пустого изображения нет пикселей для повтора - только заливка
+    let (width, height) = image.dimensions();
+    let mode = if width == 0 || height == 0 { BorderMode::Constant(color) } else { mode };
+    let sides = (left, right, top, bottom);
+    match image {
+        DynamicImage::ImageLuma8(gray) => {
+            DynamicImage::ImageLuma8(pad_buffer(gray, sides, mode, Luma([GrayMethod::Rec601.gray(color)])))
+        }
+        _ => DynamicImage::ImageRgb8(pad_buffer(&image.to_rgb8(), sides, mode, Rgb(color))),
+    }
+}
+
+/// Наибольшая сторона результата замощения
+pub const MAX_TILED_DIMENSION: u32 = 16384;
+
+fn tile_buffer<P: Pixel>(
+    source: &ImageBuffer<P, Vec<P::Subpixel>>,
+    columns: u32,
+    rows: u32,
+    mirror: bool,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = source.dimensions();
+    // В зеркальном режиме каждая нечетная плитка отражена, поэтому швы совпадают
+    let index = |i: u32, n: u32| if mirror && (i / n) % 2 == 1 { n - 1 - i % n } else { i % n };
+    ImageBuffer::from_fn(width * columns, height * rows, |x, y| *source.get_pixel(index(x, width), index(y, height)))
+}
+
+/// Замощение: изображение повторяется в сетке `columns`×`rows`, при `mirror`
+/// соседние плитки отражаются по горизонтали и вертикали. Результат
+/// больше 16384 пикселей по стороне не строится
+pub fn apply_tile(image: &DynamicImage, columns: u32, rows: u32, mirror: bool) -> Result<DynamicImage, String> {
+    let (columns, rows) = (columns.max(1), rows.max(1));
+    if columns == 1 && rows == 1 {
+        return Ok(image.clone());
+    }
+    let (width, height) = (image.width() as u64 * columns as u64, image.height() as u64 * rows as u64);
+    if width > MAX_TILED_DIMENSION as u64 || height > MAX_TILED_DIMENSION as u64 {
+        return Err(format!("результат {width}×{height} больше {MAX_TILED_DIMENSION}×{MAX_TILED_DIMENSION}"));
+    }
+    Ok(match image {
+        DynamicImage::ImageLuma8(gray) => DynamicImage::ImageLuma8(tile_buffer(gray, columns, rows, mirror)),
+        DynamicImage::ImageRgba8(rgba) => DynamicImage::ImageRgba8(tile_buffer(rgba, columns, rows, mirror)),
+        _ => DynamicImage::ImageRgb8(tile_buffer(&image.to_rgb8(), columns, rows, mirror)),
+    })
+}
+
+/// Интерполяция при геометрических преобразованиях
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 2] = [Interpolation::Nearest, Interpolation::Bilinear];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Interpolation::Nearest => "Ближайший",
+            Interpolation::Bilinear => "Билинейная",
+        }
+    }
+}
+
+/// Геометрическое преобразование обратным отображением: для каждого пикселя
+/// результата `width`×`height` функция `inverse` дает точку исходного
+/// изображения (координаты центров пикселей - `x + 0.5`). Отсчеты за краем
+/// исходного изображения берутся цветом `background`, поэтому край
+/// при билинейной интерполяции сглаживается.
+pub fn warp_inverse(
+    img: &RgbImage,
+    width: u32,
+    height: u32,
+    interpolation: Interpolation,
+    background: [u8; 3],
+    inverse: impl Fn(f32, f32) -> (f32, f32),
+) -> RgbImage {
+    let (src_width, src_height) = (img.width() as i64, img.height() as i64);
+    let texel = |x: i64, y: i64| -> [u8; 3] {
+        if (0..src_width).contains(&x) && (0..src_height).contains(&y) {
+            img.get_pixel(x as u32, y as u32).0
+        } else {
+            background
+        }
+    };
+    RgbImage::from_fn(width, height, |x, y| {
+        let (sx, sy) = inverse(x as f32 + 0.5, y as f32 + 0.5);
+        match interpolation {
+            Interpolation::Nearest => Rgb(texel(sx.floor() as i64, sy.floor() as i64)),
+            Interpolation::Bilinear => {
+                let (sx, sy) = (sx - 0.5, sy - 0.5);
+                let (x0, y0) = (sx.floor(), sy.floor());
+                let (fx, fy) = (sx - x0, sy - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let corners = [texel(x0, y0), texel(x0 + 1, y0), texel(x0, y0 + 1), texel(x0 + 1, y0 + 1)];
+                Rgb(std::array::from_fn(|c| {
+                    let top = corners[0][c] as f32 * (1.0 - fx) + corners[1][c] as f32 * fx;
+                    let bottom = corners[2][c] as f32 * (1.0 - fx) + corners[3][c] as f32 * fx;
+                    (top * (1.0 - fy) + bottom * fy).round() as u8
+                }))
+            }
+        }
+    })
+}
+
+/// Поворот на `degrees` градусов против часовой стрелки вокруг центра.
+/// При `expand` холст расширяется до описанного прямоугольника, иначе
+/// сохраняет размер и углы обрезаются; открывшиеся области заливаются
+/// `background`. Углы, кратные 90°, при совпадающем размере холста
+/// поворачиваются перестановкой пикселей без интерполяции.
+pub fn apply_rotate(
+    image: &DynamicImage,
+    degrees: f32,
+    interpolation: Interpolation,
+    expand: bool,
+    background: [u8; 3],
+) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let quarter_turns = degrees / 90.0;
+    if quarter_turns.fract() == 0.0 {
+        let turns = (quarter_turns as i32).rem_euclid(4);
+        match turns {
+            0 => return image.clone(),
+            2 => return image.rotate180(),
+            _ if expand || width == height => {
+                return if turns == 1 { image.rotate270() } else { image.rotate90() };
+            }
+            _ => {}
+        }
+    }
+
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (out_width, out_height) = if expand {
+        // Допуск убирает лишний пиксель из-за погрешности синуса и косинуса
+        let fit = |a: u32, b: u32| ((a as f32 * cos.abs() + b as f32 * sin.abs()) - 1e-3).ceil().max(1.0) as u32;
+        (fit(width, height), fit(height, width))
+    } else {
+        (width, height)
+    };
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let (out_cx, out_cy) = (out_width as f32 / 2.0, out_height as f32 / 2.0);
+    let rotated = warp_inverse(&image.to_rgb8(), out_width, out_height, interpolation, background, |x, y| {
+        let (dx, dy) = (x - out_cx, y - out_cy);
+        (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+    });
+    DynamicImage::ImageRgb8(rotated)
+}
+
+/// Проективное преобразование плоскости - матрица 3×3 по строкам, h33 = 1
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Homography(pub [f64; 9]);
+
+impl Homography {
+    /// Преобразование, переводящее четыре точки `from` в точки `to`:
+    /// система из восьми уравнений решается методом Гаусса с выбором
+    /// главного элемента. None - если система или сама матрица вырождена
+    /// (три точки на прямой)
+    pub fn from_points(from: [(f64, f64); 4], to: [(f64, f64); 4]) -> Option<Self> {
+        // Строки [a | b]: x' = (h1 x + h2 y + h3) / (h7 x + h8 y + 1), y' - аналогично
+        let mut system = [[0f64; 9]; 8];
+        for (i, (&(x, y), &(u, v))) in from.iter().zip(&to).enumerate() {
+            system[2 * i] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+            system[2 * i + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+        }
+        let scale = system.iter().flat_map(|row| row[..8].iter()).fold(0f64, |m, v| m.max(v.abs()));
+        for col in 0..8 {
+            let pivot = (col..8).max_by(|&a, &b| system[a][col].abs().total_cmp(&system[b][col].abs()))?;
+            if system[pivot][col].abs() <= 1e-10 * scale.max(1.0) {
+                return None;
+            }
+            system.swap(col, pivot);
+            let pivot_row = system[col];
+            for (row, equation) in system.iter_mut().enumerate() {
+                if row != col {
+                    let factor = equation[col] / pivot_row[col];
+                    for (value, &p) in equation[col..].iter_mut().zip(&pivot_row[col..]) {
+                        *value -= factor * p;
+                    }
+                }
+            }
+        }
+        let h: [f64; 8] = std::array::from_fn(|i| system[i][8] / system[i][i]);
+        let h = [h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0];
+        // Решение есть и при трех точках `to` на прямой, но матрица тогда вырождена
+        let det = h[0] * (h[4] * h[8] - h[5] * h[7]) - h[1] * (h[3] * h[8] - h[5] * h[6])
+            + h[2] * (h[3] * h[7] - h[4] * h[6]);
+        let norm = h.iter().map(|v| v * v).sum::<f64>().sqrt();
+        (det.abs() > 1e-10 * norm.powi(3)).then_some(Homography(h))
+    }
+
+    pub fn map(&self, x: f64, y: f64) -> (f64, f64) {
+        let h = &self.0;
+        let w = h[6] * x + h[7] * y + h[8];
+        ((h[0] * x + h[1] * y + h[2]) / w, (h[3] * x + h[4] * y + h[5]) / w)
+    }
+}
+
+/// Четырехугольник выпуклый и невырожденный: повороты на всех вершинах
+/// в одну сторону. Самопересекающийся или с тремя точками на прямой - нет
+pub fn is_convex_quad(quad: [(f64, f64); 4]) -> bool {
+    let turns: Vec<f64> = (0..4)
+        .map(|i| {
+            let (a, b, c) = (quad[i], quad[(i + 1) % 4], quad[(i + 2) % 4]);
+            (b.0 - a.0) * (c.1 - b.1) - (b.1 - a.1) * (c.0 - b.0)
+        })
+        .collect();
+    let edge = |i: usize| (quad[(i + 1) % 4].0 - quad[i].0).hypot(quad[(i + 1) % 4].1 - quad[i].1);
+    // Порог относительно сторон: почти коллинеарные вершины тоже отклоняются
+    let min_turn = (0..4).map(|i| edge(i) * edge((i + 1) % 4)).fold(f64::INFINITY, f64::min) * 1e-3;
+    turns.iter().all(|&t| t > min_turn) || turns.iter().all(|&t| t < -min_turn)
+}
+
+/// Исправление перспективы: четырехугольник `quad` (левый верхний, правый
+/// верхний, правый нижний, левый нижний углы в пикселях изображения)
+/// переводится в прямоугольник со сторонами по наибольшим противоположным
+/// сторонам четырехугольника
+pub fn apply_perspective(image: &DynamicImage, quad: [(f32, f32); 4]) -> Result<DynamicImage, String> {
+    let quad = quad.map(|(x, y)| (x as f64, y as f64));
+    if !is_convex_quad(quad) {
+        return Err("углы должны образовывать выпуклый четырехугольник без самопересечений".to_string());
+    }
+    let length = |a: (f64, f64), b: (f64, f64)| (b.0 - a.0).hypot(b.1 - a.1);
+    let [top_left, top_right, bottom_right, bottom_left] = quad;
+    let width = length(top_left, top_right).max(length(bottom_left, bottom_right)).round();
+    let height = length(top_left, bottom_left).max(length(top_right, bottom_right)).round();
+    let (width, height) = (width.clamp(1.0, MAX_DIMENSION as f64), height.clamp(1.0, MAX_DIMENSION as f64));
+
+    let rectangle = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)];
+    let inverse = Homography::from_points(rectangle, quad).ok_or("вырожденный четырехугольник")?;
+    let warped = warp_inverse(&image.to_rgb8(), width as u32, height as u32, Interpolation::Bilinear, [0; 3], |x, y| {
+        let (sx, sy) = inverse.map(x as f64, y as f64);
+        (sx as f32, sy as f32)
+    });
+    Ok(DynamicImage::ImageRgb8(warped))
+}
+
+/// Наибольшая сторона изображения после изменения размера
+pub const MAX_DIMENSION: u32 = 32768;
+
+/// Фильтр при изменении размера
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResizeFilter {
+    Nearest,
+    /// Усреднение по площади: уменьшение вдвое - среднее блоков 2×2
+    Box,
+    Bilinear,
+    CatmullRom,
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    pub const ALL: [ResizeFilter; 5] = [
+        ResizeFilter::Nearest,
+        ResizeFilter::Box,
+        ResizeFilter::Bilinear,
+        ResizeFilter::CatmullRom,
+        ResizeFilter::Lanczos3,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ResizeFilter::Nearest => "Ближайший",
+            ResizeFilter::Box => "По площади",
+            ResizeFilter::Bilinear => "Билинейный",
+            ResizeFilter::CatmullRom => "Бикубический (Катмулл-Ром)",
+            ResizeFilter::Lanczos3 => "Ланцош-3",
+        }
+    }
+}
+
+/// Веса усреднения по площади: для каждого выходного отсчета - исходные
+/// отсчеты, попавшие в его отрезок, с долями перекрытия
+fn area_weights(src: u32, dst: u32) -> Vec<Vec<(usize, f32)>> {
+    let scale = src as f64 / dst as f64;
+    (0..dst)
+        .map(|i| {
+            let (start, end) = (i as f64 * scale, (i + 1) as f64 * scale);
+            let first = start.floor() as u32;
+            let last = (end.ceil() as u32).min(src);
+            (first..last)
+                .map(|j| {
+                    let overlap = (end.min(j as f64 + 1.0) - start.max(j as f64)) / scale;
+                    (j as usize, overlap as f32)
+                })
+                .filter(|&(_, w)| w > 0.0)
+                .collect()
+        })
+        .collect()
+}
+
+/// Изменение размера усреднением по площади: сначала по строкам, затем по столбцам
+fn resize_area(img: &RgbImage, width: u32, height: u32) -> RgbImage {
+    let (src_width, src_height) = img.dimensions();
+    let data: &[u8] = img;
+    let columns = area_weights(src_width, width);
+    let rows = area_weights(src_height, height);
+
+    let mut horizontal = vec![0f32; width as usize * src_height as usize * 3];
+    for y in 0..src_height as usize {
+        for (x, weights) in columns.iter().enumerate() {
+            for c in 0..3 {
+                let row = y * src_width as usize;
+                let sum: f32 = weights.iter().map(|&(j, w)| data[(row + j) * 3 + c] as f32 * w).sum();
+                horizontal[(y * width as usize + x) * 3 + c] = sum;
+            }
+        }
+    }
+    RgbImage::from_fn(width, height, |x, y| {
+        Rgb(std::array::from_fn(|c| {
+            let sum: f32 = rows[y as usize]
+                .iter()
+                .map(|&(j, w)| horizontal[(j * width as usize + x as usize) * 3 + c] * w)
+                .sum();
+            sum.round().clamp(0.0, 255.0) as u8
+        }))
+    })
+}
+
+/// Изменение размера до `width`×`height`; размеры вне 1..=32768 отклоняются
+pub fn apply_resize(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: ResizeFilter,
+) -> Result<DynamicImage, String> {
+    let valid = 1..=MAX_DIMENSION;
+    if !valid.contains(&width) || !valid.contains(&height) {
+        return Err(format!("размеры должны быть от 1 до {MAX_DIMENSION} пикселей, задано {width}×{height}"));
+    }
+    let filter_type = match filter {
+        ResizeFilter::Nearest => image::imageops::FilterType::Nearest,
+        ResizeFilter::Bilinear => image::imageops::FilterType::Triangle,
+        ResizeFilter::CatmullRom => image::imageops::FilterType::CatmullRom,
+        ResizeFilter::Lanczos3 => image::imageops::FilterType::Lanczos3,
+        ResizeFilter::Box => {
+            let resized = resize_area(&image.to_rgb8(), width, height);
+            return Ok(match image {
+                DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(DynamicImage::ImageRgb8(resized).to_luma8()),
+                _ => DynamicImage::ImageRgb8(resized),
+            });
+        }
+    };
+    Ok(image.resize_exact(width, height, filter_type))
+}
+
+/// Виньетирование: затемнение канала V по мере удаления от центра, при
+/// отрицательной `strength` - осветление к белому. Расстояние эллиптическое:
+/// полуоси равны половинам сторон кадра, в углах расстояние равно 1, поэтому
+/// виньетка повторяет форму кадра. Эффект плавно нарастает от `radius`
+/// до `radius + feather`.
+pub fn apply_vignette(image: &DynamicImage, strength: f32, radius: f32, feather: f32) -> DynamicImage {
+    if strength == 0.0 {
+        return image.clone();
+    }
+    let strength = strength.clamp(-1.0, 1.0);
+
+    let mut img = image.to_rgb8();
+    let (width, height) = img.dimensions();
+    let cx = (width as f32 - 1.0) / 2.0;
+    let cy = (height as f32 - 1.0) / 2.0;
+
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let dx = (x as f32 - cx) / cx.max(0.5);
+        let dy = (y as f32 - cy) / cy.max(0.5);
+        let distance = ((dx * dx + dy * dy) / 2.0).sqrt();
+        let weight = strength * smoothstep(radius, radius + feather, distance);
+
+        if weight > 0.0 {
+            let (h, s, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+            let (r, g, b) = hsv_to_rgb(h, s, (v * (1.0 - weight)).clamp(0.0, 1.0));
+            pixel.0 = [r, g, b];
+        } else if weight < 0.0 {
+            // Белая виньетка: смешивание с белым в доле |weight|
+            pixel.0 = pixel.0.map(|c| (c as f32 + (255.0 - c as f32) * -weight).round() as u8);
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Палитры псевдоцвета для отображения полутоновых результатов
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Colormap {
+    Viridis,
+    Inferno,
+    Jet,
+    Turbo,
+    Heat,
+    Grayscale,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 6] =
+        [Colormap::Viridis, Colormap::Inferno, Colormap::Jet, Colormap::Turbo, Colormap::Heat, Colormap::Grayscale];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Viridis => "Viridis",
+            Colormap::Inferno => "Inferno",
+            Colormap::Jet => "Jet",
+            Colormap::Turbo => "Turbo",
+            Colormap::Heat => "Тепловая",
+            Colormap::Grayscale => "Оттенки серого",
+        }
+    }
+
+    /// Опорные цвета, равномерно распределенные по диапазону 0..=255
+    pub fn control_points(self) -> &'static [[u8; 3]] {
+        match self {
+            Colormap::Viridis => &[
+                [68, 1, 84], [71, 45, 123], [59, 82, 139], [44, 114, 142], [33, 145, 140],
+                [40, 174, 128], [94, 201, 98], [173, 220, 48], [253, 231, 37],
+            ],
+            Colormap::Inferno => &[
+                [0, 0, 4], [31, 12, 72], [85, 15, 109], [136, 34, 106], [186, 54, 85],
+                [227, 89, 51], [249, 142, 9], [249, 203, 53], [252, 255, 164],
+            ],
+            Colormap::Jet => &[
+                [0, 0, 128], [0, 0, 255], [0, 128, 255], [0, 255, 255], [128, 255, 128],
+                [255, 255, 0], [255, 128, 0], [255, 0, 0], [128, 0, 0],
+            ],
+            Colormap::Turbo => &[
+                [48, 18, 59], [68, 84, 196], [68, 144, 254], [31, 200, 222], [41, 239, 162], [125, 255, 86],
+                [193, 243, 52], [241, 202, 58], [254, 146, 42], [234, 79, 13], [122, 4, 3],
+            ],
+            Colormap::Heat => &[[0, 0, 0], [255, 0, 0], [255, 255, 0], [255, 255, 255]],
+            Colormap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+        }
+    }
+
+    /// Таблица 256×3, построенная линейной интерполяцией опорных цветов
+    pub fn table(self) -> [[u8; 3]; 256] {
+        let points = self.control_points();
+        let segments = (points.len() - 1) as f32;
+        let mut table = [[0u8; 3]; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            let t = i as f32 / 255.0 * segments;
+            let k = (t.floor() as usize).min(points.len() - 2);
+            let frac = t - k as f32;
+            for c in 0..3 {
+                let a = points[k][c] as f32;
+                let b = points[k + 1][c] as f32;
+                entry[c] = (a + (b - a) * frac).round() as u8;
+            }
+        }
+        table
+    }
+}
+
+/// Псевдоцвет: яркость каждого пикселя заменяется цветом из палитры
+pub fn apply_colormap(image: &DynamicImage, map: Colormap) -> DynamicImage {
+    let gray = image.to_luma8();
+    let table = map.table();
+    let mut result = RgbImage::new(gray.width(), gray.height());
+    for (dst, src) in result.pixels_mut().zip(gray.pixels()) {
+        dst.0 = table[src[0] as usize];
+    }
+    DynamicImage::ImageRgb8(result)
+}
+
+/// Способ перевода цвета в оттенки серого
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GrayMethod {
+    /// Яркость по весам Rec. 601: 0.299 R + 0.587 G + 0.114 B
+    Rec601,
+    /// Яркость по весам Rec. 709: 0.2126 R + 0.7152 G + 0.0722 B
+    Rec709,
+    /// Среднее (R + G + B) / 3
+    Average,
+    /// Светлота (max + min) / 2
+    Lightness,
+    Red,
+    Green,
+    Blue,
+}
+
+impl GrayMethod {
+    pub const ALL: [GrayMethod; 7] = [
+        GrayMethod::Rec601,
+        GrayMethod::Rec709,
+        GrayMethod::Average,
+        GrayMethod::Lightness,
+        GrayMethod::Red,
+        GrayMethod::Green,
+        GrayMethod::Blue,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GrayMethod::Rec601 => "Яркость (Rec. 601)",
+            GrayMethod::Rec709 => "Яркость (Rec. 709)",
+            GrayMethod::Average => "Среднее RGB",
+            GrayMethod::Lightness => "Светлота",
+            GrayMethod::Red => "Канал R",
+            GrayMethod::Green => "Канал G",
+            GrayMethod::Blue => "Канал B",
+        }
+    }
+
+    fn gray(self, [r, g, b]: [u8; 3]) -> u8 {
+        let weighted = |wr: f32, wg: f32, wb: f32| (wr * r as f32 + wg * g as f32 + wb * b as f32).round() as u8;
+        match self {
+            GrayMethod::Rec601 => weighted(0.299, 0.587, 0.114),
+            GrayMethod::Rec709 => weighted(0.2126, 0.7152, 0.0722),
+            GrayMethod::Average => ((r as u16 + g as u16 + b as u16 + 1) / 3) as u8,
+            GrayMethod::Lightness => (r.max(g).max(b) as u16 + r.min(g).min(b) as u16).div_ceil(2) as u8,
+            GrayMethod::Red => r,
+            GrayMethod::Green => g,
+            GrayMethod::Blue => b,
+        }
+    }
+}
+
+/// Перевод в оттенки серого выбранным способом, результат - Luma8
+pub fn apply_grayscale(image: &DynamicImage, method: GrayMethod) -> DynamicImage {
+    let rgb_image = image.to_rgb8();
+    let (width, height) = rgb_image.dimensions();
+    let data = rgb_image.pixels().map(|p| method.gray(p.0)).collect();
+    DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, data).unwrap())
+}
+
+/// Канал изображения для просмотра
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChannelPlane {
+    Red,
+    Green,
+    Blue,
+    Hue,
+    Saturation,
+    Value,
+}
+
+impl ChannelPlane {
+    pub const ALL: [ChannelPlane; 6] = [
+        ChannelPlane::Red,
+        ChannelPlane::Green,
+        ChannelPlane::Blue,
+        ChannelPlane::Hue,
+        ChannelPlane::Saturation,
+        ChannelPlane::Value,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChannelPlane::Red => "R",
+            ChannelPlane::Green => "G",
+            ChannelPlane::Blue => "B",
+            ChannelPlane::Hue => "H",
+            ChannelPlane::Saturation => "S",
+            ChannelPlane::Value => "V",
+        }
+    }
+}
+
+/// Выделяет канал как изображение в оттенках серого: H масштабируется
+/// из 0..360, S и V из 0..1 в 0..255; у серых пикселей тон не определен
+/// и выводится как 0. При `tinted` каналы R, G и B окрашиваются в свой
+/// цвет (Rgb8), иначе результат - Luma8.
+pub fn apply_channel_view(image: &DynamicImage, plane: ChannelPlane, tinted: bool) -> DynamicImage {
+    let rgb_image = image.to_rgb8();
+    let (width, height) = rgb_image.dimensions();
+    let index = match plane {
+        ChannelPlane::Red => Some(0),
+        ChannelPlane::Green => Some(1),
+        ChannelPlane::Blue => Some(2),
+        _ => None,
+    };
+
+    if let Some(c) = index
+        && tinted
+    {
+        let mut tinted_image = RgbImage::new(width, height);
+        for (out, pixel) in tinted_image.pixels_mut().zip(rgb_image.pixels()) {
+            out[c] = pixel[c];
+        }
+        return DynamicImage::ImageRgb8(tinted_image);
+    }
+
+    let data = rgb_image
+        .pixels()
+        .map(|p| match index {
+            Some(c) => p[c],
+            None => {
+                let (h, s, v) = rgb_to_hsv(p[0], p[1], p[2]);
+                let value = match plane {
+                    ChannelPlane::Hue if s > 0.0 => h / 360.0,
+                    ChannelPlane::Hue => 0.0,
+                    ChannelPlane::Saturation => s,
+                    _ => v,
+                };
+                (value * 255.0).round().clamp(0.0, 255.0) as u8
+            }
+        })
+        .collect();
+    DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, data).unwrap())
+}
+
+/// Источник значения для канала при перестановке каналов
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChannelSource {
+    Red,
+    Green,
+    Blue,
+    Zero,
+}
+
+impl ChannelSource {
+    pub const ALL: [ChannelSource; 4] =
+        [ChannelSource::Red, ChannelSource::Green, ChannelSource::Blue, ChannelSource::Zero];
+    pub const IDENTITY: [ChannelSource; 3] = [ChannelSource::Red, ChannelSource::Green, ChannelSource::Blue];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChannelSource::Red => "R",
+            ChannelSource::Green => "G",
+            ChannelSource::Blue => "B",
+            ChannelSource::Zero => "0",
+        }
+    }
+}
+
+/// Перестановка каналов: в выходной R, G и B записываются каналы из `map`
+pub fn apply_channel_map(image: &DynamicImage, map: [ChannelSource; 3]) -> DynamicImage {
+    if map == ChannelSource::IDENTITY {
+        return image.clone();
+    }
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        let source = pixel.0;
+        pixel.0 = map.map(|channel| match channel {
+            ChannelSource::Red => source[0],
+            ChannelSource::Green => source[1],
+            ChannelSource::Blue => source[2],
+            ChannelSource::Zero => 0,
+        });
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Наибольший множитель канала при балансе белого
+const MAX_WB_GAIN: f32 = 4.0;
+
+/// Баланс белого по модели "серого мира": каждый канал умножается так,
+/// чтобы средние R, G и B сравнялись с их общим средним. Множители
+/// ограничены `MAX_WB_GAIN`; канал с нулевым средним не меняется.
+/// Возвращает изображение и множители каналов.
+pub fn apply_gray_world_wb(image: &DynamicImage) -> (DynamicImage, [f32; 3]) {
+    let mut img = image.to_rgb8();
+    let count = (img.width() as u64 * img.height() as u64).max(1) as f64;
+    let mut sums = [0u64; 3];
+    for pixel in img.pixels() {
+        for (sum, &v) in sums.iter_mut().zip(&pixel.0) {
+            *sum += v as u64;
+        }
+    }
+    let means = sums.map(|s| (s as f64 / count) as f32);
+    let average = means.iter().sum::<f32>() / 3.0;
+    let gains = means.map(|m| if m > 0.0 { (average / m).min(MAX_WB_GAIN) } else { 1.0 });
+
+    let luts = gains.map(|g| lut_from_fn(|v| v * g));
+    apply_channel_luts(&mut img, &luts);
+    (DynamicImage::ImageRgb8(img), gains)
+}
+
+/// Отдельная таблица для каждого из каналов R, G и B
+fn apply_channel_luts(img: &mut RgbImage, luts: &[Lut; 3]) {
+    for pixel in img.pixels_mut() {
+        for (v, lut) in pixel.0.iter_mut().zip(luts) {
+            *v = lut[*v as usize];
+        }
+    }
+}
+
+/// Цветовая температура и оттенок: множители каналов в линейном свете.
+/// `temperature` (−100..100) - от синего к янтарному: R·2^(t/200), B·2^(−t/200);
+/// `tint` (−100..100) - от зеленого к пурпурному: G·2^(−tint/200).
+/// Результат зажимается к 0..255.
+pub fn apply_temperature_tint(image: &DynamicImage, temperature: f32, tint: f32) -> DynamicImage {
+    if temperature == 0.0 && tint == 0.0 {
+        return image.clone();
+    }
+    let gains = [(temperature / 200.0).exp2(), (-tint / 200.0).exp2(), (-temperature / 200.0).exp2()];
+    let luts = gains.map(|gain| {
+        let mut lut = [0u8; 256];
+        for (i, entry) in lut.iter_mut().enumerate() {
+            *entry = linear_to_srgb(srgb_to_linear(i as u8) * gain);
+        }
+        lut
+    });
+    let mut img = image.to_rgb8();
+    apply_channel_luts(&mut img, &luts);
+    DynamicImage::ImageRgb8(img)
+}
+
+pub fn apply_inversion(image: &DynamicImage) -> DynamicImage {
+    let mut img = image.to_rgb8();
+    apply_lut_rgb(&mut img, &invert_lut());
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Инверсия яркости без смены цвета: V в HSV заменяется на 1 - V, тон и
+/// насыщенность сохраняются (голубое небо остается синим, а не оранжевым).
+/// Для ярких цветов преобразование необратимо: все цвета с V = 1 уходят в
+/// черный, а светлые сжимаются в немногие темные уровни.
+pub fn apply_value_inversion(image: &DynamicImage) -> DynamicImage {
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        let (h, s, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        let (r, g, b) = hsv_to_rgb(h, s, 1.0 - v);
+        pixel[0] = r;
+        pixel[1] = g;
+        pixel[2] = b;
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+const SEPIA_MATRIX: [[f32; 3]; 3] = [[0.393, 0.769, 0.189], [0.349, 0.686, 0.168], [0.272, 0.534, 0.131]];
+
+/// Сепия: классическая матрица, результат смешивается с исходным цветом
+/// в доле `intensity` (0 - без изменений, 1 - чистая сепия)
+pub fn apply_sepia(image: &DynamicImage, intensity: f32) -> DynamicImage {
+    if intensity <= 0.0 {
+        return image.clone();
+    }
+    let intensity = intensity.min(1.0);
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        let [r, g, b] = pixel.0.map(|v| v as f32);
+        for (value, row) in pixel.0.iter_mut().zip(SEPIA_MATRIX) {
+            let sepia = (row[0] * r + row[1] * g + row[2] * b).min(255.0);
+            *value = (*value as f32 + intensity * (sepia - *value as f32)).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Соляризация (эффект Сабатье): значения выше порога инвертируются.
+/// Порог 0 инвертирует все изображение, порог 255 ничего не меняет.
+fn solarize_lut(threshold: u8) -> Lut {
+    let inverted = invert_lut();
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = if threshold == 0 || i as u8 > threshold { inverted[i] } else { i as u8 };
+    }
+    lut
+}
+
+pub fn apply_solarize(image: &DynamicImage, threshold: u8) -> DynamicImage {
+    let mut img = image.to_rgb8();
+    apply_lut_rgb(&mut img, &solarize_lut(threshold));
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Квантование до `levels` равноотстоящих значений 0..=255; два уровня -
+/// порог по 128, с ростом числа уровней таблица стремится к тождественной
+fn posterize_lut(levels: u8) -> Lut {
+    let step = 255.0 / (levels.max(2) - 1) as f32;
+    lut_from_fn(|v| (v / step).round() * step)
+}
+
+pub fn apply_posterize(image: &DynamicImage, levels: u8, target: LutTarget) -> DynamicImage {
+    apply_lut(image, &posterize_lut(levels), target)
+}
+
+/// Квантование цветов медианным сечением: палитра из `colors` (2..=256)
+/// цветов, каждый пиксель заменяется ближайшим цветом палитры. Если
+/// различных цветов не больше `colors`, изображение возвращается без
+/// изменений, а палитрой служат сами эти цвета. Палитра упорядочена по яркости,
+/// у каждого цвета - доля покрытых им пикселей.
+pub fn apply_quantize(image: &DynamicImage, colors: u16) -> (DynamicImage, Vec<([u8; 3], f32)>) {
+    let colors = colors.clamp(2, 256) as usize;
+    let mut img = image.to_rgb8();
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for pixel in img.pixels() {
+        *counts.entry(pixel.0).or_insert(0) += 1;
+    }
+    let mut entries: Vec<([u8; 3], u32)> = counts.into_iter().collect();
+
+    let by_brightness = |color: &[u8; 3]| 299 * color[0] as u32 + 587 * color[1] as u32 + 114 * color[2] as u32;
+    let total = img.pixels().len() as f32;
+    if entries.len() <= colors {
+        entries.sort_unstable_by_key(|(color, _)| by_brightness(color));
+        let palette = entries.into_iter().map(|(color, count)| (color, count as f32 / total)).collect();
+        return (image.clone(), palette);
+    }
+
+    let mut palette = median_cut(&mut entries, colors);
+    palette.sort_unstable_by_key(by_brightness);
+    palette.dedup();
+
+    // Ближайший цвет ищется один раз для каждого различного цвета изображения
+    let search = NearestColor::new(&palette);
+    let mapping: HashMap<[u8; 3], [u8; 3]> =
+        entries.iter().map(|&(color, _)| (color, search.find(color))).collect();
+    for pixel in img.pixels_mut() {
+        pixel.0 = mapping[&pixel.0];
+    }
+    let mut coverage: HashMap<[u8; 3], u32> = HashMap::new();
+    for (color, count) in &entries {
+        *coverage.entry(mapping[color]).or_insert(0) += count;
+    }
+    let palette = palette.into_iter().map(|color| (color, coverage[&color] as f32 / total)).collect();
+    (DynamicImage::ImageRgb8(img), palette)
+}
+
+/// Сколько пикселей не больше участвует в поиске доминирующих цветов
+const DOMINANT_SAMPLE_LIMIT: usize = 100_000;
+
+/// Доминирующие цвета изображения методом k-средних. Выборка - не более
+/// 100 тыс. пикселей с постоянным шагом, поэтому результат для одного и того же
+/// изображения не меняется; начальные центры дает медианное сечение выборки.
+/// Возвращает цвета с долей покрытия по убыванию доли.
+pub fn dominant_colors(image: &DynamicImage, count: usize) -> Vec<([u8; 3], f32)> {
+    let img = image.to_rgb8();
+    let data: &[u8] = &img;
+    let total = data.len() / 3;
+    if total == 0 {
+        return Vec::new();
+    }
+    let step = total.div_ceil(DOMINANT_SAMPLE_LIMIT);
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for i in (0..total).step_by(step) {
+        *counts.entry([data[3 * i], data[3 * i + 1], data[3 * i + 2]]).or_insert(0) += 1;
+    }
+    // Порядок обхода HashMap случаен - сортируем, чтобы сечение было повторяемым
+    let mut entries: Vec<([u8; 3], u32)> = counts.into_iter().collect();
+    entries.sort_unstable();
+    let samples: u32 = entries.iter().map(|&(_, n)| n).sum();
+
+    let mut centers: Vec<[f32; 3]> =
+        median_cut(&mut entries, count.max(1)).into_iter().map(|c| c.map(|v| v as f32)).collect();
+    let nearest = |centers: &[[f32; 3]], color: [u8; 3]| {
+        let distance = |center: &[f32; 3]| (0..3).map(|c| (center[c] - color[c] as f32).powi(2)).sum::<f32>();
+        (0..centers.len()).min_by(|&a, &b| distance(&centers[a]).total_cmp(&distance(&centers[b]))).unwrap_or(0)
+    };
+    let mut weights = vec![0u32; centers.len()];
+    for _ in 0..20 {
+        let mut sums = vec![[0f64; 3]; centers.len()];
+        weights.iter_mut().for_each(|w| *w = 0);
+        for &(color, n) in &entries {
+            let k = nearest(&centers, color);
+            for c in 0..3 {
+                sums[k][c] += color[c] as f64 * n as f64;
+            }
+            weights[k] += n;
+        }
+        let mut shift = 0f32;
+        for (k, center) in centers.iter_mut().enumerate() {
+            if weights[k] == 0 {
+                continue;
+            }
+            let updated = sums[k].map(|s| (s / weights[k] as f64) as f32);
+            shift = (0..3).map(|c| (updated[c] - center[c]).abs()).fold(shift, f32::max);
+            *center = updated;
+        }
+        if shift < 0.5 {
+            break;
+        }
+    }
+
+    let mut result: Vec<([u8; 3], f32)> = centers
+        .iter()
+        .zip(&weights)
+        .filter(|&(_, &w)| w > 0)
+        .map(|(center, &w)| (center.map(|v| v.round() as u8), w as f32 / samples as f32))
+        .collect();
+    result.sort_by(|a, b| b.1.total_cmp(&a.1));
+    result
+}
+
+/// Ящик медианного сечения: диапазон в списке цветов и его наибольший размах
+struct ColorBox {
+    range: std::ops::Range<usize>,
+    channel: usize,
+    span: u8,
+}
+
+impl ColorBox {
+    fn new(entries: &[([u8; 3], u32)], range: std::ops::Range<usize>) -> Self {
+        let mut min = [u8::MAX; 3];
+        let mut max = [0u8; 3];
+        for (color, _) in &entries[range.clone()] {
+            for c in 0..3 {
+                min[c] = min[c].min(color[c]);
+                max[c] = max[c].max(color[c]);
+            }
+        }
+        let channel = (0..3).max_by_key(|&c| max[c] - min[c]).unwrap_or(0);
+        Self { span: max[channel] - min[channel], channel, range }
+    }
+}
+
+/// Делит множество различных цветов (с числом пикселей) на `colors` ящиков,
+/// каждый раз разрезая ящик с наибольшим размахом по взвешенной медиане
+/// его самого широкого канала; цвет ящика - взвешенное среднее
+fn median_cut(entries: &mut [([u8; 3], u32)], colors: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![ColorBox::new(entries, 0..entries.len())];
+    while boxes.len() < colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.span > 0)
+            .max_by_key(|(_, b)| b.span)
+            .map(|(index, _)| index);
+        let Some(index) = widest else {
+            break;
+        };
+        let ColorBox { range, channel, .. } = boxes.swap_remove(index);
+        let slice = &mut entries[range.clone()];
+        slice.sort_unstable_by_key(|(color, _)| color[channel]);
+
+        let total: u64 = slice.iter().map(|&(_, count)| count as u64).sum();
+        let mut accumulated = 0u64;
+        let mut split = 1;
+        for (i, &(_, count)) in slice.iter().enumerate() {
+            accumulated += count as u64;
+            if accumulated * 2 >= total {
+                split = i + 1;
+                break;
+            }
+        }
+        let split = range.start + split.clamp(1, slice.len() - 1);
+        boxes.push(ColorBox::new(entries, range.start..split));
+        boxes.push(ColorBox::new(entries, split..range.end));
+    }
+
+    boxes
+        .iter()
+        .map(|b| {
+            let mut sum = [0u64; 3];
+            let mut total = 0u64;
+            for &(color, count) in &entries[b.range.clone()] {
+                for c in 0..3 {
+                    sum[c] += color[c] as u64 * count as u64;
+                }
+                total += count as u64;
+            }
+            sum.map(|s| ((s + total / 2) / total) as u8)
+        })
+        .collect()
+}
+
+/// Поиск ближайшего цвета палитры: цвета отсортированы по зеленому каналу,
+/// просмотр идет от ближайшего по G в обе стороны и прекращается, когда
+/// разность одного G уже не меньше лучшего найденного расстояния
+struct NearestColor {
+    palette: Vec<[u8; 3]>,
+}
+
+impl NearestColor {
+    fn new(palette: &[[u8; 3]]) -> Self {
+        let mut palette = palette.to_vec();
+        palette.sort_unstable_by_key(|color| color[1]);
+        Self { palette }
+    }
+
+    fn find(&self, color: [u8; 3]) -> [u8; 3] {
+        let distance = |other: &[u8; 3]| -> u32 {
+            (0..3).map(|c| (color[c].abs_diff(other[c]) as u32).pow(2)).sum()
+        };
+        let start = self.palette.partition_point(|other| other[1] < color[1]);
+        let mut best = self.palette[start.min(self.palette.len() - 1)];
+        let mut best_distance = distance(&best);
+        let (mut up, mut down) = (start, start);
+        loop {
+            let mut advanced = false;
+            if let Some(other) = self.palette.get(up)
+                && (other[1].abs_diff(color[1]) as u32).pow(2) < best_distance
+            {
+                let d = distance(other);
+                if d < best_distance {
+                    best = *other;
+                    best_distance = d;
+                }
+                up += 1;
+                advanced = true;
+            }
+            if let Some(other) = down.checked_sub(1).and_then(|i| self.palette.get(i))
+                && (other[1].abs_diff(color[1]) as u32).pow(2) < best_distance
+            {
+                let d = distance(other);
+                if d < best_distance {
+                    best = *other;
+                    best_distance = d;
+                }
+                down -= 1;
+                advanced = true;
+            }
+            if !advanced {
+                return best;
+            }
+        }
+    }
+}
+
+/// Экспозиция: умножение на 2^ev в линейном свете с обратным кодированием в sRGB
+pub fn apply_exposure(image: &DynamicImage, ev: f32) -> DynamicImage {
+    if ev == 0.0 || !ev.is_finite() {
+        return image.clone();
+    }
+    let gain = ev.exp2();
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = linear_to_srgb(srgb_to_linear(i as u8) * gain);
+    }
+    apply_lut_channels(image, &lut)
+}
+
+pub fn apply_brightness(image: &DynamicImage, value: i16) -> DynamicImage {
+    let mut img = image.to_rgb8();
+    apply_lut_rgb(&mut img, &brightness_lut(value));
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Степенное преобразование s = 255 · (r / 255)^(1/γ); γ > 1 осветляет
+/// тени, γ < 1 затемняет. Недопустимое γ дает тождественную таблицу.
+fn gamma_lut(gamma: f32) -> Lut {
+    if !gamma.is_finite() || gamma <= 0.0 {
+        return lut_from_fn(|v| v);
+    }
+    lut_from_fn(|v| 255.0 * (v / 255.0).powf(1.0 / gamma))
+}
+
+pub fn apply_gamma(image: &DynamicImage, gamma: f32) -> DynamicImage {
+    if gamma == 1.0 {
+        // Тождественное преобразование возвращает изображение без изменений
+        return image.clone();
+    }
+    apply_lut_channels(image, &gamma_lut(gamma))
+}
+
+/// Растяжение относительно среднего серого: s = (r - 128) · k + 128;
+/// k = 1 ничего не меняет, k = 0 дает ровный серый
+fn contrast_lut(factor: f32) -> Lut {
+    if !factor.is_finite() {
+        return lut_from_fn(|v| v);
+    }
+    let factor = factor.max(0.0);
+    lut_from_fn(|v| (v - 128.0) * factor + 128.0)
+}
+
+pub fn apply_contrast(image: &DynamicImage, factor: f32) -> DynamicImage {
+    let mut img = image.to_rgb8();
+    apply_lut_rgb(&mut img, &contrast_lut(factor));
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Яркость, контраст и гамма подряд за один проход: три таблицы
+/// объединяются в одну, результат совпадает с последовательным применением
+pub fn apply_tone(image: &DynamicImage, brightness: i16, contrast: f32, gamma: f32) -> DynamicImage {
+    let lut = compose_luts(&compose_luts(&brightness_lut(brightness), &contrast_lut(contrast)), &gamma_lut(gamma));
+    let mut img = image.to_rgb8();
+    apply_lut_rgb(&mut img, &lut);
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Яркость в модели HSV: `delta` (доля от 0..1) прибавляется только к V
+pub fn apply_brightness_hsv(image: &DynamicImage, delta: f32) -> DynamicImage {
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        // Сдвигаем только V, тон и насыщенность остаются прежними
+        let (h, s, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        let (r, g, b) = hsv_to_rgb(h, s, (v + delta).clamp(0.0, 1.0));
+        pixel[0] = r;
+        pixel[1] = g;
+        pixel[2] = b;
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Насыщенность в модели HSV: S умножается на `factor` и обрезается к 0..1,
+/// тон и V не меняются; 0 дает оттенки серого с V = max(R, G, B)
+pub fn apply_saturation(image: &DynamicImage, factor: f32) -> DynamicImage {
+    if factor == 1.0 {
+        return image.clone();
+    }
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        // У серых пикселей S = 0, умножение ничего не меняет
+        if pixel[0] == pixel[1] && pixel[1] == pixel[2] {
+            continue;
+        }
+        let (h, s, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        let (r, g, b) = hsv_to_rgb(h, (s * factor).clamp(0.0, 1.0), v);
+        pixel.0 = [r, g, b];
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Насыщенность, ниже которой пиксель считается серым: его тон - шум
+const NEAR_GRAY_SATURATION: f32 = 0.05;
+
+/// Сочность: нелинейная насыщенность. При `amount` > 0 S умножается на
+/// 1 + amount · (1 - S)², так что тусклые цвета усиливаются почти вдвое,
+/// а уже насыщенные почти не меняются; при `amount` < 0 вес обратный,
+/// 1 - (1 - S)², и сильнее всего гасятся яркие цвета. Диапазон -1..=1,
+/// почти серые пиксели пропускаются, чтобы шум не становился цветным.
+pub fn apply_vibrance(image: &DynamicImage, amount: f32) -> DynamicImage {
+    if amount == 0.0 {
+        return image.clone();
+    }
+    let amount = amount.clamp(-1.0, 1.0);
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        let (h, s, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        if s < NEAR_GRAY_SATURATION {
+            continue;
+        }
+        let dullness = (1.0 - s) * (1.0 - s);
+        let weight = if amount > 0.0 { dullness } else { 1.0 - dullness };
+        let (r, g, b) = hsv_to_rgb(h, (s * (1.0 + amount * weight)).clamp(0.0, 1.0), v);
+        pixel.0 = [r, g, b];
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Поворот тона на `degrees` градусов (по модулю 360) в модели HSV
+pub fn apply_hue_rotate(image: &DynamicImage, degrees: f32) -> DynamicImage {
+    if degrees.rem_euclid(360.0) == 0.0 {
+        return image.clone();
+    }
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        // У серых пикселей тона нет - оставляем их точно как есть
+        if pixel[0] == pixel[1] && pixel[1] == pixel[2] {
+            continue;
+        }
+        let (h, s, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        let (r, g, b) = hsv_to_rgb((h + degrees).rem_euclid(360.0), s, v);
+        pixel.0 = [r, g, b];
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Градиентная карта: яркость (Rec. 601) отображается на градиент между
+/// опорными цветами `stops` - пары (положение 0..=1, цвет). Смешивание идет
+/// в линейном свете, положение на оси яркости тоже переводится в линейный
+/// свет, поэтому градиент от черного к белому дает ровно перевод в серый.
+/// До первой и после последней опоры цвет постоянный.
+pub fn apply_gradient_map(image: &DynamicImage, stops: &[(f32, [u8; 3])]) -> DynamicImage {
+    let mut stops: Vec<(f32, [f32; 3])> = stops
+        .iter()
+        .filter(|(position, _)| position.is_finite())
+        .map(|&(position, color)| (srgb_decode(position.clamp(0.0, 1.0)), color.map(srgb_to_linear)))
+        .collect();
+    if stops.is_empty() {
+        return apply_grayscale(image, GrayMethod::Rec601);
+    }
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut lut = [[0u8; 3]; 256];
+    for (level, entry) in lut.iter_mut().enumerate() {
+        let x = srgb_to_linear(level as u8);
+        let upper = stops.partition_point(|&(position, _)| position <= x);
+        let color = if upper == 0 {
+            stops[0].1
+        } else if upper == stops.len() {
+            stops[upper - 1].1
+        } else {
+            let (p0, c0) = stops[upper - 1];
+            let (p1, c1) = stops[upper];
+            let t = (x - p0) / (p1 - p0);
+            std::array::from_fn(|c| c0[c] + (c1[c] - c0[c]) * t)
+        };
+        *entry = color.map(linear_to_srgb);
+    }
+
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        pixel.0 = lut[GrayMethod::Rec601.gray(pixel.0) as usize];
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Выборочная замена тона: пиксели с тоном в пределах `src_center ± src_width`
+/// (с переходом через 0°, например 350°..10° для красных) поворачиваются так,
+/// что центр диапазона переходит в `dst_hue`. За краями диапазона поворот
+/// плавно ослабевает на протяжении `feather` градусов. S и V не меняются,
+/// серые и почти серые пиксели не перекрашиваются.
+pub fn apply_hue_replace(
+    image: &DynamicImage,
+    src_center: f32,
+    src_width: f32,
+    dst_hue: f32,
+    feather: f32,
+) -> DynamicImage {
+    // Кратчайший поворот от центра к новому тону, -180..180
+    let rotation = (dst_hue - src_center + 180.0).rem_euclid(360.0) - 180.0;
+    if rotation == 0.0 {
+        return image.clone();
+    }
+    let width = src_width.max(0.0);
+    let feather = feather.max(0.0);
+    let mut img = image.to_rgb8();
+    for pixel in img.pixels_mut() {
+        let (h, s, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        if s < NEAR_GRAY_SATURATION {
+            continue;
+        }
+        let distance = ((h - src_center + 180.0).rem_euclid(360.0) - 180.0).abs();
+        let weight = if distance <= width {
+            1.0
+        } else if distance < width + feather {
+            let t = 1.0 - (distance - width) / feather;
+            t * t * (3.0 - 2.0 * t)
+        } else {
+            continue;
+        };
+        let (r, g, b) = hsv_to_rgb(h + rotation * weight, s, v);
+        pixel.0 = [r, g, b];
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Генератор псевдослучайных чисел SplitMix64: одно и то же зерно дает
+/// одну и ту же последовательность на любой платформе
+pub struct NoiseRng {
+    state: u64,
+    /// Второе значение пары Бокса - Мюллера
+    spare: Option<f64>,
+}
+
+impl NoiseRng {
+    /// Генератор с заданным зерном, без зерна - от текущего времени
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        Self { state: seed, spare: None }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Равномерное значение в [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Стандартное нормальное значение (преобразование Бокса - Мюллера)
+    pub fn gaussian(&mut self) -> f64 {
+        if let Some(spare) = self.spare.take() {
+            return spare;
+        }
+        // 1 - u лежит в (0, 1], логарифм конечен
+        let radius = (-2.0 * (1.0 - self.next_f64()).ln()).sqrt();
+        let angle = std::f64::consts::TAU * self.next_f64();
+        self.spare = Some(radius * angle.sin());
+        radius * angle.cos()
+    }
+}
+
+/// Аддитивный гауссов шум: к каждому каналу независимо прибавляется
+/// N(0, sigma²), результат зажимается к 0..=255. С одинаковым `seed`
+/// результат побайтно совпадает; sigma = 0 ничего не меняет.
+pub fn apply_gaussian_noise(image: &DynamicImage, sigma: f32, seed: Option<u64>) -> DynamicImage {
+    if sigma <= 0.0 || !sigma.is_finite() {
+        return image.clone();
+    }
+    let mut rng = NoiseRng::new(seed);
+    let mut img = image.to_rgb8();
+    for value in img.iter_mut() {
+        let noisy = *value as f64 + sigma as f64 * rng.gaussian();
+        *value = noisy.round().clamp(0.0, 255.0) as u8;
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Объективные метрики качества результата относительно эталона
+#[derive(Clone, Copy, Debug)]
+pub struct QualityMetrics {
+    /// Среднеквадратичная ошибка по всем каналам RGB
+    pub mse: f64,
+    /// Пиковое отношение сигнал/шум, дБ; бесконечность для одинаковых изображений
+    pub psnr: f64,
+    /// Индекс структурного сходства по яркости (Rec. 601)
+    pub ssim: f64,
+}
+
+/// MSE, PSNR и SSIM изображения `test` относительно `reference`. SSIM
+/// считается по гауссову окну 11×11 с σ = 1.5 и константами
+/// (0.01·255)², (0.03·255)², как у Ванга и др.; у границ окно обрезается.
+/// `None`, если размеры различаются.
+pub fn compute_quality_metrics(reference: &DynamicImage, test: &DynamicImage) -> Option<QualityMetrics> {
+    if reference.dimensions() != test.dimensions() {
+        return None;
+    }
+    let (a, b) = (reference.to_rgb8(), test.to_rgb8());
+    let squared: f64 = a.iter().zip(b.iter()).map(|(&x, &y)| (x as f64 - y as f64).powi(2)).sum();
+    let mse = squared / a.len().max(1) as f64;
+    let psnr = if mse == 0.0 { f64::INFINITY } else { 10.0 * (255.0 * 255.0 / mse).log10() };
+
+    let (width, height) = (a.width() as usize, a.height() as usize);
+    let luma = |img: &RgbImage| -> Vec<f32> { img.pixels().map(|p| GrayMethod::Rec601.gray(p.0) as f32).collect() };
+    let (x, y) = (luma(&a), luma(&b));
+    let kernel = gaussian_weights(1.5, 5);
+    let mean = |plane: &[f32]| gaussian_plane_mean(plane, width, height, &kernel);
+    let product = |p: &[f32], q: &[f32]| -> Vec<f32> { p.iter().zip(q).map(|(u, v)| u * v).collect() };
+    let (mu_x, mu_y) = (mean(&x), mean(&y));
+    let (xx, yy, xy) = (mean(&product(&x, &x)), mean(&product(&y, &y)), mean(&product(&x, &y)));
+
+    const C1: f32 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f32 = (0.03 * 255.0) * (0.03 * 255.0);
+    let total: f64 = (0..x.len())
+        .map(|i| {
+            let (mx, my) = (mu_x[i], mu_y[i]);
+            let (vx, vy, cov) = (xx[i] - mx * mx, yy[i] - my * my, xy[i] - mx * my);
+            ((2.0 * mx * my + C1) * (2.0 * cov + C2) / ((mx * mx + my * my + C1) * (vx + vy + C2))) as f64
+        })
+        .sum();
+    let ssim = if x.is_empty() { 1.0 } else { total / x.len() as f64 };
+    Some(QualityMetrics { mse, psnr, ssim })
+}
+
+/// Режим смешивания двух изображений
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlendMode {
+    Add,
+    Subtract,
+    Difference,
+    Multiply,
+    Screen,
+    Average,
+    Min,
+    Max,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 8] = [
+        BlendMode::Add,
+        BlendMode::Subtract,
+        BlendMode::Difference,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Average,
+        BlendMode::Min,
+        BlendMode::Max,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BlendMode::Add => "Сложение",
+            BlendMode::Subtract => "Вычитание",
+            BlendMode::Difference => "Модуль разности",
+            BlendMode::Multiply => "Умножение",
+            BlendMode::Screen => "Экран",
+            BlendMode::Average => "Среднее",
+            BlendMode::Min => "Минимум",
+            BlendMode::Max => "Максимум",
+        }
+    }
+
+    /// Смешивание значений канала, результат зажат к 0..=255
+    fn mix(self, a: f32, b: f32) -> f32 {
+        let mixed = match self {
+            BlendMode::Add => a + b,
+            BlendMode::Subtract => a - b,
+            BlendMode::Difference => (a - b).abs(),
+            BlendMode::Multiply => a * b / 255.0,
+            BlendMode::Screen => 255.0 - (255.0 - a) * (255.0 - b) / 255.0,
+            BlendMode::Average => (a + b) / 2.0,
+            BlendMode::Min => a.min(b),
+            BlendMode::Max => a.max(b),
+        };
+        mixed.clamp(0.0, 255.0)
+    }
+}
+
+/// Смешивание `a` с `b` в режиме `mode`; `opacity` - доля смешанного
+/// результата. `b` приводится к размерам `a`: при `resize` растягивается,
+/// иначе совмещается центрами - лишнее обрезается, а где `b` не хватает,
+/// остается `a`
+pub fn apply_blend(a: &DynamicImage, b: &DynamicImage, mode: BlendMode, opacity: f32, resize: bool) -> DynamicImage {
+    let (width, height) = a.dimensions();
+    let b = if resize && b.dimensions() != (width, height) {
+        b.resize_exact(width, height, image::imageops::FilterType::Triangle).to_rgb8()
+    } else {
+        b.to_rgb8()
+    };
+    let offset_x = (b.width() as i64 - width as i64) / 2;
+    let offset_y = (b.height() as i64 - height as i64) / 2;
+    let opacity = opacity.clamp(0.0, 1.0);
+
+    let mut img = a.to_rgb8();
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let (bx, by) = (x as i64 + offset_x, y as i64 + offset_y);
+        if !(0..b.width() as i64).contains(&bx) || !(0..b.height() as i64).contains(&by) {
+            continue;
+        }
+        let other = b.get_pixel(bx as u32, by as u32);
+        for c in 0..3 {
+            let value = pixel[c] as f32;
+            let mixed = mode.mix(value, other[c] as f32);
+            pixel[c] = (value + (mixed - value) * opacity).round() as u8;
+        }
+    }
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Применение результата по маске: result·m + source·(1 − m), m = mask/255.
+/// Маска другого размера масштабируется к изображению методом ближайшего
+/// соседа. `None`, если операция изменила размер изображения
+pub fn apply_mask(result: &DynamicImage, source: &DynamicImage, mask: &GrayImage) -> Option<DynamicImage> {
+    let (width, height) = source.dimensions();
+    if result.dimensions() != (width, height) {
+        return None;
+    }
+    let resized;
+    let mask = if mask.dimensions() == (width, height) {
+        mask
+    } else {
+        resized = image::imageops::resize(mask, width, height, image::imageops::FilterType::Nearest);
+        &resized
+    };
+    let gray = matches!(result, DynamicImage::ImageLuma8(_)) && matches!(source, DynamicImage::ImageLuma8(_));
+    let base = source.to_rgb8();
+    let mut img = result.to_rgb8();
+    for ((pixel, base), weight) in img.pixels_mut().zip(base.pixels()).zip(mask.pixels()) {
+        let m = weight[0] as f32 / 255.0;
+        for c in 0..3 {
+            pixel[c] = (pixel[c] as f32 * m + base[c] as f32 * (1.0 - m)).round() as u8;
+        }
+    }
+    Some(if gray {
+        DynamicImage::ImageLuma8(DynamicImage::ImageRgb8(img).to_luma8())
+    } else {
+        DynamicImage::ImageRgb8(img)
+    })
+}
+
+/// Вид разностного изображения
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DifferenceMode {
+    /// |a - b|, одинаковые пиксели черные
+    Absolute,
+    /// a - b со смещением 128: серый - без изменений, светлее - a ярче
+    Signed,
+}
+
+impl DifferenceMode {
+    pub const ALL: [DifferenceMode; 2] = [DifferenceMode::Absolute, DifferenceMode::Signed];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DifferenceMode::Absolute => "Модуль",
+            DifferenceMode::Signed => "Со знаком",
+        }
+    }
+}
+
+/// Поканальная разность двух изображений в RGB, усиленная в `gain` раз
+/// и зажатая к 0..=255. `None`, если размеры различаются.
+pub fn apply_difference(a: &DynamicImage, b: &DynamicImage, gain: f32, mode: DifferenceMode) -> Option<DynamicImage> {
+    if a.dimensions() != b.dimensions() {
+        return None;
+    }
+    let mut result = a.to_rgb8();
+    let b = b.to_rgb8();
+    for (value, &other) in result.iter_mut().zip(b.iter()) {
+        let difference = (*value as f32 - other as f32) * gain;
+        *value = match mode {
+            DifferenceMode::Absolute => difference.abs(),
+            DifferenceMode::Signed => difference + 128.0,
+        }
+        .round()
+        .clamp(0.0, 255.0) as u8;
+    }
+    Some(DynamicImage::ImageRgb8(result))
+}
+
+/// Статистика одного канала, посчитанная по его 256-уровневой гистограмме
+#[derive(Clone, Debug, Default)]
+pub struct ChannelStats {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: u8,
+    pub max: u8,
+    pub median: u8,
+    /// Энтропия Шеннона, бит
+    pub entropy: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageStats {
+    pub red: ChannelStats,
+    pub green: ChannelStats,
+    pub blue: ChannelStats,
+    pub luma: ChannelStats,
+    /// Полутоновое изображение - выводится только яркость
+    pub grayscale: bool,
+    /// Гистограммы R, G, B и яркости
+    pub histograms: [[u64; 256]; 4],
+}
+
+fn channel_stats(histogram: &[u64; 256]) -> ChannelStats {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return ChannelStats::default();
+    }
+    let n = total as f64;
+
+    let mut sum = 0.0;
+    let mut sum_sq = 0.0;
+    let mut entropy = 0.0;
+    for (v, &count) in histogram.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let c = count as f64;
+        sum += v as f64 * c;
+        sum_sq += (v as f64) * (v as f64) * c;
+        let p = c / n;
+        entropy -= p * p.log2();
+    }
+    let mean = sum / n;
+    let variance = (sum_sq / n - mean * mean).max(0.0);
+
+    let min = histogram.iter().position(|&c| c > 0).unwrap_or(0) as u8;
+    let max = histogram.iter().rposition(|&c| c > 0).unwrap_or(0) as u8;
+
+    // Медиана - первое значение, на котором накопленная частота достигает половины
+    let mut cumulative = 0u64;
+    let mut median = 0u8;
+    for (v, &count) in histogram.iter().enumerate() {
+        cumulative += count;
+        if cumulative * 2 >= total {
+            median = v as u8;
+            break;
+        }
+    }
+
+    ChannelStats { mean, std_dev: variance.sqrt(), min, max, median, entropy }
+}
+
+pub fn compute_stats(image: &DynamicImage) -> ImageStats {
+    let rgb = image.to_rgb8();
+    let mut histograms = [[0u64; 256]; 3];
+    for pixel in rgb.pixels() {
+        for c in 0..3 {
+            histograms[c][pixel[c] as usize] += 1;
+        }
+    }
+    let luma = gray_histogram(&image.to_luma8());
+
+    ImageStats {
+        red: channel_stats(&histograms[0]),
+        green: channel_stats(&histograms[1]),
+        blue: channel_stats(&histograms[2]),
+        luma: channel_stats(&luma),
+        grayscale: !image.color().has_color(),
+        histograms: [histograms[0], histograms[1], histograms[2], luma],
+    }
+}
+
+impl ImageStats {
+    /// Каналы для вывода: у полутонового изображения R, G и B совпадают
+    /// с яркостью, поэтому остается одна строка
+    pub fn channels(&self) -> Vec<(&'static str, &ChannelStats)> {
+        if self.grayscale {
+            vec![("Яркость", &self.luma)]
+        } else {
+            vec![("R", &self.red), ("G", &self.green), ("B", &self.blue), ("Яркость", &self.luma)]
+        }
+    }
+
+    /// Таблица с разделителями-табуляциями для вставки в отчет
+    pub fn to_table(&self, title: &str) -> String {
+        let mut text = format!("{title}\nКанал\tСреднее\tСКО\tМин\tМакс\tМедиана\tЭнтропия\n");
+        for (name, stats) in self.channels() {
+            text += &format!(
+                "{}\t{:.2}\t{:.2}\t{}\t{}\t{}\t{:.3}\n",
+                name, stats.mean, stats.std_dev, stats.min, stats.max, stats.median, stats.entropy
+            );
+        }
+        text
+    }
+
+    /// Гистограммы в CSV: 256 строк "bin,luma,r,g,b" после заголовка
+    pub fn histogram_csv(&self) -> String {
+        let [red, green, blue, luma] = &self.histograms;
+        let mut text = String::from("bin,luma,r,g,b\n");
+        for bin in 0..256 {
+            text += &format!("{bin},{},{},{},{}\n", luma[bin], red[bin], green[bin], blue[bin]);
+        }
+        text
+    }
+
+    /// Рисует гистограмму так же, как панель в окне: столбики яркости,
+    /// при `rgb` - полупрозрачные линии каналов, `marker` - отметка порога
+    pub fn render_histogram(&self, rgb: bool, marker: Option<u8>) -> RgbImage {
+        const WIDTH: u32 = 1024;
+        const HEIGHT: u32 = 400;
+        const BIN_WIDTH: u32 = WIDTH / 256;
+        let [red, green, blue, luma] = &self.histograms;
+        let shown: &[&[u64; 256]] = if rgb { &[luma, red, green, blue] } else { &[luma] };
+        let max = shown.iter().flat_map(|h| h.iter()).copied().max().unwrap_or(0).max(1) as f32;
+        let y_of = |count: f32| (HEIGHT - 1) as f32 * (1.0 - count / max);
+
+        let mut img = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([24, 24, 24]));
+        for (bin, &count) in luma.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let top = y_of(count as f32).round() as u32;
+            for x in bin as u32 * BIN_WIDTH..(bin as u32 + 1) * BIN_WIDTH {
+                for y in top..HEIGHT {
+                    img.put_pixel(x, y, Rgb([150, 150, 150]));
+                }
+            }
+        }
+        if rgb {
+            let colors = [(red, [255, 60, 60]), (green, [60, 200, 60]), (blue, [70, 110, 255])];
+            for (histogram, color) in colors {
+                // Линия через центры корзин: в каждом столбце закрашивается
+                // отрезок от предыдущей высоты до текущей, чтобы не было разрывов
+                let mut previous: Option<f32> = None;
+                for x in 0..WIDTH {
+                    let position = ((x as f32 + 0.5) / BIN_WIDTH as f32 - 0.5).clamp(0.0, 255.0);
+                    let left = position.floor() as usize;
+                    let right = (left + 1).min(255);
+                    let t = position - left as f32;
+                    let y = y_of(histogram[left] as f32 * (1.0 - t) + histogram[right] as f32 * t);
+                    let (from, to) = match previous {
+                        Some(prev) => (prev.min(y), prev.max(y)),
+                        None => (y, y),
+                    };
+                    for py in from.round() as u32..=(to.round() as u32 + 1).min(HEIGHT - 1) {
+                        let pixel = img.get_pixel_mut(x, py);
+                        pixel.0 = blend_over(pixel.0, color, 0.7);
+                    }
+                    previous = Some(y);
+                }
+            }
+        }
+        if let Some(threshold) = marker {
+            let center = threshold as u32 * BIN_WIDTH + BIN_WIDTH / 2;
+            for x in center.saturating_sub(1)..=(center + 1).min(WIDTH - 1) {
+                for y in 0..HEIGHT {
+                    img.put_pixel(x, y, Rgb([255, 215, 0]));
+                }
+            }
+        }
+        img
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// Строка 256×1, в каждом канале встречаются все уровни 0..=255
+    fn all_levels() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(256, 1, |x, _| {
+            let i = x as u8;
+            Rgb([i, 255 - i, i.wrapping_mul(37)])
+        }))
+    }
+
+    #[test]
+    fn brightness_lut_matches_formula() {
+        let image = all_levels();
+        for value in [-255, -100, -1, 0, 1, 37, 255] {
+            let mut expected = image.to_rgb8();
+            for pixel in expected.pixels_mut() {
+                for i in 0..3 {
+                    pixel[i] = ((pixel[i] as i16 + value).clamp(0, 255)) as u8;
+                }
+            }
+            assert_eq!(apply_brightness(&image, value).to_rgb8(), expected, "value {value}");
+        }
+    }
+
+    #[test]
+    fn inversion_lut_matches_formula() {
+        let image = all_levels();
+        let mut expected = image.to_rgb8();
+        for pixel in expected.pixels_mut() {
+            pixel[0] = 255 - pixel[0];
+            pixel[1] = 255 - pixel[1];
+            pixel[2] = 255 - pixel[2];
+        }
+        assert_eq!(apply_inversion(&image).to_rgb8(), expected);
+    }
+
+    #[test]
+    fn threshold_lut_matches_formula() {
+        let image = all_levels();
+        for threshold in [0, 1, 127, 128, 254, 255] {
+            let mut expected = image.to_luma8();
+            for pixel in expected.pixels_mut() {
+                pixel[0] = if pixel[0] > threshold { 255 } else { 0 };
+            }
+            assert_eq!(apply_manual_threshold(&image, threshold).to_luma8(), expected, "threshold {threshold}");
+        }
+    }
+
+    #[test]
+    fn tone_matches_sequential_operations() {
+        let image = all_levels();
+        for (brightness, contrast, gamma) in [(0, 1.0, 1.0), (40, 1.5, 0.7), (-60, 0.5, 2.2)] {
+            let sequential = apply_gamma(&apply_contrast(&apply_brightness(&image, brightness), contrast), gamma);
+            assert_eq!(apply_tone(&image, brightness, contrast, gamma).to_rgb8(), sequential.to_rgb8());
+        }
+    }
+
+    #[test]
+    fn perspective_identity_corners_keep_image() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(7, 5, |x, y| Rgb([(x * 30) as u8, (y * 50) as u8, 99])));
+        let corners = [(0.0, 0.0), (7.0, 0.0), (7.0, 5.0), (0.0, 5.0)];
+        let points = corners.map(|(x, y)| (x as f64, y as f64));
+        let identity = Homography::from_points(points, points).expect("невырожденные углы");
+        let (x, y) = identity.map(3.25, 1.5);
+        assert!((x - 3.25).abs() < 1e-9 && (y - 1.5).abs() < 1e-9);
+        assert_eq!(apply_perspective(&image, corners).unwrap().to_rgb8(), image.to_rgb8());
+    }
+
+    #[test]
+    fn perspective_maps_unit_square_onto_known_quad() {
+        let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let quad = [(10.0, 5.0), (50.0, 0.0), (60.0, 40.0), (0.0, 30.0)];
+        let close = |(x, y): (f64, f64), (u, v): (f64, f64)| (x - u).abs() < 1e-9 && (y - v).abs() < 1e-9;
+        let forward = Homography::from_points(square, quad).expect("невырожденный четырехугольник");
+        for (corner, target) in square.iter().zip(&quad) {
+            assert!(close(forward.map(corner.0, corner.1), *target), "{:?}", corner);
+        }
+        // Центр квадрата переходит в точку пересечения диагоналей четырехугольника:
+        // (10, 5) + 19/65·(50, 35) = (50, 0) + 33/65·(-50, 30) = (320/13, 198/13)
+        let center = (320.0 / 13.0, 198.0 / 13.0);
+        assert!(close(forward.map(0.5, 0.5), center), "{:?}", forward.map(0.5, 0.5));
+
+        let inverse = Homography::from_points(quad, square).expect("невырожденный квадрат");
+        assert!(close(inverse.map(center.0, center.1), (0.5, 0.5)));
+        for (corner, source) in quad.iter().zip(&square) {
+            assert!(close(inverse.map(corner.0, corner.1), *source), "{:?}", corner);
+        }
+        let (x, y) = forward.map(0.3, 0.7);
+        assert!(close(inverse.map(x, y), (0.3, 0.7)));
+    }
+
+    #[test]
+    fn local_threshold_clamps_oversized_window() {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_fn(5, 3, |x, y| Luma([(x * 40 + y * 30) as u8])));
+        for method in LocalMethod::ALL {
+            let clamped = apply_local_threshold(&image, method, 5, -0.2, SAUVOLA_R);
+            let oversized = apply_local_threshold(&image, method, 1001, -0.2, SAUVOLA_R);
+            assert_eq!(oversized.dimensions(), (5, 3));
+            assert_eq!(oversized.to_luma8(), clamped.to_luma8(), "{}", method.name());
+        }
+        let single = DynamicImage::ImageLuma8(GrayImage::from_pixel(1, 1, Luma([200])));
+        apply_local_threshold(&single, LocalMethod::Niblack, u32::MAX, -0.2, SAUVOLA_R);
+    }
+
+    #[test]
+    fn vignette_zero_strength_keeps_image() {
+        let pixels = RgbaImage::from_fn(6, 4, |x, y| Rgba([(x * 40) as u8, (y * 60) as u8, 7, 128]));
+        let image = DynamicImage::ImageRgba8(pixels);
+        assert_eq!(apply_vignette(&image, 0.0, 0.5, 0.5), image);
+    }
+
+    /// Прежнее контрастирование по полному диапазону V, без отсечения
+    fn apply_linear_contrast(image: &DynamicImage) -> DynamicImage {
+        let mut img = image.to_rgb8();
+        let mut min_v: f32 = 1.0;
+        let mut max_v: f32 = 0.0;
+        for pixel in img.pixels() {
+            let (_, _, v) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+            min_v = min_v.min(v);
+            max_v = max_v.max(v);
+        }
+        stretch_value(&mut img, min_v, max_v);
+        DynamicImage::ImageRgb8(img)
+    }
+
+    #[test]
+    fn clipped_contrast_without_clipping_matches_full_range() {
+        let low_contrast = DynamicImage::ImageRgb8(RgbImage::from_fn(16, 16, |x, y| {
+            Rgb([60 + x as u8 * 5, 80 + y as u8 * 4, 70 + ((x + y) as u8) * 2])
+        }));
+        for image in [all_levels(), low_contrast] {
+            let full = apply_linear_contrast(&image).to_rgb8();
+            assert_eq!(apply_linear_contrast_clipped(&image, 0.0, 100.0).to_rgb8(), full);
+        }
+    }
+
+    /// Тон единственного пикселя изображения 1×1
+    fn hue_of(image: &DynamicImage) -> f32 {
+        let pixel = image.to_rgb8()[(0, 0)];
+        rgb_to_hsv(pixel[0], pixel[1], pixel[2]).0
+    }
+
+    #[test]
+    fn hsv_brightness_keeps_hue_of_red() {
+        let red = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([200, 30, 0])));
+        let hue = hue_of(&red);
+        let hsv = apply_brightness_hsv(&red, 80.0 / 255.0);
+        let rgb = apply_brightness(&red, 80);
+        assert!((hue_of(&hsv) - hue).abs() < 0.5, "HSV: {} -> {}", hue, hue_of(&hsv));
+        assert!((hue_of(&rgb) - hue).abs() > 1.0, "RGB: {} -> {}", hue, hue_of(&rgb));
+    }
+
+    #[test]
+    fn hsv_round_trip_is_identity() {
+        for r in 0..=255u8 {
+            for g in 0..=255u8 {
+                for b in 0..=255u8 {
+                    let (h, s, v) = rgb_to_hsv(r, g, b);
+                    assert_eq!(hsv_to_rgb(h, s, v), (r, g, b), "hsv ({h}, {s}, {v})");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hsv_known_values() {
+        let colors = [
+            ((255, 0, 0), 0.0),
+            ((255, 255, 0), 60.0),
+            ((0, 255, 0), 120.0),
+            ((0, 255, 255), 180.0),
+            ((0, 0, 255), 240.0),
+            ((255, 0, 255), 300.0),
+        ];
+        for ((r, g, b), hue) in colors {
+            assert_eq!(rgb_to_hsv(r, g, b), (hue, 1.0, 1.0), "({r}, {g}, {b})");
+            assert_eq!(hsv_to_rgb(hue, 1.0, 1.0), (r, g, b));
+        }
+        assert_eq!(rgb_to_hsv(128, 128, 128), (0.0, 0.0, 128.0 / 255.0));
+        assert_eq!(rgb_to_hsv(0, 0, 0), (0.0, 0.0, 0.0));
+        assert_eq!(hsv_to_rgb(360.0, 1.0, 1.0), (255, 0, 0));
+        assert_eq!(hsv_to_rgb(-1e-4, 1.0, 1.0), (255, 0, 0));
+    }
+
+    #[test]
+    fn hsv_filters_with_noop_parameters_keep_bytes() {
+        // Вторая строка - серые уровни, чтобы V занимал весь диапазон 0..=255
+        let levels = all_levels().to_rgb8();
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 2, |x, y| {
+            if y == 0 { levels[(x, 0)] } else { Rgb([x as u8; 3]) }
+        }));
+        let bytes = image.to_rgb8();
+        assert_eq!(apply_brightness_hsv(&image, 0.0).to_rgb8(), bytes);
+        assert_eq!(apply_lut(&image, &lut_from_fn(|v| v), LutTarget::Value).to_rgb8(), bytes);
+        assert_eq!(apply_linear_contrast_clipped(&image, 0.0, 100.0).to_rgb8(), bytes);
+    }
+
+    #[test]
+    fn two_class_multi_otsu_matches_otsu() {
+        let mut skewed = [0u64; 256];
+        for (i, count) in skewed.iter_mut().enumerate() {
+            *count = ((i * 7919) % 97) as u64 + if (40..70).contains(&i) { 500 } else { 0 };
+        }
+        let image = all_levels();
+        for histogram in [gray_histogram(&image.to_luma8()), skewed] {
+            let expected: Vec<u8> = otsu_threshold(&histogram).into_iter().collect();
+            assert_eq!(multi_otsu_thresholds(&histogram, 2), expected);
+        }
+        let (binary, threshold) = apply_otsu_threshold(&image);
+        let (classes, thresholds) = apply_multi_otsu(&image, 2);
+        assert_eq!(thresholds, threshold.into_iter().collect::<Vec<_>>());
+        assert_eq!(classes.to_luma8(), binary.to_luma8());
+    }
+
+    #[test]
+    fn three_class_multi_otsu_separates_peaks() {
+        let mut histogram = [0u64; 256];
+        for level in [20, 120, 220] {
+            histogram[level] = 1000;
+        }
+        let thresholds = multi_otsu_thresholds(&histogram, 3);
+        assert_eq!(thresholds.len(), 2);
+        assert!((20..120).contains(&thresholds[0]) && (120..220).contains(&thresholds[1]), "{thresholds:?}");
+    }
+
+    /// Сильное пятно 2×2, слабая линия от него вправо и отдельный слабый пиксель
+    fn hysteresis_sample() -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(10, 5, |x, y| match (x, y) {
+            (1..=2, 1..=2) => Luma([250]),
+            (3..=6, 2) => Luma([100]),
+            (9, 4) => Luma([100]),
+            _ => Luma([10]),
+        }))
+    }
+
+    #[test]
+    fn hysteresis_keeps_connected_weak_pixels() {
+        let result = apply_hysteresis_threshold(&hysteresis_sample(), 50, 200).to_luma8();
+        for x in 3..=6 {
+            assert_eq!(result[(x, 2)][0], 255, "линия, x = {x}");
+        }
+        assert_eq!(result[(1, 1)][0], 255);
+        assert_eq!(result[(9, 4)][0], 0, "отдельный слабый пиксель");
+        assert_eq!(result[(0, 0)][0], 0);
+    }
+
+    #[test]
+    fn hysteresis_low_above_high_is_single_threshold() {
+        let image = hysteresis_sample();
+        let result = apply_hysteresis_threshold(&image, 200, 50);
+        assert_eq!(result.to_luma8(), apply_manual_threshold(&image, 50).to_luma8());
+    }
+
+    #[test]
+    fn kuwahara_keeps_flat_image() {
+        let flat = DynamicImage::ImageRgb8(RgbImage::from_pixel(9, 7, Rgb([90, 140, 30])));
+        assert_eq!(apply_kuwahara(&flat, 3).to_rgb8(), flat.to_rgb8());
+    }
+
+    #[test]
+    fn kuwahara_preserves_step_edge() {
+        // Ступенька 50 | 200 с шумом ±8 в шахматном порядке
+        let noisy = DynamicImage::ImageRgb8(RgbImage::from_fn(16, 8, |x, y| {
+            let base: i16 = if x < 8 { 50 } else { 200 };
+            let noise = if (x + y) % 2 == 0 { 8 } else { -8 };
+            Rgb([(base + noise) as u8; 3])
+        }));
+        let result = apply_kuwahara(&noisy, 2).to_rgb8();
+        let spread = |img: &RgbImage, columns: std::ops::Range<u32>| {
+            let values: Vec<f64> =
+                columns.flat_map(|x| (0..8).map(move |y| img[(x, y)][0] as f64)).collect();
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+        };
+        for y in 0..8 {
+            assert!(result[(7, y)][0] < 70 && result[(8, y)][0] > 180, "строка {y}");
+        }
+        assert!(spread(&result, 0..8) < spread(&noisy.to_rgb8(), 0..8));
+        assert!(spread(&result, 8..16) < spread(&noisy.to_rgb8(), 8..16));
+    }
+
+    #[test]
+    fn kuwahara_clamps_quadrants_at_border() {
+        // Эталон: квадранты всегда (r+1)×(r+1), координаты за краем зажимаются
+        let source = noise_image(7, 5);
+        let luma = DynamicImage::ImageRgb8(source.clone()).to_luma8();
+        let r = 2i64;
+        let at = |x: i64, y: i64| (x.clamp(0, 6) as u32, y.clamp(0, 4) as u32);
+        let result = apply_kuwahara(&DynamicImage::ImageRgb8(source.clone()), r as u8).to_rgb8();
+        for (x, y, pixel) in result.enumerate_pixels() {
+            let (x, y) = (x as i64, y as i64);
+            let quadrants = [(x - r, y - r), (x, y - r), (x - r, y), (x, y)].map(|(x0, y0)| {
+                let points: Vec<(u32, u32)> =
+                    (y0..=y0 + r).flat_map(|sy| (x0..=x0 + r).map(move |sx| at(sx, sy))).collect();
+                let values: Vec<f64> = points.iter().map(|&p| luma[p][0] as f64).collect();
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                let variance = values.iter().map(|v| v * v).sum::<f64>() / values.len() as f64 - mean * mean;
+                (variance, points)
+            });
+            let (_, points) = quadrants.iter().fold(&quadrants[0], |best, q| if q.0 < best.0 { q } else { best });
+            let mean: [u8; 3] = std::array::from_fn(|c| {
+                (points.iter().map(|&p| source[p][c] as f64).sum::<f64>() / points.len() as f64).round() as u8
+            });
+            assert_eq!(pixel.0, mean, "({}, {})", x, y);
+        }
+    }
+
+    /// Тусклый градиент с оттенком (R выше G и B на 40) и двумя выбросами 0 и 255
+    fn dull_with_outliers() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(101, 10, |x, y| match (x, y) {
+            (0, 0) => Rgb([0; 3]),
+            (100, 9) => Rgb([255; 3]),
+            _ => Rgb([50 + x as u8, 10 + x as u8, 10 + x as u8]),
+        }))
+    }
+
+    #[test]
+    fn auto_contrast_is_idempotent() {
+        let once = apply_auto_contrast(&dull_with_outliers(), 0.5);
+        let twice = apply_auto_contrast(&once, 0.5);
+        for (a, b) in once.to_rgb8().iter().zip(twice.to_rgb8().iter()) {
+            assert!(a.abs_diff(*b) <= 1, "{a} -> {b}");
+        }
+    }
+
+    #[test]
+    fn auto_contrast_clips_outliers_per_channel() {
+        let image = dull_with_outliers();
+        // Без отсечения выбросы уже занимают весь диапазон
+        assert_eq!(apply_auto_contrast(&image, 0.0).to_rgb8(), image.to_rgb8());
+        let result = apply_auto_contrast(&image, 0.5).to_rgb8();
+        assert_eq!(result[(0, 1)].0, [0, 0, 0]);
+        assert_eq!(result[(100, 0)].0, [255, 255, 255]);
+        // Оттенок убран: в середине каналы совпадают
+        let middle = result[(50, 5)];
+        assert!(middle[0].abs_diff(middle[1]) <= 1 && middle[1] == middle[2], "{middle:?}");
+    }
+
+    #[test]
+    fn auto_contrast_keeps_uniform_channel() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(8, 8, |x, _| Rgb([x as u8 * 30, 77, 77])));
+        let result = apply_auto_contrast(&image, 0.5).to_rgb8();
+        assert!(result.pixels().all(|p| p[1] == 77 && p[2] == 77));
+    }
+
+    /// Яркость Rec. 601 цвета палитры
+    fn table_luma([r, g, b]: [u8; 3]) -> f32 {
+        0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32
+    }
+
+    #[test]
+    fn colormap_endpoints_match_control_points() {
+        let ends = DynamicImage::ImageLuma8(GrayImage::from_fn(2, 1, |x, _| Luma([if x == 0 { 0 } else { 255 }])));
+        for map in Colormap::ALL {
+            let points = map.control_points();
+            let (first, last) = (points[0], points[points.len() - 1]);
+            let table = map.table();
+            assert_eq!((table[0], table[255]), (first, last), "{}", map.name());
+            let colored = apply_colormap(&ends, map).to_rgb8();
+            assert_eq!((colored[(0, 0)].0, colored[(1, 0)].0), (first, last), "{}", map.name());
+        }
+    }
+
+    #[test]
+    fn sequential_colormaps_grow_in_luminance() {
+        for map in [Colormap::Inferno, Colormap::Heat] {
+            let table = map.table();
+            for i in 1..256 {
+                assert!(table_luma(table[i]) >= table_luma(table[i - 1]), "{} на {i}", map.name());
+            }
+        }
+    }
+
+    #[test]
+    fn equalization_of_flat_histogram_is_identity() {
+        let gray = GrayImage::from_fn(256, 4, |x, _| Luma([x as u8]));
+        let luma = DynamicImage::ImageLuma8(gray.clone());
+        let rgb = DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(gray).to_rgb8());
+        for space in EqualizationSpace::ALL {
+            assert_eq!(apply_histogram_equalization(&luma, space), luma, "{}", space.name());
+            assert_eq!(apply_histogram_equalization(&rgb, space).to_rgb8(), rgb.to_rgb8(), "{}", space.name());
+        }
+    }
+
+    #[test]
+    fn equalization_spans_full_range() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(41, 3, |x, y| {
+            Rgb([100 + x as u8, 120 + (x as u8) / 2, 90 + y as u8 * 10])
+        }));
+        let range = |values: Vec<f32>| values.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &v| (v.min(lo), v.max(hi)));
+        let value = apply_histogram_equalization(&image, EqualizationSpace::HsvValue).to_rgb8();
+        assert_eq!(range(value.pixels().map(|p| p[0].max(p[1]).max(p[2]) as f32).collect()), (0.0, 255.0));
+        let channels = apply_histogram_equalization(&image, EqualizationSpace::RgbPerChannel).to_rgb8();
+        for c in 0..3 {
+            assert_eq!(range(channels.pixels().map(|p| p[c] as f32).collect()), (0.0, 255.0), "канал {c}");
+        }
+        // Y после обратного перевода сдвигается, когда цвет выходит за границы RGB
+        let luma = apply_histogram_equalization(&image, EqualizationSpace::YCbCrLuma).to_rgb8();
+        let (low, high) = range(luma.pixels().map(|p| rgb_to_ycbcr(p[0], p[1], p[2]).0).collect());
+        assert!(low <= 8.0 && high >= 247.0, "Y: {low}..{high}");
+    }
+
+    #[test]
+    fn ycbcr_round_trip_is_identity() {
+        for r in 0..=255u8 {
+            for g in 0..=255u8 {
+                for b in 0..=255u8 {
+                    let (y, cb, cr) = rgb_to_ycbcr(r, g, b);
+                    assert_eq!(ycbcr_to_rgb(y, cb, cr), (r, g, b));
+                }
+            }
+        }
+    }
+
+    /// Псевдослучайное изображение без внешних зависимостей
+    fn noise_image(width: u32, height: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            let seed = (x * 73856093) ^ (y * 19349663);
+            Rgb([(seed % 251) as u8, (seed / 7 % 253) as u8, (seed / 13 % 241) as u8])
+        })
+    }
+
+    #[test]
+    fn box_blur_matches_naive_mean() {
+        let source = noise_image(9, 6);
+        let (width, height) = (source.width() as i64, source.height() as i64);
+        for radius in [1u32, 2, 4, 12] {
+            // Два раздельных прохода с округлением после каждого, как в фильтре
+            let r = radius as i64;
+            let pass = |img: &RgbImage, horizontal: bool| {
+                RgbImage::from_fn(img.width(), img.height(), |x, y| {
+                    let mut pixel = [0u8; 3];
+                    for (c, value) in pixel.iter_mut().enumerate() {
+                        let (x, y) = (x as i64, y as i64);
+                        let sum: u64 = (-r..=r)
+                            .map(|d| {
+                                let (sx, sy) = if horizontal { (x + d, y) } else { (x, y + d) };
+                                img[(sx.clamp(0, width - 1) as u32, sy.clamp(0, height - 1) as u32)][c] as u64
+                            })
+                            .sum();
+                        let length = 2 * r as u64 + 1;
+                        *value = ((sum + length / 2) / length) as u8;
+                    }
+                    Rgb(pixel)
+                })
+            };
+            let expected = pass(&pass(&source, true), false);
+            let blurred = apply_box_blur(&DynamicImage::ImageRgb8(source.clone()), radius, 1);
+            assert_eq!(blurred.to_rgb8(), expected, "радиус {radius}");
+        }
+    }
+
+    #[test]
+    fn box_blur_keeps_constant_image_and_zero_radius() {
+        let constant = DynamicImage::ImageRgb8(RgbImage::from_pixel(12, 5, Rgb([17, 200, 93])));
+        assert_eq!(apply_box_blur(&constant, 3, 3), constant);
+        let noisy = DynamicImage::ImageRgb8(noise_image(7, 7));
+        assert_eq!(apply_box_blur(&noisy, 0, 3), noisy);
+        // Тождество сохраняет и формат, и альфа-канал
+        let pixels = RgbaImage::from_fn(5, 5, |x, y| Rgba([(x * 50) as u8, (y * 50) as u8, 9, (x * y * 10) as u8]));
+        let image = DynamicImage::ImageRgba8(pixels);
+        assert_eq!(apply_box_blur(&image, 0, 1), image);
+        // Окно больше изображения: края зажимаются, однотонное остается однотонным
+        let narrow = DynamicImage::ImageRgb8(RgbImage::from_pixel(3, 40, Rgb([250, 3, 128])));
+        assert_eq!(apply_box_blur(&narrow, 50, 1), narrow);
+    }
+
+    #[test]
+    fn identity_curve_gives_identity_lut() {
+        let identity = lut_from_fn(|v| v);
+        assert_eq!(curve_lut(&[(0.0, 0.0), (255.0, 255.0)]), identity);
+        assert_eq!(curve_lut(&[(0.0, 0.0), (100.0, 100.0), (255.0, 255.0)]), identity);
+    }
+
+    #[test]
+    fn monotone_points_give_monotone_lut() {
+        let points = [(0.0, 0.0), (64.0, 20.0), (128.0, 200.0), (200.0, 210.0), (255.0, 255.0)];
+        let lut = curve_lut(&points);
+        assert!(lut.windows(2).all(|w| w[0] <= w[1]), "{lut:?}");
+        for (x, y) in points {
+            assert_eq!(lut[x as usize], y as u8);
+        }
+    }
+
+    #[test]
+    fn solarize_thresholds_at_range_ends() {
+        let image = all_levels();
+        assert_eq!(solarize_lut(0), invert_lut());
+        assert_eq!(apply_solarize(&image, 0).to_rgb8(), apply_inversion(&image).to_rgb8());
+        assert_eq!(apply_solarize(&image, 255).to_rgb8(), image.to_rgb8());
+    }
+
+    #[test]
+    fn hsv_brightness_keeps_red_saturated() {
+        let red = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([128, 0, 0])));
+        let hsv = apply_brightness_hsv(&red, 0.2).to_rgb8()[(0, 0)];
+        let rgb = apply_brightness(&red, 51).to_rgb8()[(0, 0)];
+        assert_eq!(rgb_to_hsv(hsv[0], hsv[1], hsv[2]).0, 0.0);
+        assert_eq!(rgb_to_hsv(hsv[0], hsv[1], hsv[2]).1, 1.0);
+        // Сдвиг RGB поднимает G и B, красный уходит в розовый
+        assert_eq!(rgb.0, [179, 51, 51]);
+        assert!(rgb_to_hsv(rgb[0], rgb[1], rgb[2]).1 < 0.75);
+    }
+
+    #[test]
+    fn srgb_round_trip_is_exact() {
+        for i in 0..=255u8 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(i)), i);
+        }
+    }
+
+    #[test]
+    fn exposure_zero_and_opposite_stops() {
+        let image = all_levels();
+        assert_eq!(apply_exposure(&image, 0.0), image);
+        // Средние тона: после +1 EV линейное значение не превышает 1
+        let mid_tones = DynamicImage::ImageLuma8(GrayImage::from_fn(121, 1, |x, _| Luma([40 + x as u8])));
+        let restored = apply_exposure(&apply_exposure(&mid_tones, 1.0), -1.0).to_luma8();
+        for (a, b) in restored.iter().zip(mid_tones.to_luma8().iter()) {
+            assert!(a.abs_diff(*b) <= 1, "{b} -> {a}");
+        }
+    }
+
+    #[test]
+    fn value_lut_keeps_hue() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([200, 100, 0])));
+        let darker = lut_from_fn(|v| v / 2.0);
+        assert_eq!(apply_lut(&image, &darker, LutTarget::Rgb).to_rgb8()[(0, 0)].0, [100, 50, 0]);
+        let pixel = apply_lut(&image, &darker, LutTarget::Value).to_rgb8()[(0, 0)];
+        assert_eq!(rgb_to_hsv(pixel[0], pixel[1], pixel[2]).0, rgb_to_hsv(200, 100, 0).0);
+        assert_eq!(pixel[0], 100);
+    }
+
+    #[test]
+    fn triangle_threshold_finds_knee_after_peak() {
+        let mut unimodal = [0u64; 256];
+        unimodal[10] = 1000;
+        unimodal[11..=100].fill(1);
+        assert_eq!(triangle_threshold(&unimodal), Some(11));
+        // Хвост слева от пика - гистограмма отражается
+        let mut mirrored = unimodal;
+        mirrored.reverse();
+        assert_eq!(triangle_threshold(&mirrored), Some(243));
+
+        let mut bimodal = [0u64; 256];
+        bimodal[50] = 1000;
+        bimodal[200] = 400;
+        assert_eq!(triangle_threshold(&bimodal), Some(51));
+        assert_eq!(triangle_threshold(&[0; 256]), None);
+    }
+
+    /// Левая половина 60, правая 190
+    fn two_levels() -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(8, 4, |x, _| Luma([if x < 4 { 60 } else { 190 }])))
+    }
+
+    #[test]
+    fn otsu_threshold_of_two_level_image() {
+        let (binary, threshold) = apply_otsu_threshold(&two_levels());
+        assert_eq!(threshold, Some(60));
+        let binary = binary.to_luma8();
+        assert!(binary.enumerate_pixels().all(|(x, _, p)| p[0] == if x < 4 { 0 } else { 255 }));
+    }
+
+    #[test]
+    fn single_tile_otsu_matches_global() {
+        let image = DynamicImage::ImageRgb8(noise_image(13, 9));
+        let (global, _) = apply_otsu_threshold(&image);
+        for interpolate in [false, true] {
+            assert_eq!(apply_tiled_otsu(&image, 1, interpolate).to_luma8(), global.to_luma8());
+        }
+    }
+
+    #[test]
+    fn uniform_tiles_inherit_global_threshold() {
+        // Каждый блок 4×2 однотонный, и все они берут глобальный порог 60
+        let image = two_levels();
+        let tiled = apply_tiled_otsu(&image, 2, false);
+        assert_eq!(tiled.to_luma8(), apply_otsu_threshold(&image).0.to_luma8());
+    }
+
+    #[test]
+    fn gradient_operators_peak_at_step_edge() {
+        // Вертикальная ступенька 0 | 100 между столбцами 3 и 4
+        let step = GrayImage::from_fn(8, 5, |x, _| Luma([if x < 4 { 0 } else { 100 }]));
+        let expected = [
+            (GradientOperator::Sobel, 400.0, vec![3, 4]),
+            (GradientOperator::Prewitt, 300.0, vec![3, 4]),
+            (GradientOperator::Scharr, 1600.0, vec![3, 4]),
+            (GradientOperator::Roberts, 100.0 * std::f32::consts::SQRT_2, vec![3]),
+        ];
+        for (operator, peak, columns) in expected {
+            let (gx, gy) = compute_gradient(&step, operator);
+            assert_eq!((gx.len(), gy.len()), (40, 40), "{}", operator.name());
+            for (i, (x, y)) in gx.iter().zip(&gy).enumerate() {
+                let magnitude = x.hypot(*y);
+                let column = i % 8;
+                let want = if columns.contains(&column) { peak } else { 0.0 };
+                assert!((magnitude - want).abs() < 1e-3, "{} в столбце {column}: {magnitude}", operator.name());
+            }
+        }
+    }
+
+    /// Столбцы, в которых после подавления немаксимумов остался отклик
+    fn ridge_columns(profile: &[u8]) -> Vec<usize> {
+        let width = profile.len();
+        let image = GrayImage::from_fn(width as u32, 5, |x, _| Luma([profile[x as usize]]));
+        let (gx, gy) = compute_gradient(&image, GradientOperator::Sobel);
+        let suppressed = non_max_suppression(width, 5, &gx, &gy);
+        let mut columns: Vec<usize> =
+            (0..suppressed.len()).filter(|&i| suppressed[i] > 0.0).map(|i| i % width).collect();
+        columns.sort_unstable();
+        columns.dedup();
+        columns
+    }
+
+    #[test]
+    fn non_max_suppression_keeps_single_ridge() {
+        // Плавный подъем: максимум градиента в столбце 4
+        assert_eq!(ridge_columns(&[0, 0, 0, 20, 60, 100, 120, 120, 120]), vec![4]);
+        // Ступенька дает два равных отклика, остается один
+        assert_eq!(ridge_columns(&[0, 0, 0, 0, 100, 100, 100, 100]).len(), 1);
+    }
+
+    #[test]
+    fn canny_handles_tiny_images() {
+        for (width, height) in [(1, 1), (1, 7), (7, 1), (2, 2), (3, 3)] {
+            let pixels = GrayImage::from_fn(width, height, |x, y| Luma([((x + y) * 90) as u8]));
+            let image = DynamicImage::ImageLuma8(pixels);
+            assert_eq!(apply_canny(&image, 1.4, 20, 50).dimensions(), (width, height));
+        }
+    }
+
+    #[test]
+    fn motion_blur_of_length_one_is_noop() {
+        let pixels = RgbaImage::from_fn(6, 6, |x, y| Rgba([(x * 40) as u8, (y * 40) as u8, 3, 200]));
+        let image = DynamicImage::ImageRgba8(pixels);
+        for angle in [0.0, 33.0, 90.0] {
+            assert_eq!(apply_motion_blur(&image, 1, angle), image);
+        }
+    }
+
+    #[test]
+    fn axis_motion_blur_matches_box_blur() {
+        // В одной строке (столбце) проход поперек не меняет пикселей
+        let row = DynamicImage::ImageRgb8(noise_image(20, 1));
+        let column = DynamicImage::ImageRgb8(noise_image(1, 20));
+        for radius in [1u32, 3, 7] {
+            let length = 2 * radius + 1;
+            assert_eq!(apply_motion_blur(&row, length, 0.0), apply_box_blur(&row, radius, 1));
+            assert_eq!(apply_motion_blur(&row, length, 180.0), apply_box_blur(&row, radius, 1));
+            assert_eq!(apply_motion_blur(&column, length, 90.0), apply_box_blur(&column, radius, 1));
+        }
+    }
+
+    /// Цвет единственного пикселя после поворота тона
+    fn rotated(rgb: [u8; 3], degrees: f32) -> [u8; 3] {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb(rgb)));
+        apply_hue_rotate(&image, degrees).to_rgb8()[(0, 0)].0
+    }
+
+    #[test]
+    fn hue_rotation_known_colors() {
+        assert_eq!(rotated([255, 0, 0], 120.0), [0, 255, 0]);
+        assert_eq!(rotated([255, 0, 0], -120.0), [0, 0, 255]);
+        assert_eq!(rotated([0, 0, 255], 180.0), [255, 255, 0]);
+        // Переход через 360°: 350° + 20° = 10°
+        let (r, g, b) = hsv_to_rgb(350.0, 1.0, 1.0);
+        let [r, g, b] = rotated([r, g, b], 20.0);
+        let hue = rgb_to_hsv(r, g, b).0;
+        assert!((hue - 10.0).abs() < 1.0, "{hue}");
+    }
+
+    #[test]
+    fn hue_rotation_by_full_turns_is_identity() {
+        let image = DynamicImage::ImageRgb8(noise_image(16, 16));
+        assert_eq!(apply_hue_rotate(&image, 0.0), image);
+        assert_eq!(apply_hue_rotate(&image, 360.0), image);
+        assert_eq!(apply_hue_rotate(&image, -720.0), image);
+    }
+
+    #[test]
+    fn hue_rotation_keeps_gray_pixels() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 2, |x, y| {
+            if y == 0 { Rgb([x as u8; 3]) } else { Rgb([x as u8, 0, 255 - x as u8]) }
+        }));
+        let result = apply_hue_rotate(&image, 77.0).to_rgb8();
+        for x in 0..256 {
+            assert_eq!(result[(x, 0)], Rgb([x as u8; 3]));
+        }
+    }
+
+    #[test]
+    fn grayscale_methods_on_known_colors() {
+        // Порядок: Rec601, Rec709, Average, Lightness, Red, Green, Blue
+        let cases = [
+            ([255, 0, 0], [76, 54, 85, 128, 255, 0, 0]),
+            ([10, 200, 60], [127, 149, 90, 105, 10, 200, 60]),
+            ([128, 128, 128], [128; 7]),
+        ];
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(3, 1, |x, _| Rgb(cases[x as usize].0)));
+        for (index, method) in GrayMethod::ALL.into_iter().enumerate() {
+            let gray = apply_grayscale(&image, method).to_luma8();
+            for (x, (rgb, expected)) in cases.iter().enumerate() {
+                assert_eq!(gray[(x as u32, 0)][0], expected[index], "{} для {rgb:?}", method.name());
+            }
+        }
+    }
+
+    #[test]
+    fn gray_world_neutralizes_cast() {
+        // Серый градиент под теплым освещением: G·0.9, B·0.6
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(101, 2, |x, _| {
+            let v = 20.0 + x as f32 * 2.0;
+            Rgb([v as u8, (v * 0.9).round() as u8, (v * 0.6).round() as u8])
+        }));
+        let (balanced, gains) = apply_gray_world_wb(&image);
+        assert!(gains[0] < 1.0 && gains[2] > 1.0, "{gains:?}");
+        for pixel in balanced.to_rgb8().pixels() {
+            let [r, g, b] = pixel.0;
+            assert!(r.abs_diff(g) <= 2 && g.abs_diff(b) <= 2, "{:?}", pixel.0);
+        }
+    }
+
+    #[test]
+    fn gray_world_handles_empty_channels() {
+        let red = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])));
+        let (_, gains) = apply_gray_world_wb(&red);
+        assert_eq!(gains[1..], [1.0, 1.0]);
+        assert!(gains.iter().all(|g| g.is_finite() && *g <= MAX_WB_GAIN));
+    }
+
+    #[test]
+    fn viridis_luminance_is_monotonic() {
+        let table = Colormap::Viridis.table();
+        for i in 1..256 {
+            assert!(table_luma(table[i]) >= table_luma(table[i - 1]), "уровень {i}");
+        }
+    }
+
+    #[test]
+    fn grayscale_colormap_is_identity_on_gray() {
+        let gray = DynamicImage::ImageLuma8(GrayImage::from_fn(256, 1, |x, _| Luma([x as u8])));
+        assert_eq!(apply_colormap(&gray, Colormap::Grayscale).to_rgb8(), gray.to_rgb8());
+    }
+
+    #[test]
+    fn quantize_reproduces_two_color_image() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(10, 4, |x, _| {
+            if x < 3 { Rgb([200, 30, 40]) } else { Rgb([10, 90, 250]) }
+        }));
+        let (result, palette) = apply_quantize(&image, 2);
+        assert_eq!(result, image);
+        assert_eq!(palette, vec![([200, 30, 40], 0.3), ([10, 90, 250], 0.7)]);
+    }
+
+    #[test]
+    fn quantize_maps_to_palette_colors() {
+        let image = DynamicImage::ImageRgb8(noise_image(24, 24));
+        let (result, palette) = apply_quantize(&image, 8);
+        assert!(palette.len() <= 8);
+        assert!((palette.iter().map(|(_, share)| share).sum::<f32>() - 1.0).abs() < 1e-4);
+        assert!(result.to_rgb8().pixels().all(|p| palette.iter().any(|(color, _)| *color == p.0)));
+    }
+
+    #[test]
+    fn value_inversion_round_trips() {
+        // При V > 1/2 первая инверсия сжимает каналы в меньшее число уровней,
+        // поэтому возврат в пределах ±1 проверяется только для темных цветов;
+        // серые уровни возвращаются точно при любом V
+        let grays = DynamicImage::ImageLuma8(GrayImage::from_fn(256, 1, |x, _| Luma([x as u8])));
+        assert_eq!(apply_value_inversion(&apply_value_inversion(&grays)).to_rgb8(), grays.to_rgb8());
+        for image in [all_levels(), DynamicImage::ImageRgb8(noise_image(32, 32))] {
+            let source = image.to_rgb8();
+            let twice = apply_value_inversion(&apply_value_inversion(&image)).to_rgb8();
+            for (before, after) in source.pixels().zip(twice.pixels()) {
+                if before.0.into_iter().max().unwrap() <= 127 {
+                    assert!((0..3).all(|c| before[c].abs_diff(after[c]) <= 1), "{:?} -> {:?}", before, after);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn value_inversion_keeps_hue_and_saturation() {
+        // Голубое небо остается голубым, а не становится оранжевым:
+        // V = 120/255 переходит в 135/255, каналы умножаются на 135/120
+        let sky = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([40, 80, 120])));
+        let pixel = apply_value_inversion(&sky).to_rgb8()[(0, 0)];
+        assert_eq!(pixel.0, [45, 90, 135]);
+        let (h, s, _) = rgb_to_hsv(pixel[0], pixel[1], pixel[2]);
+        let (h0, s0, _) = rgb_to_hsv(40, 80, 120);
+        assert!((h - h0).abs() < 1e-3 && (s - s0).abs() < 1e-6);
+        // Насыщенный цвет с V = 1 уходит в черный
+        let red = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([255, 0, 0])));
+        assert_eq!(apply_value_inversion(&red).to_rgb8()[(0, 0)].0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn hue_replace_wraps_around_red() {
+        let hues = [355.0, 5.0, 30.0, 17.0];
+        let mut image = RgbImage::from_fn(5, 1, |x, _| {
+            let (r, g, b) = hsv_to_rgb(hues.get(x as usize).copied().unwrap_or(0.0), 1.0, 1.0);
+            Rgb([r, g, b])
+        });
+        // Последний пиксель - серый
+        image.put_pixel(4, 0, Rgb([140, 140, 140]));
+        let image = DynamicImage::ImageRgb8(image);
+
+        // Красные 350°..10° переносятся в зеленый, растушевка 10°
+        let result = apply_hue_replace(&image, 0.0, 10.0, 120.0, 10.0).to_rgb8();
+        let hue = |x: u32| {
+            let p = result[(x, 0)];
+            rgb_to_hsv(p[0], p[1], p[2]).0
+        };
+        assert!((hue(0) - 115.0).abs() < 1.0, "{}", hue(0));
+        assert!((hue(1) - 125.0).abs() < 1.0, "{}", hue(1));
+        assert_eq!(result[(2, 0)], image.to_rgb8()[(2, 0)]);
+        // В зоне растушевки тон сдвигается частично: t = 0.3, вес 0.216
+        assert!((hue(3) - (17.0 + 120.0 * 0.216)).abs() < 1.0, "{}", hue(3));
+        assert_eq!(result[(4, 0)].0, [140, 140, 140]);
+    }
+
+    #[test]
+    fn black_to_white_gradient_map_is_grayscale() {
+        let image = DynamicImage::ImageRgb8(noise_image(32, 32));
+        let expected = apply_grayscale(&image, GrayMethod::Rec601).to_rgb8();
+        let stops = [(0.0, [0, 0, 0]), (1.0, [255, 255, 255])];
+        assert_eq!(apply_gradient_map(&image, &stops).to_rgb8(), expected);
+        // Средняя опора серого цвета в своем же положении ничего не меняет
+        let with_midpoint = [(0.0, [0, 0, 0]), (0.5, [128, 128, 128]), (1.0, [255, 255, 255])];
+        let levels = DynamicImage::ImageLuma8(GrayImage::from_fn(256, 1, |x, _| Luma([x as u8])));
+        assert_eq!(apply_gradient_map(&levels, &with_midpoint).to_rgb8(), levels.to_rgb8());
+    }
+
+    #[test]
+    fn pixelate_checkerboard_gives_flat_gray() {
+        // Клетки 4×4, блок 8: в каждом блоке поровну черного и белого
+        let board = DynamicImage::ImageRgb8(RgbImage::from_fn(16, 16, |x, y| {
+            Rgb([if (x / 4 + y / 4) % 2 == 0 { 0 } else { 255 }; 3])
+        }));
+        let result = apply_pixelate(&board, 8).to_rgb8();
+        assert!(result.pixels().all(|p| p.0 == [128; 3]), "{:?}", result.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn pixelate_partial_cells_and_block_one() {
+        let image = DynamicImage::ImageRgb8(noise_image(10, 7));
+        assert_eq!(apply_pixelate(&image, 1), image);
+        // Правый нижний блок 4×4 занимает только пиксели 8..10 × 4..7
+        let source = image.to_rgb8();
+        let result = apply_pixelate(&image, 4).to_rgb8();
+        let cell: Vec<[u8; 3]> = (4..7).flat_map(|y| (8..10).map(move |x| (x, y))).map(|p| source[p].0).collect();
+        let mean = |c: usize| ((cell.iter().map(|p| p[c] as u32).sum::<u32>() + 3) / 6) as u8;
+        assert_eq!(result[(9, 6)].0, [mean(0), mean(1), mean(2)]);
+    }
+
+    #[test]
+    fn blend_over_matches_hand_computed_values() {
+        let dst = [200, 100, 0];
+        let src = [0, 200, 255];
+        assert_eq!(blend_over(dst, src, 0.0), dst);
+        assert_eq!(blend_over(dst, src, 1.0), src);
+        // 200 - 200*0.25 = 150, 100 + 100*0.25 = 125, 255*0.25 = 63.75
+        assert_eq!(blend_over(dst, src, 0.25), [150, 125, 64]);
+        assert_eq!(blend_over(dst, src, 0.5), [100, 150, 128]);
+        assert_eq!(blend_over(dst, src, 0.75), [50, 175, 191]);
+    }
+
+    #[test]
+    fn watermark_stays_inside_and_zero_opacity_is_noop() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 20, Rgb([0, 0, 0])));
+        let mut watermark = Watermark {
+            text: "Иванов, гр. 13".to_string(),
+            size: 500.0,
+            opacity: 0.0,
+            color: [255, 255, 255],
+            position: WatermarkPosition::BottomRight,
+        };
+        assert_eq!(apply_watermark(&image, &watermark), image);
+
+        // Огромная надпись уменьшается до ширины кадра, но видна при любом положении
+        watermark.opacity = 1.0;
+        for position in WatermarkPosition::ALL {
+            watermark.position = position;
+            let result = apply_watermark(&image, &watermark).to_rgb8();
+            assert_eq!(result.dimensions(), (40, 20));
+            assert!(result.pixels().any(|p| p.0 != [0; 3]), "{:?}", position);
+        }
+    }
+
+    #[test]
+    fn single_white_pixel_dilates_to_element_and_erodes_away() {
+        let mut mask = GrayImage::new(11, 11);
+        mask.put_pixel(5, 5, Luma([255]));
+        let mask = DynamicImage::ImageLuma8(mask);
+        for shape in ElementShape::ALL {
+            let element = StructuringElement { radius: 3, shape };
+            let dilated = apply_dilate(&mask, element).to_luma8();
+            for (x, y, pixel) in dilated.enumerate_pixels() {
+                let (dx, dy) = (x.abs_diff(5), y.abs_diff(5));
+                let inside = match shape {
+                    ElementShape::Square => dx <= 3 && dy <= 3,
+                    ElementShape::Disk => dx * dx + dy * dy <= 9,
+                };
+                assert_eq!(pixel[0], if inside { 255 } else { 0 }, "{:?} ({}, {})", shape, x, y);
+            }
+            assert!(apply_erode(&mask, element).to_luma8().pixels().all(|p| p[0] == 0));
+        }
+    }
+
+    #[test]
+    fn opening_and_closing_are_idempotent_on_otsu_mask() {
+        let (mask, _) = apply_otsu_threshold(&DynamicImage::ImageRgb8(noise_image(40, 30)));
+        assert!(matches!(mask, DynamicImage::ImageLuma8(_)));
+        for shape in ElementShape::ALL {
+            let element = StructuringElement { radius: 2, shape };
+            let opened = apply_open(&mask, element);
+            assert_eq!(apply_open(&opened, element), opened, "{:?}", shape);
+            let closed = apply_close(&mask, element);
+            assert_eq!(apply_close(&closed, element), closed, "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn thick_plus_skeletonizes_to_connected_cross() {
+        // Крест из полос толщиной 5 через центр (15, 15)
+        let plus = GrayImage::from_fn(31, 31, |x, y| {
+            let bar = |a: u32, b: u32| a.abs_diff(15) <= 2 && (3..28).contains(&b);
+            Luma([if bar(x, y) || bar(y, x) { 255 } else { 0 }])
+        });
+        let (skeleton, iterations) = apply_skeletonize(&DynamicImage::ImageLuma8(plus.clone()));
+        let skeleton = skeleton.to_luma8();
+        assert!(iterations > 0);
+        let set = |x: i64, y: i64| x >= 0 && y >= 0 && x < 31 && y < 31 && skeleton[(x as u32, y as u32)][0] == 255;
+        let points: Vec<(i64, i64)> =
+            (0..31).flat_map(|y| (0..31).map(move |x| (x, y))).filter(|&(x, y)| set(x, y)).collect();
+        // Скелет лежит внутри креста, на средних линиях полос, и не толще пикселя
+        for &(x, y) in &points {
+            assert!(plus[(x as u32, y as u32)][0] == 255);
+            assert!(x.abs_diff(15) <= 1 || y.abs_diff(15) <= 1, "({}, {})", x, y);
+            assert!(!(set(x + 1, y) && set(x, y + 1) && set(x + 1, y + 1)), "({}, {})", x, y);
+        }
+        // Все четыре луча тянутся вдоль полос, укорачиваясь лишь на полуширину
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            assert!((-1..=1).any(|t| set(15 + dx * 9 + dy * t, 15 + dy * 9 + dx * t)), "{:?}", (dx, dy));
+        }
+        // 8-связность: обход из первой точки достигает всех
+        let mut seen = vec![points[0]];
+        let mut index = 0;
+        while index < seen.len() {
+            let (x, y) = seen[index];
+            for (nx, ny) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy))) {
+                if set(nx, ny) && !seen.contains(&(nx, ny)) {
+                    seen.push((nx, ny));
+                }
+            }
+            index += 1;
+        }
+        assert_eq!(seen.len(), points.len());
+    }
+
+    #[test]
+    fn fill_holes_closes_donut_and_keeps_open_c() {
+        let ring = |x: u32, y: u32| {
+            let d = (x as i32 - 15).pow(2) + (y as i32 - 15).pow(2);
+            (36..=144).contains(&d)
+        };
+        let disk = GrayImage::from_fn(31, 31, |x, y| {
+            Luma([if (x as i32 - 15).pow(2) + (y as i32 - 15).pow(2) <= 144 { 255 } else { 0 }])
+        });
+        let donut = GrayImage::from_fn(31, 31, |x, y| Luma([if ring(x, y) { 255 } else { 0 }]));
+        assert_eq!(apply_fill_holes(&DynamicImage::ImageLuma8(donut)).to_luma8(), disk);
+
+        // Разрез кольца до правого края соединяет отверстие с фоном
+        let c_shape = DynamicImage::ImageLuma8(GrayImage::from_fn(31, 31, |x, y| {
+            Luma([if ring(x, y) && !(x > 15 && y.abs_diff(15) <= 1) { 255 } else { 0 }])
+        }));
+        assert_eq!(apply_fill_holes(&c_shape), c_shape);
+    }
+
+    #[test]
+    fn hough_recovers_two_known_line_angles() {
+        let mut img = RgbImage::new(120, 120);
+        for (rho, theta) in [(40.0, 30.0), (30.0, 110.0)] {
+            draw_polar_line(&mut img, &HoughLine { rho, theta, votes: 0 }, [255, 255, 255]);
+        }
+        let image = DynamicImage::ImageRgb8(img);
+        let (_, lines) = apply_hough_lines(&image, 1.0, 30, 2, HoughOutput::Lines, [255, 0, 0]);
+        let mut thetas: Vec<f32> = lines.iter().map(|line| line.theta).collect();
+        thetas.sort_by(f32::total_cmp);
+        assert_eq!(thetas.len(), 2);
+        assert!((thetas[0] - 30.0).abs() <= 1.0 && (thetas[1] - 110.0).abs() <= 1.0, "{:?}", thetas);
+
+        // Аккумулятор: θ по столбцам, ρ от -max_rho до max_rho по строкам
+        let (accumulator, _) = apply_hough_lines(&image, 1.0, 30, 2, HoughOutput::Accumulator, [255, 0, 0]);
+        let max_rho = (120f64).hypot(120.0).ceil() as u32;
+        assert_eq!(accumulator.dimensions(), (180, 2 * max_rho + 1));
+    }
+
+    #[test]
+    fn difference_of_identical_images_is_black() {
+        let image = DynamicImage::ImageRgb8(noise_image(16, 8));
+        let same = apply_difference(&image, &image, 4.0, DifferenceMode::Absolute).unwrap().to_rgb8();
+        assert!(same.pixels().all(|p| p.0 == [0; 3]));
+        let signed = apply_difference(&image, &image, 4.0, DifferenceMode::Signed).unwrap().to_rgb8();
+        assert!(signed.pixels().all(|p| p.0 == [128; 3]));
+
+        let mut changed = image.to_rgb8();
+        let original = changed[(3, 5)].0;
+        changed[(3, 5)].0 = original.map(|v| v.saturating_add(10));
+        let expected = original.map(|v| v.saturating_add(10) - v);
+        let changed = DynamicImage::ImageRgb8(changed);
+        let diff = apply_difference(&changed, &image, 1.0, DifferenceMode::Absolute).unwrap().to_rgb8();
+        assert!(diff.enumerate_pixels().all(|(x, y, p)| p.0 == if (x, y) == (3, 5) { expected } else { [0; 3] }));
+        // Усиление умножает разность, знаковый режим смещает ее к серому
+        let amplified = apply_difference(&changed, &image, 2.0, DifferenceMode::Absolute).unwrap().to_rgb8();
+        assert_eq!(amplified[(3, 5)].0, expected.map(|v| v * 2));
+        let signed = apply_difference(&image, &changed, 1.0, DifferenceMode::Signed).unwrap().to_rgb8();
+        assert_eq!(signed[(3, 5)].0, expected.map(|v| 128 - v));
+
+        let other = DynamicImage::ImageRgb8(noise_image(8, 16));
+        assert!(apply_difference(&image, &other, 1.0, DifferenceMode::Absolute).is_none());
+    }
+
+    #[test]
+    fn quality_metrics_of_constant_offset() {
+        // Значения 0..240 не переполняются при сдвиге на 10
+        let mut base = noise_image(32, 24);
+        base.iter_mut().for_each(|v| *v %= 241);
+        let shifted = RgbImage::from_fn(32, 24, |x, y| Rgb(base[(x, y)].0.map(|v| v + 10)));
+        let (base, shifted) = (DynamicImage::ImageRgb8(base), DynamicImage::ImageRgb8(shifted));
+
+        let same = compute_quality_metrics(&base, &base).unwrap();
+        assert_eq!(same.mse, 0.0);
+        assert_eq!(same.psnr, f64::INFINITY);
+        assert!((same.ssim - 1.0).abs() < 1e-6, "{}", same.ssim);
+
+        // MSE = 10², PSNR = 10·lg(255² / 10²) = 20·lg 25.5
+        let metrics = compute_quality_metrics(&base, &shifted).unwrap();
+        assert!((metrics.mse - 100.0).abs() < 1e-9, "{}", metrics.mse);
+        assert!((metrics.psnr - 20.0 * 25.5f64.log10()).abs() < 1e-9, "{}", metrics.psnr);
+        // Сдвиг яркости не меняет структуру, SSIM близок к единице, но меньше ее
+        assert!(metrics.ssim < 1.0 && metrics.ssim > 0.9, "{}", metrics.ssim);
+
+        let other = DynamicImage::ImageRgb8(noise_image(24, 32));
+        assert!(compute_quality_metrics(&base, &other).is_none());
+    }
+
+    #[test]
+    fn stats_of_tiny_gray_image_show_single_luma_row() {
+        let gray = GrayImage::from_raw(2, 2, vec![10, 20, 30, 40]).unwrap();
+        let stats = compute_stats(&DynamicImage::ImageLuma8(gray));
+        assert!(stats.grayscale);
+        let channels = stats.channels();
+        assert_eq!(channels.len(), 1);
+        let (name, luma) = channels[0];
+        assert_eq!(name, "Яркость");
+        // Среднее 25, дисперсия (15² + 5² + 5² + 15²) / 4 = 125, четыре равновероятных уровня - 2 бита
+        assert_eq!((luma.mean, luma.min, luma.max, luma.median), (25.0, 10, 40, 20));
+        assert!((luma.std_dev - 125f64.sqrt()).abs() < 1e-9);
+        assert!((luma.entropy - 2.0).abs() < 1e-9);
+        assert_eq!(stats.to_table("Исходное").lines().count(), 3);
+    }
+
+    #[test]
+    fn stats_of_tiny_color_image_per_channel() {
+        let rgb = RgbImage::from_raw(2, 1, vec![255, 0, 0, 255, 0, 255]).unwrap();
+        let stats = compute_stats(&DynamicImage::ImageRgb8(rgb));
+        assert!(!stats.grayscale);
+        let names: Vec<&str> = stats.channels().iter().map(|&(name, _)| name).collect();
+        assert_eq!(names, ["R", "G", "B", "Яркость"]);
+        assert_eq!((stats.red.mean, stats.red.std_dev, stats.red.entropy), (255.0, 0.0, 0.0));
+        assert_eq!((stats.green.min, stats.green.max), (0, 0));
+        assert_eq!((stats.blue.mean, stats.blue.std_dev, stats.blue.median), (127.5, 127.5, 0));
+        assert!((stats.blue.entropy - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn histogram_csv_and_render_without_gui() {
+        let stats = compute_stats(&two_levels());
+        let csv = stats.histogram_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 257);
+        assert_eq!(lines[0], "bin,luma,r,g,b");
+        assert_eq!(lines[1 + 60], "60,16,16,16,16");
+        assert_eq!(lines[1 + 190], "190,16,16,16,16");
+        assert_eq!(lines[1 + 100], "100,0,0,0,0");
+
+        // Корзина - 4 столбца шириной; оба уровня одинаковой высоты во весь график
+        let plot = stats.render_histogram(false, Some(100));
+        assert_eq!(plot.dimensions(), (1024, 400));
+        assert_eq!(plot[(60 * 4, 0)].0, [150, 150, 150]);
+        assert_eq!(plot[(190 * 4 + 3, 399)].0, [150, 150, 150]);
+        assert_eq!(plot[(10 * 4, 399)].0, [24, 24, 24]);
+        assert!((0..400).all(|y| plot[(100 * 4 + 2, y)].0 == [255, 215, 0]));
+        assert_eq!(stats.render_histogram(true, None).dimensions(), (1024, 400));
+    }
+
+    #[test]
+    fn dominant_colors_of_three_blocks() {
+        // Полосы шириной 50, 30 и 20 из 100 столбцов
+        let colors = [[200, 30, 40], [20, 120, 220], [240, 240, 60]];
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(100, 40, |x, _| {
+            Rgb(colors[if x < 50 { 0 } else if x < 80 { 1 } else { 2 }])
+        }));
+        let palette = dominant_colors(&image, 3);
+        assert_eq!(palette.len(), 3);
+        for ((color, share), (expected, expected_share)) in palette.iter().zip(colors.iter().zip([0.5, 0.3, 0.2])) {
+            assert_eq!(color, expected);
+            assert!((share - expected_share).abs() < 1e-6, "{:?}: {}", color, share);
+        }
+        assert_eq!(dominant_colors(&image, 3), palette);
+    }
+
+    #[test]
+    fn nearest_upscale_is_blocky_and_box_halving_averages() {
+        let tiny = DynamicImage::ImageRgb8(noise_image(3, 2));
+        let source = tiny.to_rgb8();
+        let big = apply_resize(&tiny, 12, 8, ResizeFilter::Nearest).unwrap().to_rgb8();
+        assert_eq!(big.dimensions(), (12, 8));
+        assert!(big.enumerate_pixels().all(|(x, y, p)| p == source.get_pixel(x / 4, y / 4)));
+
+        let image = DynamicImage::ImageRgb8(noise_image(10, 6));
+        let source = image.to_rgb8();
+        let half = apply_resize(&image, 5, 3, ResizeFilter::Box).unwrap().to_rgb8();
+        for (x, y, pixel) in half.enumerate_pixels() {
+            let block = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| source[(2 * x + dx, 2 * y + dy)].0);
+            let mean: [u8; 3] = std::array::from_fn(|c| {
+                (block.iter().map(|p| p[c] as f32).sum::<f32>() / 4.0).round() as u8
+            });
+            assert_eq!(pixel.0, mean, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn resize_rejects_zero_and_huge_dimensions() {
+        let image = DynamicImage::ImageRgb8(noise_image(4, 4));
+        assert!(apply_resize(&image, 0, 4, ResizeFilter::Bilinear).is_err());
+        assert!(apply_resize(&image, 4, MAX_DIMENSION + 1, ResizeFilter::Lanczos3).is_err());
+        let resized = apply_resize(&image, 7, 3, ResizeFilter::CatmullRom).unwrap();
+        assert_eq!(resized.dimensions(), (7, 3));
+    }
+
+    #[test]
+    fn tiling_places_pixels_exactly() {
+        // Несимметричный образец 3×2: все пиксели различны
+        let sample = RgbImage::from_fn(3, 2, |x, y| Rgb([(10 * x + 100 * y) as u8, 0, 0]));
+        let image = DynamicImage::ImageRgb8(sample.clone());
+        assert_eq!(apply_tile(&image, 1, 1, true).unwrap(), image);
+
+        let tiled = apply_tile(&image, 3, 2, false).unwrap().to_rgb8();
+        assert_eq!(tiled.dimensions(), (9, 4));
+        assert!(tiled.enumerate_pixels().all(|(x, y, p)| p == &sample[(x % 3, y % 2)]));
+
+        // Вторая плитка по каждой оси отражена, третья снова прямая
+        let mirrored = apply_tile(&image, 3, 2, true).unwrap().to_rgb8();
+        assert_eq!(mirrored[(3, 0)], sample[(2, 0)]);
+        assert_eq!(mirrored[(5, 0)], sample[(0, 0)]);
+        assert_eq!(mirrored[(6, 0)], sample[(0, 0)]);
+        assert_eq!(mirrored[(4, 2)], sample[(1, 1)]);
+        assert_eq!(mirrored[(7, 3)], sample[(1, 0)]);
+
+        let wide = DynamicImage::ImageRgb8(RgbImage::new(MAX_TILED_DIMENSION / 2 + 1, 1));
+        assert!(apply_tile(&wide, 2, 1, false).is_err());
+    }
+
+    #[test]
+    fn blend_subtract_self_and_multiply_by_white() {
+        let image = DynamicImage::ImageRgb8(noise_image(12, 9));
+        let black = apply_blend(&image, &image, BlendMode::Subtract, 1.0, true).to_rgb8();
+        assert!(black.pixels().all(|p| p.0 == [0; 3]));
+        // Белое B другого размера: и растяжение, и обрезка по центру ничего не меняют
+        let white = DynamicImage::ImageRgb8(RgbImage::from_pixel(20, 15, Rgb([255; 3])));
+        for resize in [true, false] {
+            assert_eq!(apply_blend(&image, &white, BlendMode::Multiply, 1.0, resize), image);
+        }
+        // Непрозрачность 0 - без изменений, сложение зажимается в 255
+        assert_eq!(apply_blend(&image, &white, BlendMode::Difference, 0.0, true), image);
+        let added = apply_blend(&image, &white, BlendMode::Add, 1.0, true).to_rgb8();
+        assert!(added.pixels().all(|p| p.0 == [255; 3]));
+    }
+}